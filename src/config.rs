@@ -10,6 +10,7 @@ use std::fmt::Formatter;
 use bdk::bitcoin::secp256k1::Secp256k1;
 
 use crate::e::{ErrorKind, S5Error};
+use crate::wallet::descriptor;
 
 
 pub const DEFAULT: &str = "default";
@@ -55,8 +56,9 @@ impl WalletConfig {
     socks5: Option<String>,
     db_path: Option<String>
   ) -> Result<Self, S5Error> {
-    let deposit_desc: &str = &descriptor.replace("/*", "/0/*");
-    let change_desc: &str = &descriptor.replace("/*", "/1/*");
+    let (deposit_desc, change_desc) = split_descriptor(descriptor)?;
+    let deposit_desc: &str = &deposit_desc;
+    let change_desc: &str = &change_desc;
     let network = if <&str>::clone(&descriptor).contains("xpub")
       || <&str>::clone(&descriptor).contains("xprv")
     {
@@ -176,8 +178,9 @@ impl WalletConfig {
   }
 
   pub fn new_offline(descriptor: &str, db_path: Option<String>) -> Result<Self, S5Error> {
-    let deposit_desc: &str = &descriptor.replace("/*", "/0/*");
-    let change_desc: &str = &descriptor.replace("/*", "/1/*");
+    let (deposit_desc, change_desc) = split_descriptor(descriptor)?;
+    let deposit_desc: &str = &deposit_desc;
+    let change_desc: &str = &change_desc;
     let network = if <&str>::clone(&descriptor).contains("xpub")
       || <&str>::clone(&descriptor).contains("xprv")
     {
@@ -196,6 +199,87 @@ impl WalletConfig {
   }
 }
 
+/// Splits a descriptor into its deposit (external) and change (internal) keychain descriptors.
+/// Accepts:
+/// - two descriptors separated by whitespace or a newline: deposit first, then change
+/// - BIP389 multipath descriptors i.e. xpub/<0;1>/*
+/// - descriptors ending in /0/* which get /1/* for change
+/// - descriptors ending in /* which get /0/* and /1/*
+///
+/// Keys of a descriptor can mix these forms, each key is split on its own.
+/// Any checksum is verified and then dropped since the derived descriptors no longer match it.
+pub fn split_descriptor(descriptor: &str) -> Result<(String, String), S5Error> {
+  let parts: Vec<&str> = descriptor.split_whitespace().collect();
+  match parts.len() {
+    0 => return Err(S5Error::new(ErrorKind::Input, "Empty Descriptor")),
+    1 => (),
+    2 => {
+      return Ok((
+        descriptor::verify(parts[0])?.to_string(),
+        descriptor::verify(parts[1])?.to_string(),
+      ))
+    }
+    _ => return Err(S5Error::new(ErrorKind::Input, "Expected At Most Two Descriptors")),
+  };
+  let descriptor = descriptor::verify(parts[0])?;
+
+  let mut deposit = String::new();
+  let mut change = String::new();
+  let mut rest = descriptor;
+  while let Some(wildcard) = rest.find("/*") {
+    let before = &rest[..wildcard];
+    if before.ends_with('>') {
+      // multipath keys are split below
+      deposit.push_str(before);
+      change.push_str(before);
+    } else {
+      match before.strip_suffix("/0") {
+        Some(path) => {
+          deposit.push_str(before);
+          change.push_str(path);
+          change.push_str("/1");
+        }
+        None => {
+          deposit.push_str(before);
+          deposit.push_str("/0");
+          change.push_str(before);
+          change.push_str("/1");
+        }
+      }
+    }
+    deposit.push_str("/*");
+    change.push_str("/*");
+    rest = &rest[wildcard + 2..];
+  }
+  deposit.push_str(rest);
+  change.push_str(rest);
+  Ok((multipath(&deposit, 0)?, multipath(&change, 1)?))
+}
+
+/// Replaces every BIP389 multipath segment <a;b> with the path at index.
+fn multipath(descriptor: &str, index: usize) -> Result<String, S5Error> {
+  let mut result = String::new();
+  let mut rest = descriptor;
+  while let Some(start) = rest.find('<') {
+    result.push_str(&rest[..start]);
+    let end = match rest[start..].find('>') {
+      Some(end) => start + end,
+      None => return Err(S5Error::new(ErrorKind::Input, "Invalid Multipath Descriptor")),
+    };
+    let paths: Vec<&str> = rest[start + 1..end].split(';').collect();
+    if paths.len() != 2 || paths.iter().any(|path| path.is_empty()) {
+      return Err(S5Error::new(
+        ErrorKind::Input,
+        "Multipath Descriptor Must Have Two Paths",
+      ));
+    }
+    result.push_str(paths[index]);
+    rest = &rest[end + 1..];
+  }
+  result.push_str(rest);
+  Ok(result)
+}
+
 pub fn create_blockchain_client(config: AnyBlockchainConfig) -> Result<AnyBlockchain, S5Error> {
   match config {
     AnyBlockchainConfig::Electrum(conf) => {
@@ -325,4 +409,47 @@ mod tests {
       .unwrap();
     println!("{:#?}", config_error);
   }
+
+  #[test]
+  fn test_split_descriptor() {
+    let xkey = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
+    let deposit = format!("wpkh({}/0/*)", xkey);
+    let change = format!("wpkh({}/1/*)", xkey);
+
+    let (d, c) = split_descriptor(&format!("wpkh({}/*)", xkey)).unwrap();
+    assert_eq!((d, c), (deposit.clone(), change.clone()));
+    let (d, c) = split_descriptor(&deposit).unwrap();
+    assert_eq!((d, c), (deposit.clone(), change.clone()));
+    let (d, c) = split_descriptor(&format!("wpkh({}/<0;1>/*)", xkey)).unwrap();
+    assert_eq!((d, c), (deposit.clone(), change.clone()));
+    let (d, c) = split_descriptor(&format!("{}\n{}", deposit, change)).unwrap();
+    assert_eq!((d, c), (deposit.clone(), change.clone()));
+    let checksum = descriptor::checksum(&format!("wpkh({}/*)", xkey)).unwrap();
+    let (d, c) = split_descriptor(&format!("wpkh({}/*)#{}", xkey, checksum)).unwrap();
+    assert_eq!((d, c), (deposit, change));
+    assert_eq!(
+      split_descriptor(&format!("wpkh({}/*)#8x2yf6ku", xkey)).err().unwrap().message,
+      "Invalid Descriptor Checksum"
+    );
+
+    let multi = format!("wsh(multi(2,{}/0/*,{}/*))", xkey, xkey);
+    let (_, c) = split_descriptor(&multi).unwrap();
+    assert_eq!(c, format!("wsh(multi(2,{}/1/*,{}/1/*))", xkey, xkey));
+    let multi = format!("wsh(multi(2,{}/<0;1>/*,{}/<2;3>/*))", xkey, xkey);
+    let (d, c) = split_descriptor(&multi).unwrap();
+    assert_eq!(d, format!("wsh(multi(2,{}/0/*,{}/2/*))", xkey, xkey));
+    assert_eq!(c, format!("wsh(multi(2,{}/1/*,{}/3/*))", xkey, xkey));
+
+    let mixed = format!("wsh(multi(2,{}/<0;1>/*,{}/*))", xkey, xkey);
+    let (d, c) = split_descriptor(&mixed).unwrap();
+    assert_eq!(d, format!("wsh(multi(2,{}/0/*,{}/0/*))", xkey, xkey));
+    assert_eq!(c, format!("wsh(multi(2,{}/1/*,{}/1/*))", xkey, xkey));
+
+    assert!(split_descriptor(&format!("wpkh({}/<0;1;2>/*)", xkey)).is_err());
+    assert!(split_descriptor(&format!("wpkh({}/<0;1/*)", xkey)).is_err());
+    assert!(split_descriptor("a b c").is_err());
+
+    let config = WalletConfig::new_offline(&format!("wpkh({}/0/*)", xkey), None).unwrap();
+    assert_eq!(config.change_desc, format!("wpkh({}/1/*)", xkey));
+  }
 }
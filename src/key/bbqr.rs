@@ -0,0 +1,538 @@
+use crate::e::{ErrorKind, S5Error};
+use crate::key::encoding::Encoding;
+use std::collections::BTreeMap;
+
+/// Every BBQr part starts with B$, the encoding, the file type, the number of parts and the part index (base 36).
+const HEADER_LENGTH: usize = 8;
+/// Largest number of parts, ZZ in base 36.
+pub const MAX_PARTS: usize = 1295;
+/// Compression window of BBQr (wbits 10), so that signing devices can inflate with 1KB of memory.
+const WINDOW: usize = 1024;
+/// Most earlier positions compared when looking for a match, as zlib does to bound compression time.
+const MAX_CHAIN: usize = 128;
+/// Largest file inflated from a Z encoded BBQr, well above any PSBT a signing device produces.
+const MAX_INFLATED_LENGTH: usize = 4_000_000;
+
+/// Length and distance tables of DEFLATE (RFC 1951): (base, extra bits).
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0), (11, 1), (13, 1),
+    (15, 1), (17, 1), (19, 2), (23, 2), (27, 2), (31, 2), (35, 3), (43, 3), (51, 3), (59, 3),
+    (67, 4), (83, 4), (99, 4), (115, 4), (131, 5), (163, 5), (195, 5), (227, 5), (258, 0),
+];
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0), (2, 0), (3, 0), (4, 0), (5, 1), (7, 1), (9, 2), (13, 2), (17, 3), (25, 3),
+    (33, 4), (49, 4), (65, 5), (97, 5), (129, 6), (193, 6), (257, 7), (385, 7), (513, 8), (769, 8),
+    (1025, 9), (1537, 9), (2049, 10), (3073, 10), (4097, 11), (6145, 11), (8193, 12), (12289, 12),
+    (16385, 13), (24577, 13),
+];
+/// Order of the code length code lengths in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Content of a BBQr, given by the file type in its header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileType {
+    Psbt,
+    Transaction,
+    Json,
+    Cbor,
+    Binary,
+    Unicode,
+}
+impl FileType {
+    pub fn code(&self) -> char {
+        match self {
+            FileType::Psbt => 'P',
+            FileType::Transaction => 'T',
+            FileType::Json => 'J',
+            FileType::Cbor => 'C',
+            FileType::Binary => 'B',
+            FileType::Unicode => 'U',
+        }
+    }
+
+    fn from_code(code: char) -> Result<FileType, S5Error> {
+        match code {
+            'P' => Ok(FileType::Psbt),
+            'T' => Ok(FileType::Transaction),
+            'J' => Ok(FileType::Json),
+            'C' => Ok(FileType::Cbor),
+            'B' => Ok(FileType::Binary),
+            'U' => Ok(FileType::Unicode),
+            _ => Err(S5Error::new(ErrorKind::Input, "Unsupported BBQr File Type")),
+        }
+    }
+}
+
+/// Encoding of the data of a BBQr: H (hex), 2 (base32) or Z (compressed, then base32).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BbqrEncoding {
+    Hex,
+    Base32,
+    Zlib,
+}
+impl BbqrEncoding {
+    pub fn from_str(encoding: &str) -> BbqrEncoding {
+        match encoding.to_uppercase().as_str() {
+            "H" | "HEX" => BbqrEncoding::Hex,
+            "2" | "BASE32" => BbqrEncoding::Base32,
+            _ => BbqrEncoding::Zlib,
+        }
+    }
+
+    pub fn code(&self) -> char {
+        match self {
+            BbqrEncoding::Hex => 'H',
+            BbqrEncoding::Base32 => '2',
+            BbqrEncoding::Zlib => 'Z',
+        }
+    }
+
+    /// Parts are cut on whole hex bytes and whole base32 groups (8 characters for 5 bytes).
+    fn split_modulus(&self) -> usize {
+        match self {
+            BbqrEncoding::Hex => 2,
+            _ => 8,
+        }
+    }
+}
+
+fn base36(value: usize) -> String {
+    let digits = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    format!("{}{}", digits[value / 36] as char, digits[value % 36] as char)
+}
+
+/// Writes DEFLATE bits, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit: usize,
+}
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u8) {
+        for i in 0..count {
+            if self.bit == 0 {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 1 << self.bit;
+            }
+            self.bit = (self.bit + 1) % 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, length: u8) {
+        let reversed = (0..length).fold(0, |reversed, i| (reversed << 1) | ((code >> i) & 1));
+        self.write(reversed, length);
+    }
+
+    fn write_literal(&mut self, symbol: u16) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol as u32, 8),
+            144..=255 => self.write_code(0x190 + (symbol - 144) as u32, 9),
+            256..=279 => self.write_code((symbol - 256) as u32, 7),
+            _ => self.write_code(0xc0 + (symbol - 280) as u32, 8),
+        }
+    }
+}
+
+/// Compresses into a single fixed Huffman DEFLATE block, with matches no further back than the BBQr window.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { bytes: vec![], bit: 0 };
+    writer.write(1, 1);
+    writer.write(1, 2);
+    let mut heads: BTreeMap<[u8; 3], Vec<usize>> = BTreeMap::new();
+    let mut position = 0;
+    while position < data.len() {
+        let mut best: Option<(usize, usize)> = None;
+        if position + 3 <= data.len() {
+            let prefix = [data[position], data[position + 1], data[position + 2]];
+            if let Some(candidates) = heads.get(&prefix) {
+                for candidate in candidates.iter().rev().take(MAX_CHAIN) {
+                    let distance = position - candidate;
+                    if distance > WINDOW {
+                        break;
+                    }
+                    let length = (0..258.min(data.len() - position))
+                        .take_while(|i| data[candidate + i] == data[position + i])
+                        .count();
+                    if best.is_none_or(|(best_length, _)| length > best_length) {
+                        best = Some((length, distance));
+                    }
+                    if length == 258 {
+                        break;
+                    }
+                }
+            }
+        }
+        let step = match best {
+            Some((length, distance)) if length >= 3 => {
+                let code = LENGTHS.iter().rposition(|(base, _)| *base as usize <= length).unwrap();
+                writer.write_literal(257 + code as u16);
+                writer.write((length - LENGTHS[code].0 as usize) as u32, LENGTHS[code].1);
+                let code = DISTANCES.iter().rposition(|(base, _)| *base as usize <= distance).unwrap();
+                writer.write_code(code as u32, 5);
+                writer.write((distance - DISTANCES[code].0 as usize) as u32, DISTANCES[code].1);
+                length
+            }
+            _ => {
+                writer.write_literal(data[position] as u16);
+                1
+            }
+        };
+        for index in position..position + step {
+            if index + 3 <= data.len() {
+                let prefix = [data[index], data[index + 1], data[index + 2]];
+                let candidates = heads.entry(prefix).or_default();
+                candidates.push(index);
+                let expired = candidates.partition_point(|candidate| index - candidate > WINDOW);
+                candidates.drain(..expired);
+            }
+        }
+        position += step;
+    }
+    writer.write_literal(256);
+    writer.bytes
+}
+
+/// Reads DEFLATE bits, least significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u8) -> Result<u32, S5Error> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = match self.bytes.get(self.position / 8) {
+                Some(byte) => byte,
+                None => return Err(invalid_compression()),
+            };
+            value |= (((byte >> (self.position % 8)) & 1) as u32) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+fn invalid_compression() -> S5Error {
+    S5Error::new(ErrorKind::Input, "Invalid BBQr Compression")
+}
+
+/// A canonical Huffman code: the number of codes of each length and the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = vec![];
+        for length in 1..16 {
+            for (symbol, symbol_length) in lengths.iter().enumerate() {
+                if *symbol_length as usize == length {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, S5Error> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_compression())
+    }
+}
+
+fn fixed_huffman() -> (Huffman, Huffman) {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5u8; 30]))
+}
+
+fn dynamic_huffman(reader: &mut BitReader) -> Result<(Huffman, Huffman), S5Error> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length_huffman = Huffman::new(&lengths);
+    let mut lengths: Vec<u8> = vec![];
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_huffman.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(previous) => (*previous, 3 + reader.bits(2)? as usize),
+                None => return Err(invalid_compression()),
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+    if lengths.len() != literals + distances {
+        return Err(invalid_compression());
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+/// Decompresses raw DEFLATE (stored, fixed and dynamic Huffman blocks) into at most max_length bytes.
+fn inflate(data: &[u8], max_length: usize) -> Result<Vec<u8>, S5Error> {
+    let mut reader = BitReader { bytes: data, position: 0 };
+    let mut output: Vec<u8> = vec![];
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.position = reader.position.div_ceil(8) * 8;
+                let start = reader.position / 8;
+                let header = data.get(start..start + 4).ok_or_else(invalid_compression)?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err(invalid_compression());
+                }
+                let stored = data.get(start + 4..start + 4 + length).ok_or_else(invalid_compression)?;
+                if output.len() + stored.len() > max_length {
+                    return Err(invalid_compression());
+                }
+                output.extend_from_slice(stored);
+                reader.position = (start + 4 + length) * 8;
+            }
+            block_type @ 1..=2 => {
+                let (literals, distances) = if block_type == 1 {
+                    fixed_huffman()
+                } else {
+                    dynamic_huffman(&mut reader)?
+                };
+                loop {
+                    let symbol = literals.decode(&mut reader)? as usize;
+                    if symbol < 256 {
+                        if output.len() == max_length {
+                            return Err(invalid_compression());
+                        }
+                        output.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let (base, extra) = *LENGTHS.get(symbol - 257).ok_or_else(invalid_compression)?;
+                    let length = base as usize + reader.bits(extra)? as usize;
+                    let code = distances.decode(&mut reader)? as usize;
+                    let (base, extra) = *DISTANCES.get(code).ok_or_else(invalid_compression)?;
+                    let distance = base as usize + reader.bits(extra)? as usize;
+                    if distance > output.len() || output.len() + length > max_length {
+                        return Err(invalid_compression());
+                    }
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+            }
+            _ => return Err(invalid_compression()),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+/// Splits data into BBQr parts of at most max_part_length characters, header included.
+/// Z falls back to 2 when compression does not make the data smaller.
+pub fn split(
+    data: &[u8],
+    file_type: FileType,
+    encoding: BbqrEncoding,
+    max_part_length: usize,
+) -> Result<Vec<String>, S5Error> {
+    if data.is_empty() {
+        return Err(S5Error::new(ErrorKind::Input, "Nothing To Encode"));
+    }
+    let (encoding, encoded) = match encoding {
+        BbqrEncoding::Hex => (encoding, Encoding::Hex.encode(data).to_uppercase()),
+        BbqrEncoding::Base32 => (encoding, Encoding::Base32.encode(data)),
+        BbqrEncoding::Zlib => {
+            let compressed = deflate(data);
+            if compressed.len() < data.len() {
+                (encoding, Encoding::Base32.encode(&compressed))
+            } else {
+                (BbqrEncoding::Base32, Encoding::Base32.encode(data))
+            }
+        }
+    };
+    let modulus = encoding.split_modulus();
+    let capacity = max_part_length.saturating_sub(HEADER_LENGTH) / modulus * modulus;
+    if capacity == 0 {
+        return Err(S5Error::new(ErrorKind::Input, "BBQr Part Length Too Small"));
+    }
+    let count = encoded.len().div_ceil(capacity);
+    if count > MAX_PARTS {
+        return Err(S5Error::new(ErrorKind::Input, "Too Many BBQr Parts"));
+    }
+    // spread the data evenly, so the last part is not a small leftover
+    let per_part = encoded.len().div_ceil(count).div_ceil(modulus) * modulus;
+    Ok(encoded
+        .as_bytes()
+        .chunks(per_part)
+        .enumerate()
+        .map(|(index, chunk)| {
+            format!(
+                "B${}{}{}{}{}",
+                encoding.code(),
+                file_type.code(),
+                base36(count),
+                base36(index),
+                String::from_utf8_lossy(chunk)
+            )
+        })
+        .collect())
+}
+
+/// Collects scanned BBQr parts, in any order and with repeats, until all are received.
+#[derive(Debug, Clone, Default)]
+pub struct BbqrJoiner {
+    header: Option<(char, char, usize)>,
+    parts: BTreeMap<usize, String>,
+}
+impl BbqrJoiner {
+    pub fn new() -> BbqrJoiner {
+        BbqrJoiner::default()
+    }
+
+    pub fn receive(&mut self, part: &str) -> Result<(), S5Error> {
+        let invalid = || S5Error::new(ErrorKind::Input, "Invalid BBQr");
+        let part = part.trim();
+        if part.len() < HEADER_LENGTH || !part.is_ascii() || !part.starts_with("B$") {
+            return Err(invalid());
+        }
+        let header: Vec<char> = part[2..HEADER_LENGTH].to_uppercase().chars().collect();
+        let count = usize::from_str_radix(&part[4..6], 36).map_err(|_| invalid())?;
+        let index = usize::from_str_radix(&part[6..8], 36).map_err(|_| invalid())?;
+        if count == 0 || index >= count {
+            return Err(invalid());
+        }
+        let expected = (header[0], header[1], count);
+        match self.header {
+            Some(current) if current != expected => {
+                return Err(S5Error::new(ErrorKind::Input, "BBQr Part Of Another File"))
+            }
+            Some(_) => (),
+            None => self.header = Some(expected),
+        }
+        self.parts.insert(index, part[HEADER_LENGTH..].to_string());
+        Ok(())
+    }
+
+    /// Number of parts received and number of parts of the file.
+    pub fn progress(&self) -> (usize, usize) {
+        match self.header {
+            Some((_, _, count)) => (self.parts.len(), count),
+            None => (0, 0),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.header.is_some_and(|(_, _, count)| self.parts.len() == count)
+    }
+
+    /// Joins and decodes the parts into the file type and its data.
+    pub fn join(&self) -> Result<(FileType, Vec<u8>), S5Error> {
+        let (encoding, file_type) = match self.header {
+            Some((encoding, file_type, _)) if self.is_complete() => (encoding, FileType::from_code(file_type)?),
+            _ => return Err(S5Error::new(ErrorKind::Input, "Incomplete BBQr")),
+        };
+        let encoded: String = self.parts.values().cloned().collect();
+        let data = match encoding {
+            'H' => Encoding::Hex.decode(&encoded)?,
+            '2' => Encoding::Base32.decode(&encoded)?,
+            'Z' => inflate(&Encoding::Base32.decode(&encoded)?, MAX_INFLATED_LENGTH)?,
+            _ => return Err(S5Error::new(ErrorKind::Input, "Unsupported BBQr Encoding")),
+        };
+        Ok((file_type, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deflate() {
+        let data = "{\"xfp\": \"3442193E\", \"p2wsh\": \"tpub\", \"p2wsh_deriv\": \"m/48'/1'/0'/2'\"}".repeat(40);
+        let compressed = deflate(data.as_bytes());
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(inflate(&compressed, MAX_INFLATED_LENGTH).unwrap(), data.as_bytes());
+        let random: Vec<u8> = (0..3000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        assert_eq!(inflate(&deflate(&random), MAX_INFLATED_LENGTH).unwrap(), random);
+
+        // python3 zlib.compressobj(9, zlib.DEFLATED, -10) output (a dynamic Huffman block) of skewed random letters
+        let letters = b"aaaaaaaabbbbccde";
+        let expected: Vec<u8> = (0..300)
+            .scan(12345u64, |x, _| {
+                *x = (*x * 1_103_515_245 + 12345) % (1 << 31);
+                Some(letters[((*x >> 16) % 16) as usize])
+            })
+            .collect();
+        let zlib = Encoding::Hex
+            .decode("2d8f0116c0200842cf0ac8fdaf30a0ad372b85af09a02512e2391709a6a12d6ed987088e8da836429c55537254af49d6ace85a56938d2c02fb9bd8b95f71710d1d26cd57e66b8b627bf43cbc87b8c7aae87e24ebae77e2de623817abe1a95b6bf7593324b82d03f19e109d32fda6c5aa4f99cde9799bf603")
+            .unwrap();
+        assert_eq!(inflate(&zlib, MAX_INFLATED_LENGTH).unwrap(), expected);
+        let zlib = Encoding::Hex.decode("cb48cdc9c957c8402293920a8b5004468587883000").unwrap();
+        assert_eq!(inflate(&zlib, MAX_INFLATED_LENGTH).unwrap(), "hello hello hello bbqr ".repeat(20).as_bytes());
+        // stored block
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, 0x61, 0x62, 0x63], MAX_INFLATED_LENGTH).unwrap(), b"abc");
+        assert_eq!(inflate(&[0x07], MAX_INFLATED_LENGTH).err().unwrap().message, "Invalid BBQr Compression");
+        // a file that inflates beyond the limit is rejected
+        let bomb = deflate(&[0; 100_000]);
+        assert_eq!(inflate(&bomb, 100_000).unwrap().len(), 100_000);
+        assert_eq!(inflate(&bomb, 99_999).err().unwrap().message, "Invalid BBQr Compression");
+    }
+
+    #[test]
+    fn test_split_join() {
+        let data = "{\"chain\": \"XTN\", \"xfp\": \"3442193E\"}".repeat(30);
+        let parts = split(data.as_bytes(), FileType::Json, BbqrEncoding::Zlib, 48).unwrap();
+        assert!(parts.len() > 1);
+        assert!(parts[0].starts_with(&format!("B$ZJ{}00", base36(parts.len()))));
+        assert!(parts.iter().all(|part| part.len() <= 48));
+
+        let mut joiner = BbqrJoiner::new();
+        for part in parts.iter().rev().chain(parts.iter()) {
+            joiner.receive(part).unwrap();
+        }
+        assert!(joiner.is_complete());
+        assert_eq!(joiner.join().unwrap(), (FileType::Json, data.as_bytes().to_vec()));
+
+        let hex = split(&[0xde, 0xad, 0xbe, 0xef], FileType::Transaction, BbqrEncoding::Hex, 100).unwrap();
+        assert_eq!(hex, vec!["B$HT0100DEADBEEF"]);
+        let base32 = split(&[0xde, 0xad, 0xbe, 0xef], FileType::Binary, BbqrEncoding::Zlib, 100).unwrap();
+        assert_eq!(base32, vec!["B$2B010032W353Y"]);
+
+        let mut joiner = BbqrJoiner::new();
+        joiner.receive(&parts[0]).unwrap();
+        assert_eq!(joiner.progress(), (1, parts.len()));
+        assert_eq!(joiner.join().err().unwrap().message, "Incomplete BBQr");
+        assert_eq!(joiner.receive(&hex[0]).err().unwrap().message, "BBQr Part Of Another File");
+        assert_eq!(joiner.receive("B$ZJ0203").err().unwrap().message, "Invalid BBQr");
+        assert_eq!(
+            split(data.as_bytes(), FileType::Json, BbqrEncoding::Zlib, 12).err().unwrap().message,
+            "BBQr Part Length Too Small"
+        );
+    }
+}
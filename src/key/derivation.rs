@@ -1,4 +1,5 @@
 use crate::e::{ErrorKind, S5Error};
+use crate::key::slip132;
 use bitcoin::network::constants::Network;
 use bitcoin::secp256k1::Secp256k1;
 use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
@@ -248,8 +249,9 @@ pub fn to_path_str(master_xprv: &str, derivation_path: &str) -> Result<ChildKeys
     })
 }
 
+/// Checks an xpub (tpub) or a SLIP-132 ypub, zpub, Ypub, Zpub (upub, vpub, Upub, Vpub).
 pub fn check_xpub(xpub: &str) -> bool {
-    ExtendedPubKey::from_str(xpub).is_ok()
+    slip132::to_xpub(xpub).is_ok()
 }
 
 #[cfg(test)]
@@ -317,6 +319,7 @@ mod tests {
     #[test]
     fn test_check_xpub() {
         assert!(check_xpub("tpubDDXskyWJLq5pUioZn8sGQ46aieCybzsjLb5BGmRPBAdwfGyvwiyXaoho8EYJcgJa5QGHGYpDjLQ8gWzczWbxadeRkCuExW32Boh696yuQ9m"));
+        assert!(check_xpub("zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"));
         assert_eq!(check_xpub("tpubTRICKSkyWJLq5pUioZn8sGQ46aieCybzsjLb5BGmRPBAdwfGyvwiyXaoho8EYJcgJa5QGHGYpDjLQ8gWzczWbxadeRkCuExW32Boh696yuQ9m"),false);
     }
 }
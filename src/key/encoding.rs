@@ -1,3 +1,6 @@
+use crate::e::{ErrorKind, S5Error};
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::str::FromStr;
 
 pub enum Encoding {
@@ -18,3 +21,37 @@ impl FromStr for Encoding{
         Ok(level)
     }
 }
+
+impl Encoding{
+    /// Encodes bytes as lowercase hex, unpadded uppercase base32 (RFC 4648) or base64.
+    pub fn encode(&self, data: &[u8]) -> String{
+        match self {
+            Encoding::Hex=>hex::encode(data),
+            Encoding::Base32=>base32::encode(base32::Alphabet::RFC4648 { padding: false }, data),
+            Encoding::Base64=>base64::encode(data),
+        }
+    }
+
+    /// Decodes hex or base32 in either case, or base64.
+    pub fn decode(&self, text: &str) -> Result<Vec<u8>,S5Error>{
+        let decoded = match self {
+            Encoding::Hex=>hex::decode(text.trim()).ok(),
+            Encoding::Base32=>base32::decode(base32::Alphabet::RFC4648 { padding: false }, &text.trim().to_uppercase()),
+            Encoding::Base64=>base64::decode(text.trim()).ok(),
+        };
+        match decoded {
+            Some(result)=>Ok(result),
+            None=>Err(S5Error::new(ErrorKind::Input,&format!("Invalid {}",self)))
+        }
+    }
+}
+
+impl Display for Encoding{
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result{
+        match self {
+            Encoding::Hex=>write!(f, "Hex"),
+            Encoding::Base32=>write!(f, "Base32"),
+            Encoding::Base64=>write!(f, "Base64"),
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use crate::e::{ErrorKind, S5Error};
+use crate::key::ur::crc32;
+use bitcoin::hashes::{sha256, Hash};
+use std::collections::BTreeSet;
+
+/// Smallest fragment a message is split into.
+const MIN_FRAGMENT_LENGTH: usize = 10;
+/// Largest number of fragments of a message, which keeps choosing the fragments of a mixed part cheap.
+const MAX_FRAGMENTS: usize = 1_000;
+
+/// Xoshiro256** seeded from the SHA-256 of a seed, the PRNG shared by all BC-UR implementations.
+struct Xoshiro256 {
+    s: [u64; 4],
+}
+impl Xoshiro256 {
+    fn new(seed: &[u8]) -> Xoshiro256 {
+        let digest = sha256::Hash::hash(seed);
+        let mut s = [0u64; 4];
+        for (i, chunk) in digest.as_ref().chunks(8).enumerate() {
+            s[i] = chunk.iter().fold(0u64, |value, byte| (value << 8) | *byte as u64);
+        }
+        Xoshiro256 { s }
+    }
+
+    fn next(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    fn next_int(&mut self, low: u64, high: u64) -> u64 {
+        (self.next_double() * (high - low + 1) as f64) as u64 + low
+    }
+}
+
+/// Samples the degree of a mixed part, a degree d being chosen with probability 1/d (Vose's alias method).
+fn choose_degree(seq_len: usize, rng: &mut Xoshiro256) -> usize {
+    let weights: Vec<f64> = (1..=seq_len).map(|degree| 1.0 / degree as f64).collect();
+    let sum: f64 = weights.iter().sum();
+    let mut scaled: Vec<f64> = weights.iter().map(|weight| weight * seq_len as f64 / sum).collect();
+    let mut small: Vec<usize> = vec![];
+    let mut large: Vec<usize> = vec![];
+    for i in (0..seq_len).rev() {
+        if scaled[i] < 1.0 {
+            small.push(i);
+        } else {
+            large.push(i);
+        }
+    }
+    let mut probabilities = vec![0.0; seq_len];
+    let mut aliases = vec![0; seq_len];
+    while !small.is_empty() && !large.is_empty() {
+        let a = small.pop().unwrap();
+        let g = large.pop().unwrap();
+        probabilities[a] = scaled[a];
+        aliases[a] = g;
+        scaled[g] += scaled[a] - 1.0;
+        if scaled[g] < 1.0 {
+            small.push(g);
+        } else {
+            large.push(g);
+        }
+    }
+    for i in large.into_iter().chain(small) {
+        probabilities[i] = 1.0;
+    }
+
+    let r1 = rng.next_double();
+    let r2 = rng.next_double();
+    let i = (seq_len as f64 * r1) as usize;
+    let degree = if r2 < probabilities[i] { i } else { aliases[i] };
+    degree + 1
+}
+
+fn shuffled<T>(mut items: Vec<T>, rng: &mut Xoshiro256) -> Vec<T> {
+    let mut result = vec![];
+    while !items.is_empty() {
+        let index = rng.next_int(0, items.len() as u64 - 1) as usize;
+        result.push(items.remove(index));
+    }
+    result
+}
+
+/// Gets the indexes of the fragments mixed into a part.
+/// The first seq_len parts carry one fragment each, the following ones a pseudo random selection.
+fn choose_fragments(seq_num: u32, seq_len: usize, checksum: u32) -> BTreeSet<usize> {
+    if seq_num as usize <= seq_len {
+        return BTreeSet::from([seq_num as usize - 1]);
+    }
+    let mut seed = seq_num.to_be_bytes().to_vec();
+    seed.extend_from_slice(&checksum.to_be_bytes());
+    let mut rng = Xoshiro256::new(&seed);
+    let degree = choose_degree(seq_len, &mut rng);
+    shuffled((0..seq_len).collect(), &mut rng)
+        .into_iter()
+        .take(degree)
+        .collect()
+}
+
+/// Gets the fragment length that splits a message into the fewest fragments no longer than max_fragment_length.
+fn fragment_length(message_len: usize, max_fragment_length: usize) -> usize {
+    let max_fragment_count = (message_len / MIN_FRAGMENT_LENGTH).max(1);
+    let mut length = message_len;
+    for count in 1..=max_fragment_count {
+        length = message_len.div_ceil(count);
+        if length <= max_fragment_length {
+            break;
+        }
+    }
+    length
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    for (target, source) in target.iter_mut().zip(source) {
+        *target ^= source;
+    }
+}
+
+/// A part of a fountain coded message, the XOR of one or more of its fragments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FountainPart {
+    pub seq_num: u32,
+    pub seq_len: usize,
+    pub message_len: usize,
+    pub checksum: u32,
+    pub data: Vec<u8>,
+}
+
+/// Splits a message into fragments and emits an endless sequence of parts:
+/// each fragment once, then rateless mixes so that any missed part can be recovered from later ones.
+pub struct FountainEncoder {
+    fragments: Vec<Vec<u8>>,
+    message_len: usize,
+    checksum: u32,
+    seq_num: u32,
+}
+impl FountainEncoder {
+    pub fn new(message: &[u8], max_fragment_length: usize) -> Result<FountainEncoder, S5Error> {
+        if message.is_empty() || max_fragment_length == 0 {
+            return Err(S5Error::new(ErrorKind::Input, "Nothing To Encode"));
+        }
+        let length = fragment_length(message.len(), max_fragment_length);
+        if message.len().div_ceil(length) > MAX_FRAGMENTS {
+            return Err(S5Error::new(ErrorKind::Input, "Too Many UR Fragments"));
+        }
+        let fragments = message
+            .chunks(length)
+            .map(|chunk| {
+                let mut fragment = chunk.to_vec();
+                fragment.resize(length, 0);
+                fragment
+            })
+            .collect();
+        Ok(FountainEncoder {
+            fragments,
+            message_len: message.len(),
+            checksum: crc32(message),
+            seq_num: 0,
+        })
+    }
+
+    /// Number of fragments, i.e. the number of parts a decoder needs at best.
+    pub fn seq_len(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub fn next_part(&mut self) -> FountainPart {
+        self.seq_num = self.seq_num.wrapping_add(1);
+        let indexes = choose_fragments(self.seq_num, self.seq_len(), self.checksum);
+        let mut data = vec![0u8; self.fragments[0].len()];
+        for index in indexes {
+            xor_into(&mut data, &self.fragments[index]);
+        }
+        FountainPart {
+            seq_num: self.seq_num,
+            seq_len: self.seq_len(),
+            message_len: self.message_len,
+            checksum: self.checksum,
+            data,
+        }
+    }
+}
+
+/// Reassembles a message from fountain parts received in any order, with any of them missing or repeated.
+#[derive(Debug, Clone, Default)]
+pub struct FountainDecoder {
+    expected: Option<(usize, usize, u32, usize)>,
+    received: BTreeSet<u32>,
+    simple: Vec<Option<Vec<u8>>>,
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+    message: Option<Vec<u8>>,
+}
+impl FountainDecoder {
+    pub fn is_complete(&self) -> bool {
+        self.message.is_some()
+    }
+
+    pub fn message(&self) -> Option<&Vec<u8>> {
+        self.message.as_ref()
+    }
+
+    /// Number of fragments recovered so far and number of fragments of the message.
+    pub fn progress(&self) -> (usize, usize) {
+        let recovered = self.simple.iter().filter(|fragment| fragment.is_some()).count();
+        (recovered, self.simple.len())
+    }
+
+    /// Adds a part. Parts of another message are rejected, repeated parts are ignored.
+    pub fn receive(&mut self, part: &FountainPart) -> Result<(), S5Error> {
+        let invalid = || S5Error::new(ErrorKind::Input, "Invalid UR Part");
+        if part.seq_num == 0
+            || part.seq_len == 0
+            || part.seq_len > MAX_FRAGMENTS
+            || part.data.is_empty()
+            || part.message_len > part.seq_len * part.data.len()
+        {
+            return Err(invalid());
+        }
+        let expected = (part.seq_len, part.message_len, part.checksum, part.data.len());
+        match self.expected {
+            Some(current) if current != expected => {
+                return Err(S5Error::new(ErrorKind::Input, "UR Part Of Another Message"))
+            }
+            Some(_) => (),
+            None => {
+                self.expected = Some(expected);
+                self.simple = vec![None; part.seq_len];
+            }
+        }
+        if self.is_complete() || !self.received.insert(part.seq_num) {
+            return Ok(());
+        }
+
+        let mut queue = vec![(choose_fragments(part.seq_num, part.seq_len, part.checksum), part.data.clone())];
+        while let Some((mut indexes, mut data)) = queue.pop() {
+            for index in indexes.clone() {
+                if indexes.len() > 1 {
+                    if let Some(fragment) = &self.simple[index] {
+                        xor_into(&mut data, fragment);
+                        indexes.remove(&index);
+                    }
+                }
+            }
+            for (mixed_indexes, mixed_data) in &self.mixed {
+                if mixed_indexes.len() < indexes.len() && mixed_indexes.is_subset(&indexes) {
+                    xor_into(&mut data, mixed_data);
+                    indexes = indexes.difference(mixed_indexes).cloned().collect();
+                }
+            }
+
+            if indexes.is_empty() {
+                continue;
+            } else if indexes.len() == 1 {
+                let index = *indexes.iter().next().unwrap();
+                if self.simple[index].is_some() {
+                    continue;
+                }
+                self.simple[index] = Some(data);
+                let (reducible, mixed): (Vec<_>, Vec<_>) =
+                    self.mixed.drain(..).partition(|(mixed_indexes, _)| mixed_indexes.contains(&index));
+                self.mixed = mixed;
+                queue.extend(reducible);
+            } else if !self.mixed.iter().any(|(mixed_indexes, _)| *mixed_indexes == indexes) {
+                let (reducible, mixed): (Vec<_>, Vec<_>) = self
+                    .mixed
+                    .drain(..)
+                    .partition(|(mixed_indexes, _)| indexes.is_subset(mixed_indexes));
+                self.mixed = mixed;
+                queue.extend(reducible);
+                self.mixed.push((indexes, data));
+            }
+        }
+
+        if self.simple.iter().all(|fragment| fragment.is_some()) {
+            let mut message: Vec<u8> = self.simple.iter().flatten().flatten().cloned().collect();
+            message.truncate(part.message_len);
+            if crc32(&message) != part.checksum {
+                return Err(S5Error::new(ErrorKind::Input, "Invalid UR Checksum"));
+            }
+            self.message = Some(message);
+            self.mixed.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xoshiro() {
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let numbers: Vec<u64> = (0..10).map(|_| rng.next() % 100).collect();
+        assert_eq!(numbers, vec![42, 81, 85, 8, 82, 84, 76, 73, 70, 88]);
+        assert_eq!(
+            shuffled((1..=10).collect(), &mut Xoshiro256::new(b"Wolf")),
+            vec![6, 4, 9, 3, 10, 5, 7, 8, 1, 2]
+        );
+        let degrees: Vec<usize> = (1..=10)
+            .map(|nonce| choose_degree(11, &mut Xoshiro256::new(format!("Wolf-{}", nonce).as_bytes())))
+            .collect();
+        assert_eq!(degrees, vec![11, 3, 6, 5, 2, 1, 2, 11, 1, 3]);
+    }
+
+    #[test]
+    fn test_fountain() {
+        let message: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+        assert_eq!(fragment_length(message.len(), 100), 100);
+        assert_eq!(fragment_length(message.len(), 90), 84);
+
+        let mut encoder = FountainEncoder::new(&message, 90).unwrap();
+        assert_eq!(encoder.seq_len(), 12);
+        let parts: Vec<FountainPart> = (0..40).map(|_| encoder.next_part()).collect();
+
+        // every other part is lost, the mixed parts fill the gaps
+        let mut decoder = FountainDecoder::default();
+        for part in parts.iter().step_by(2) {
+            decoder.receive(part).unwrap();
+            decoder.receive(part).unwrap();
+            if decoder.is_complete() {
+                break;
+            }
+        }
+        assert!(decoder.is_complete());
+        assert_eq!(decoder.message(), Some(&message));
+        assert_eq!(decoder.progress(), (12, 12));
+
+        let mut other = FountainEncoder::new(&message[1..], 90).unwrap();
+        let mut decoder = FountainDecoder::default();
+        decoder.receive(&parts[0]).unwrap();
+        assert_eq!(decoder.progress(), (1, 12));
+        assert_eq!(
+            decoder.receive(&other.next_part()).err().unwrap().message,
+            "UR Part Of Another Message"
+        );
+        let zero = FountainPart { seq_num: 0, ..parts[0].clone() };
+        assert_eq!(FountainDecoder::default().receive(&zero).err().unwrap().message, "Invalid UR Part");
+        assert_eq!(
+            FountainEncoder::new(&[0; 20_000], 10).err().unwrap().message,
+            "Too Many UR Fragments"
+        );
+    }
+}
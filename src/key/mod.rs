@@ -2,4 +2,8 @@ pub mod seed;
 pub mod derivation;
 pub mod ec;
 pub mod encoding;
-pub mod encryption;
\ No newline at end of file
+pub mod encryption;
+pub mod bbqr;
+pub mod fountain;
+pub mod slip132;
+pub mod ur;
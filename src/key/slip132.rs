@@ -0,0 +1,198 @@
+use crate::e::{ErrorKind, S5Error};
+use bitcoin::network::constants::Network;
+use bitcoin::util::base58;
+use bitcoin::util::bip32::ExtendedPubKey;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+/// SLIP-132 version bytes of extended public keys, with their network and implied script type.
+/// xpub and tpub do not imply a script type.
+const VERSIONS: [([u8; 4], Network, Option<&str>); 10] = [
+    ([0x04, 0x88, 0xb2, 0x1e], Network::Bitcoin, None),
+    ([0x04, 0x9d, 0x7c, 0xb2], Network::Bitcoin, Some("sh-wpkh")),
+    ([0x04, 0xb2, 0x47, 0x46], Network::Bitcoin, Some("wpkh")),
+    ([0x02, 0x95, 0xb4, 0x3f], Network::Bitcoin, Some("sh-wsh")),
+    ([0x02, 0xaa, 0x7e, 0xd3], Network::Bitcoin, Some("wsh")),
+    ([0x04, 0x35, 0x87, 0xcf], Network::Testnet, None),
+    ([0x04, 0x4a, 0x52, 0x62], Network::Testnet, Some("sh-wpkh")),
+    ([0x04, 0x5f, 0x1c, 0xf6], Network::Testnet, Some("wpkh")),
+    ([0x02, 0x42, 0x89, 0xef], Network::Testnet, Some("sh-wsh")),
+    ([0x02, 0x57, 0x54, 0x83], Network::Testnet, Some("wsh")),
+];
+
+/// An extended public key converted from a SLIP-132 encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slip132Key {
+    pub xpub: ExtendedPubKey,
+    pub script_type: Option<String>,
+}
+
+/// Converts a ypub, zpub, Ypub, Zpub (upub, vpub, Upub, Vpub on testnet) into an xpub (tpub).
+/// xpub and tpub are returned as they are.
+pub fn to_xpub(key: &str) -> Result<Slip132Key, S5Error> {
+    let mut data = match base58::from_check(key.trim()) {
+        Ok(result) if result.len() == 78 => result,
+        _ => return Err(S5Error::new(ErrorKind::Key, "Invalid Extended Public Key")),
+    };
+    let (network, script_type) = match VERSIONS.iter().find(|(version, _, _)| data[0..4] == version[..]) {
+        Some((_, network, script_type)) => (*network, script_type.map(|script_type| script_type.to_string())),
+        None => return Err(S5Error::new(ErrorKind::Key, "Unknown Extended Key Version")),
+    };
+    let version = match network {
+        Network::Bitcoin => VERSIONS[0].0,
+        _ => VERSIONS[5].0,
+    };
+    data[0..4].copy_from_slice(&version);
+    match ExtendedPubKey::decode(&data) {
+        Ok(xpub) => Ok(Slip132Key { xpub, script_type }),
+        Err(_) => Err(S5Error::new(ErrorKind::Key, "Invalid Extended Public Key")),
+    }
+}
+
+/// Converts an xpub (tpub), or any SLIP-132 key, into the version of a script type,
+/// i.e. a zpub (vpub) for wpkh. pkh, sh and tr have no SLIP-132 version and use xpub (tpub).
+pub fn from_xpub(key: &str, script_type: &str) -> Result<String, S5Error> {
+    let xpub = to_xpub(key)?.xpub;
+    let network = match xpub.network {
+        Network::Bitcoin => Network::Bitcoin,
+        _ => Network::Testnet,
+    };
+    let script_type = match script_type {
+        "pkh" | "sh" | "tr" | "" => None,
+        script_type => Some(script_type),
+    };
+    let version = match VERSIONS
+        .iter()
+        .find(|(_, version_network, version_script_type)| *version_network == network && *version_script_type == script_type)
+    {
+        Some((version, _, _)) => version,
+        None => return Err(S5Error::new(ErrorKind::Key, "Script Type Has No SLIP-132 Version")),
+    };
+    let mut data = xpub.encode().to_vec();
+    data[0..4].copy_from_slice(version);
+    Ok(base58::check_encode_slice(&data))
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConvertedKey {
+    pub xpub: String,
+    pub script_type: Option<String>,
+    pub key: String,
+}
+impl ConvertedKey {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Converts a key between SLIP-132 versions.
+/// The result has the xpub (tpub), the script type implied by the given key and the key in the version of script_type.
+/// An empty script_type keeps the version of the given key.
+pub fn convert(key: &str, script_type: &str) -> Result<ConvertedKey, S5Error> {
+    let converted = to_xpub(key)?;
+    let key = if script_type.is_empty() {
+        key.trim().to_string()
+    } else {
+        from_xpub(key, script_type)?
+    };
+    Ok(ConvertedKey {
+        xpub: converted.xpub.to_string(),
+        script_type: converted.script_type,
+        key,
+    })
+}
+
+/// Replaces every SLIP-132 public key in a key, policy or descriptor with its xpub (tpub),
+/// so that they can be parsed by miniscript. Everything else is left as it is.
+pub fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, normalized: &mut String| {
+        match to_xpub(word) {
+            Ok(converted) if converted.script_type.is_some() => normalized.push_str(&converted.xpub.to_string()),
+            _ => normalized.push_str(word),
+        }
+        word.clear();
+    };
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut normalized);
+            normalized.push(c);
+        }
+    }
+    flush(&mut word, &mut normalized);
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slip132_to_xpub() {
+        // BIP84 test vector account 0
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let converted = to_xpub(zpub).unwrap();
+        assert_eq!(converted.script_type, Some("wpkh".to_string()));
+        assert_eq!(converted.xpub.network, Network::Bitcoin);
+        assert_eq!(
+            converted.xpub.to_string(),
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V"
+        );
+
+        let tpub = "tpubDDNRbZGvdA33cgpY5uy2mmphT7sK4uciRjcQScSd64S5KRyZDxHcPuzs24or84Hywugb2JbEEt2jWH8fduiN9cmZzkSj8sSSx6txXkhXyZs";
+        let converted = to_xpub(tpub).unwrap();
+        assert_eq!(converted.script_type, None);
+        assert_eq!(converted.xpub.to_string(), tpub);
+
+        assert_eq!(
+            to_xpub("zpubNOTAKEY").err().unwrap().message,
+            "Invalid Extended Public Key"
+        );
+    }
+
+    #[test]
+    fn test_slip132_from_xpub() {
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        assert_eq!(from_xpub(xpub, "wpkh").unwrap(), zpub);
+        assert_eq!(from_xpub(zpub, "tr").unwrap(), xpub);
+        assert!(from_xpub(xpub, "sh-wpkh").unwrap().starts_with("ypub"));
+        assert!(from_xpub(xpub, "wsh").unwrap().starts_with("Zpub"));
+        let tpub = "tpubDDNRbZGvdA33cgpY5uy2mmphT7sK4uciRjcQScSd64S5KRyZDxHcPuzs24or84Hywugb2JbEEt2jWH8fduiN9cmZzkSj8sSSx6txXkhXyZs";
+        let vpub = from_xpub(tpub, "wpkh").unwrap();
+        assert!(vpub.starts_with("vpub"));
+        assert_eq!(to_xpub(&vpub).unwrap().xpub.to_string(), tpub);
+        assert!(from_xpub(tpub, "sh-wsh").unwrap().starts_with("Upub"));
+        assert_eq!(
+            from_xpub(tpub, "bare").err().unwrap().message,
+            "Script Type Has No SLIP-132 Version"
+        );
+
+        assert_eq!(
+            normalize(&format!("wpkh([7c11dc12/84'/1'/0']{}/0/*)", vpub)),
+            format!("wpkh([7c11dc12/84'/1'/0']{}/0/*)", tpub)
+        );
+        assert_eq!(normalize(&format!("pk({})", tpub)), format!("pk({})", tpub));
+        assert_eq!(normalize("and(pk(A),older(144))"), "and(pk(A),older(144))");
+
+        let converted = convert(zpub, "sh-wpkh").unwrap();
+        assert_eq!(converted.xpub, xpub);
+        assert_eq!(converted.script_type, Some("wpkh".to_string()));
+        assert_eq!(converted.key, from_xpub(xpub, "sh-wpkh").unwrap());
+        assert_eq!(convert(zpub, "").unwrap().key, zpub);
+    }
+}
@@ -0,0 +1,873 @@
+use crate::e::{ErrorKind, S5Error};
+use crate::key::fountain::{FountainDecoder, FountainEncoder, FountainPart};
+use bdk::descriptor::Descriptor;
+use bdk::miniscript::descriptor::{DescriptorPublicKey, ShInner, SinglePubKey, Wildcard, WshInner};
+use bdk::miniscript::Terminal;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::util::bip32::{ChainCode, ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint};
+use std::str::FromStr;
+
+/// The 256 bytewords used by BC-UR, in byte order.
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// CRC-32 (IEEE), the checksum of bytewords and UR fountain parts.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Encodes bytes as minimal bytewords (first and last letter of each word) followed by their CRC-32.
+pub fn bytewords_encode(data: &[u8]) -> String {
+    let mut bytes = data.to_vec();
+    bytes.extend_from_slice(&crc32(data).to_be_bytes());
+    bytes
+        .iter()
+        .map(|byte| {
+            let word = BYTEWORDS[*byte as usize];
+            format!("{}{}", &word[0..1], &word[3..4])
+        })
+        .collect()
+}
+
+/// Decodes minimal bytewords and checks their CRC-32.
+pub fn bytewords_decode(words: &str) -> Result<Vec<u8>, S5Error> {
+    let words = words.to_lowercase();
+    if !words.is_ascii() || !words.len().is_multiple_of(2) {
+        return Err(S5Error::new(ErrorKind::Input, "Invalid Bytewords"));
+    }
+    let mut bytes: Vec<u8> = vec![];
+    for pair in words.as_bytes().chunks(2) {
+        match BYTEWORDS
+            .iter()
+            .position(|word| word.as_bytes()[0] == pair[0] && word.as_bytes()[3] == pair[1])
+        {
+            Some(byte) => bytes.push(byte as u8),
+            None => return Err(S5Error::new(ErrorKind::Input, "Invalid Bytewords")),
+        }
+    }
+    if bytes.len() < 4 {
+        return Err(S5Error::new(ErrorKind::Input, "Invalid Bytewords"));
+    }
+    let checksum = bytes.split_off(bytes.len() - 4);
+    if crc32(&bytes).to_be_bytes()[..] != checksum[..] {
+        return Err(S5Error::new(ErrorKind::Input, "Invalid UR Checksum"));
+    }
+    Ok(bytes)
+}
+
+/// Encodes a cbor payload as ur:type/message, or as fountain coded ur:type/seq-len/part when it is longer than max_fragment_length.
+/// Parts are produced endlessly: the first seq_len carry one fragment each and are enough on their own,
+/// the next ones mix fragments so a scanner that missed some parts recovers them from any later ones.
+pub struct UrEncoder {
+    ur_type: String,
+    single: Option<Vec<u8>>,
+    fountain: Option<FountainEncoder>,
+}
+impl UrEncoder {
+    pub fn new(ur_type: &str, payload: &[u8], max_fragment_length: usize) -> Result<UrEncoder, S5Error> {
+        if payload.is_empty() {
+            return Err(S5Error::new(ErrorKind::Input, "Nothing To Encode"));
+        }
+        if payload.len() <= max_fragment_length {
+            return Ok(UrEncoder {
+                ur_type: ur_type.to_string(),
+                single: Some(payload.to_vec()),
+                fountain: None,
+            });
+        }
+        Ok(UrEncoder {
+            ur_type: ur_type.to_string(),
+            single: None,
+            fountain: Some(FountainEncoder::new(payload, max_fragment_length)?),
+        })
+    }
+
+    /// Number of parts that carry the whole payload once.
+    pub fn seq_len(&self) -> usize {
+        match &self.fountain {
+            Some(fountain) => fountain.seq_len(),
+            None => 1,
+        }
+    }
+
+    pub fn next_part(&mut self) -> String {
+        match (&self.single, &mut self.fountain) {
+            (_, Some(fountain)) => {
+                let part = fountain.next_part();
+                let cbor = Cbor::Array(vec![
+                    Cbor::Unsigned(part.seq_num as u64),
+                    Cbor::Unsigned(part.seq_len as u64),
+                    Cbor::Unsigned(part.message_len as u64),
+                    Cbor::Unsigned(part.checksum as u64),
+                    Cbor::Bytes(part.data),
+                ]);
+                format!(
+                    "ur:{}/{}-{}/{}",
+                    self.ur_type,
+                    part.seq_num,
+                    part.seq_len,
+                    bytewords_encode(&cbor.encode())
+                )
+            }
+            (Some(payload), None) => format!("ur:{}/{}", self.ur_type, bytewords_encode(payload)),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Collects scanned UR parts, in any order and with repeats, until the payload is complete.
+#[derive(Debug, Clone, Default)]
+pub struct UrDecoder {
+    ur_type: Option<String>,
+    fountain: FountainDecoder,
+    payload: Option<Vec<u8>>,
+}
+impl UrDecoder {
+    pub fn new() -> UrDecoder {
+        UrDecoder::default()
+    }
+
+    pub fn receive(&mut self, ur: &str) -> Result<(), S5Error> {
+        let ur = ur.trim().to_lowercase();
+        let body = match ur.strip_prefix("ur:") {
+            Some(body) => body,
+            None => return Err(S5Error::new(ErrorKind::Input, "Invalid UR")),
+        };
+        let parts: Vec<&str> = body.split('/').collect();
+        if parts.len() < 2 || parts.len() > 3 || parts[0].is_empty() {
+            return Err(S5Error::new(ErrorKind::Input, "Invalid UR"));
+        }
+        match &self.ur_type {
+            Some(ur_type) if ur_type != parts[0] => {
+                return Err(S5Error::new(ErrorKind::Input, "UR Part Of Another Message"))
+            }
+            Some(_) => (),
+            None => self.ur_type = Some(parts[0].to_string()),
+        }
+        if self.is_complete() {
+            return Ok(());
+        }
+        if parts.len() == 2 {
+            self.payload = Some(bytewords_decode(parts[1])?);
+            return Ok(());
+        }
+
+        let part = decode_part(&bytewords_decode(parts[2])?)?;
+        if parts[1] != format!("{}-{}", part.seq_num, part.seq_len) {
+            return Err(S5Error::new(ErrorKind::Input, "Invalid UR Part"));
+        }
+        self.fountain.receive(&part)?;
+        self.payload = self.fountain.message().cloned();
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.payload.is_some()
+    }
+
+    pub fn ur_type(&self) -> Option<&String> {
+        self.ur_type.as_ref()
+    }
+
+    /// Number of fragments recovered so far and number of fragments of the payload.
+    pub fn progress(&self) -> (usize, usize) {
+        match (&self.payload, self.fountain.progress()) {
+            (Some(_), (_, 0)) => (1, 1),
+            (_, progress) => progress,
+        }
+    }
+
+    pub fn payload(&self) -> Option<&Vec<u8>> {
+        self.payload.as_ref()
+    }
+}
+
+fn decode_part(bytes: &[u8]) -> Result<FountainPart, S5Error> {
+    let invalid = || S5Error::new(ErrorKind::Input, "Invalid UR Part");
+    let items = match Cbor::decode(bytes)? {
+        Cbor::Array(items) if items.len() == 5 => items,
+        _ => return Err(invalid()),
+    };
+    let number = |index: usize, max: u64| match items[index].as_u64() {
+        Some(value) if value <= max => Ok(value),
+        _ => Err(invalid()),
+    };
+    let data = match &items[4] {
+        Cbor::Bytes(data) => data.clone(),
+        _ => return Err(invalid()),
+    };
+    let seq_num = number(0, u32::MAX as u64)? as u32;
+    if seq_num == 0 {
+        return Err(invalid());
+    }
+    Ok(FountainPart {
+        seq_num,
+        seq_len: number(1, u32::MAX as u64)? as usize,
+        message_len: number(2, u32::MAX as u64)? as usize,
+        checksum: number(3, u32::MAX as u64)? as u32,
+        data,
+    })
+}
+
+/// Splits ur:type/message into its type and cbor payload.
+/// The parts of an animated UR can be given together, separated by whitespace.
+pub fn decode(ur: &str) -> Result<(String, Vec<u8>), S5Error> {
+    let mut decoder = UrDecoder::new();
+    for part in ur.split_whitespace() {
+        decoder.receive(part)?;
+    }
+    match (decoder.ur_type(), decoder.payload()) {
+        (Some(ur_type), Some(payload)) => Ok((ur_type.clone(), payload.clone())),
+        (Some(_), None) => Err(S5Error::new(ErrorKind::Input, "Incomplete UR")),
+        _ => Err(S5Error::new(ErrorKind::Input, "Invalid UR")),
+    }
+}
+
+/// A decoded CBOR item. Only the major types used by the UR registry are supported.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cbor {
+    Unsigned(u64),
+    Negative(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Bool(bool),
+    Null,
+}
+impl Cbor {
+    /// Decodes a single CBOR item that must use all the given bytes.
+    pub fn decode(bytes: &[u8]) -> Result<Cbor, S5Error> {
+        let mut position = 0;
+        let item = Cbor::decode_item(bytes, &mut position, 0)?;
+        if position != bytes.len() {
+            return Err(S5Error::new(ErrorKind::Input, "Invalid CBOR"));
+        }
+        Ok(item)
+    }
+
+    /// Encodes the item with the shortest argument encoding (canonical CBOR).
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.encode_item(&mut bytes);
+        bytes
+    }
+
+    fn encode_item(&self, bytes: &mut Vec<u8>) {
+        let head = |bytes: &mut Vec<u8>, major: u8, argument: u64| {
+            let major = major << 5;
+            match argument {
+                0..=23 => bytes.push(major | argument as u8),
+                24..=0xff => bytes.extend_from_slice(&[major | 24, argument as u8]),
+                0x100..=0xffff => {
+                    bytes.push(major | 25);
+                    bytes.extend_from_slice(&(argument as u16).to_be_bytes());
+                }
+                0x1_0000..=0xffff_ffff => {
+                    bytes.push(major | 26);
+                    bytes.extend_from_slice(&(argument as u32).to_be_bytes());
+                }
+                _ => {
+                    bytes.push(major | 27);
+                    bytes.extend_from_slice(&argument.to_be_bytes());
+                }
+            }
+        };
+        match self {
+            Cbor::Unsigned(value) => head(bytes, 0, *value),
+            Cbor::Negative(value) => head(bytes, 1, *value),
+            Cbor::Bytes(data) => {
+                head(bytes, 2, data.len() as u64);
+                bytes.extend_from_slice(data);
+            }
+            Cbor::Text(text) => {
+                head(bytes, 3, text.len() as u64);
+                bytes.extend_from_slice(text.as_bytes());
+            }
+            Cbor::Array(items) => {
+                head(bytes, 4, items.len() as u64);
+                for item in items {
+                    item.encode_item(bytes);
+                }
+            }
+            Cbor::Map(entries) => {
+                head(bytes, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode_item(bytes);
+                    value.encode_item(bytes);
+                }
+            }
+            Cbor::Tag(tag, item) => {
+                head(bytes, 6, *tag);
+                item.encode_item(bytes);
+            }
+            Cbor::Bool(false) => bytes.push(0xf4),
+            Cbor::Bool(true) => bytes.push(0xf5),
+            Cbor::Null => bytes.push(0xf6),
+        }
+    }
+
+    fn decode_item(bytes: &[u8], position: &mut usize, depth: usize) -> Result<Cbor, S5Error> {
+        let invalid = || S5Error::new(ErrorKind::Input, "Invalid CBOR");
+        if depth > 32 {
+            return Err(invalid());
+        }
+        let initial = *bytes.get(*position).ok_or_else(invalid)?;
+        *position += 1;
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+        let argument = match info {
+            0..=23 => info as u64,
+            24..=27 => {
+                let size = 1 << (info - 24);
+                let end = *position + size;
+                let slice = bytes.get(*position..end).ok_or_else(invalid)?;
+                *position = end;
+                slice.iter().fold(0u64, |value, byte| (value << 8) | *byte as u64)
+            }
+            _ => return Err(invalid()),
+        };
+        let mut take = |length: u64| -> Result<Vec<u8>, S5Error> {
+            let end = position.checked_add(length as usize).ok_or_else(invalid)?;
+            let slice = bytes.get(*position..end).ok_or_else(invalid)?;
+            *position = end;
+            Ok(slice.to_vec())
+        };
+        match major {
+            0 => Ok(Cbor::Unsigned(argument)),
+            1 => Ok(Cbor::Negative(argument)),
+            2 => Ok(Cbor::Bytes(take(argument)?)),
+            3 => match String::from_utf8(take(argument)?) {
+                Ok(text) => Ok(Cbor::Text(text)),
+                Err(_) => Err(invalid()),
+            },
+            4 => {
+                let mut items = vec![];
+                for _ in 0..argument {
+                    items.push(Cbor::decode_item(bytes, position, depth + 1)?);
+                }
+                Ok(Cbor::Array(items))
+            }
+            5 => {
+                let mut entries = vec![];
+                for _ in 0..argument {
+                    let key = Cbor::decode_item(bytes, position, depth + 1)?;
+                    let value = Cbor::decode_item(bytes, position, depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(Cbor::Map(entries))
+            }
+            6 => Ok(Cbor::Tag(
+                argument,
+                Box::new(Cbor::decode_item(bytes, position, depth + 1)?),
+            )),
+            _ => match info {
+                20 => Ok(Cbor::Bool(false)),
+                21 => Ok(Cbor::Bool(true)),
+                22 => Ok(Cbor::Null),
+                _ => Err(invalid()),
+            },
+        }
+    }
+
+    /// Gets the value of an unsigned integer key in a map.
+    pub fn get(&self, key: u64) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| *entry_key == Cbor::Unsigned(key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Removes a registry tag, mapping the newer 40000 range (i.e. 40303) to the original tags (303).
+    pub fn untag(&self) -> (Option<u64>, &Cbor) {
+        match self {
+            Cbor::Tag(tag, item) if *tag >= 40_000 => (Some(tag - 40_000), item),
+            Cbor::Tag(tag, item) => (Some(*tag), item),
+            item => (None, item),
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Cbor::Unsigned(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// UR registry tags.
+pub const TAG_HDKEY: u64 = 303;
+pub const TAG_KEYPATH: u64 = 304;
+pub const TAG_COININFO: u64 = 305;
+pub const TAG_ECKEY: u64 = 306;
+pub const TAG_OUTPUT: u64 = 308;
+
+/// An account key from a crypto-hdkey, with its origin and the script type of its output descriptor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrKey {
+    pub fingerprint: Option<Fingerprint>,
+    pub path: Option<DerivationPath>,
+    pub xpub: ExtendedPubKey,
+    pub children: Option<String>,
+    pub script_type: Option<String>,
+}
+
+fn invalid_key() -> S5Error {
+    S5Error::new(ErrorKind::Key, "Invalid UR Key")
+}
+
+/// Reads the children crypto-keypath of a crypto-hdkey into descriptor steps, i.e. /0/*
+fn decode_children(keypath: &Cbor) -> Result<String, S5Error> {
+    let components = match keypath.get(1) {
+        Some(Cbor::Array(components)) => components,
+        _ => return Err(invalid_key()),
+    };
+    let mut children = String::new();
+    for pair in components.chunks(2) {
+        let hardened = match pair.get(1) {
+            Some(Cbor::Bool(true)) => "'",
+            Some(Cbor::Bool(false)) => "",
+            _ => return Err(invalid_key()),
+        };
+        match &pair[0] {
+            Cbor::Unsigned(index) if *index < (1 << 31) => children.push_str(&format!("/{}{}", index, hardened)),
+            Cbor::Array(range) if range.is_empty() => children.push_str(&format!("/*{}", hardened)),
+            _ => return Err(invalid_key()),
+        }
+    }
+    Ok(children)
+}
+
+/// Reads a crypto-keypath into its source fingerprint and derivation path.
+fn decode_keypath(keypath: &Cbor) -> Result<(Option<Fingerprint>, DerivationPath), S5Error> {
+    let components = match keypath.get(1) {
+        Some(Cbor::Array(components)) => components,
+        _ => return Err(invalid_key()),
+    };
+    let mut path: Vec<ChildNumber> = vec![];
+    for pair in components.chunks(2) {
+        let index = match pair[0].as_u64() {
+            Some(index) if index < (1 << 31) => index as u32,
+            _ => return Err(invalid_key()),
+        };
+        let child = match pair.get(1) {
+            Some(Cbor::Bool(true)) => ChildNumber::from_hardened_idx(index),
+            Some(Cbor::Bool(false)) => ChildNumber::from_normal_idx(index),
+            _ => return Err(invalid_key()),
+        };
+        path.push(child.map_err(|_| invalid_key())?);
+    }
+    let fingerprint = keypath
+        .get(2)
+        .and_then(Cbor::as_u64)
+        .map(|fingerprint| Fingerprint::from(&(fingerprint as u32).to_be_bytes()[..]));
+    Ok((fingerprint, DerivationPath::from(path)))
+}
+
+/// Reads a crypto-hdkey (public) into an extended public key with its origin.
+pub fn decode_hdkey(hdkey: &Cbor) -> Result<UrKey, S5Error> {
+    if let Some(Cbor::Bool(true)) = hdkey.get(2) {
+        return Err(S5Error::new(ErrorKind::Key, "UR Key Must Be Public"));
+    }
+    let public_key = match hdkey.get(3) {
+        Some(Cbor::Bytes(bytes)) => PublicKey::from_slice(bytes).map_err(|_| invalid_key())?,
+        _ => return Err(invalid_key()),
+    };
+    let chain_code = match hdkey.get(4) {
+        Some(Cbor::Bytes(bytes)) if bytes.len() == 32 => ChainCode::from(&bytes[..]),
+        _ => return Err(invalid_key()),
+    };
+    let network = match hdkey.get(5).map(|info| info.untag().1.get(2)) {
+        Some(Some(Cbor::Unsigned(1))) => Network::Testnet,
+        _ => Network::Bitcoin,
+    };
+    let (fingerprint, path) = match hdkey.get(6) {
+        Some(origin) => {
+            let (fingerprint, path) = decode_keypath(origin.untag().1)?;
+            (fingerprint, Some(path))
+        }
+        None => (None, None),
+    };
+    let depth = match hdkey.get(6).and_then(|origin| origin.untag().1.get(3)).and_then(Cbor::as_u64) {
+        Some(depth) => depth as u8,
+        None => path.as_ref().map(|path| path.len() as u8).unwrap_or(0),
+    };
+    let parent_fingerprint = match hdkey.get(8).and_then(Cbor::as_u64) {
+        Some(parent) => Fingerprint::from(&(parent as u32).to_be_bytes()[..]),
+        None => Fingerprint::default(),
+    };
+    let child_number = match path.as_ref().and_then(|path| path.as_ref().last()) {
+        Some(child) => *child,
+        None => ChildNumber::from(0),
+    };
+    let children = match hdkey.get(7) {
+        Some(children) => Some(decode_children(children.untag().1)?),
+        None => None,
+    };
+
+    Ok(UrKey {
+        fingerprint,
+        path,
+        xpub: ExtendedPubKey {
+            network,
+            depth,
+            parent_fingerprint,
+            child_number,
+            public_key,
+            chain_code,
+        },
+        children,
+        script_type: None,
+    })
+}
+
+fn keypath_components(path: &[ChildNumber]) -> Vec<Cbor> {
+    path.iter()
+        .flat_map(|child| match child {
+            ChildNumber::Normal { index } => [Cbor::Unsigned(*index as u64), Cbor::Bool(false)],
+            ChildNumber::Hardened { index } => [Cbor::Unsigned(*index as u64), Cbor::Bool(true)],
+        })
+        .collect()
+}
+
+/// Encodes a descriptor key as a crypto-hdkey (extended keys) or crypto-eckey (single keys).
+fn encode_key(key: &DescriptorPublicKey) -> Cbor {
+    let xkey = match key {
+        DescriptorPublicKey::SinglePub(single) => {
+            let data = match &single.key {
+                SinglePubKey::FullKey(key) => key.to_bytes(),
+                SinglePubKey::XOnly(key) => key.serialize().to_vec(),
+            };
+            return Cbor::Tag(TAG_ECKEY, Box::new(Cbor::Map(vec![(Cbor::Unsigned(3), Cbor::Bytes(data))])));
+        }
+        DescriptorPublicKey::XPub(xkey) => xkey,
+    };
+    let xpub = &xkey.xkey;
+    let mut entries = vec![
+        (Cbor::Unsigned(3), Cbor::Bytes(xpub.public_key.serialize().to_vec())),
+        (Cbor::Unsigned(4), Cbor::Bytes(xpub.chain_code.as_bytes().to_vec())),
+    ];
+    if xpub.network != Network::Bitcoin {
+        let coin_info = Cbor::Map(vec![(Cbor::Unsigned(2), Cbor::Unsigned(1))]);
+        entries.push((Cbor::Unsigned(5), Cbor::Tag(TAG_COININFO, Box::new(coin_info))));
+    }
+    if let Some((fingerprint, path)) = &xkey.origin {
+        let origin = Cbor::Map(vec![
+            (Cbor::Unsigned(1), Cbor::Array(keypath_components(path.as_ref()))),
+            (Cbor::Unsigned(2), Cbor::Unsigned(u32::from_be_bytes(fingerprint.to_bytes()) as u64)),
+            (Cbor::Unsigned(3), Cbor::Unsigned(xpub.depth as u64)),
+        ]);
+        entries.push((Cbor::Unsigned(6), Cbor::Tag(TAG_KEYPATH, Box::new(origin))));
+    }
+    let mut children = keypath_components(xkey.derivation_path.as_ref());
+    match xkey.wildcard {
+        Wildcard::None => (),
+        Wildcard::Unhardened => children.extend([Cbor::Array(vec![]), Cbor::Bool(false)]),
+        Wildcard::Hardened => children.extend([Cbor::Array(vec![]), Cbor::Bool(true)]),
+    }
+    if !children.is_empty() {
+        let children = Cbor::Map(vec![(Cbor::Unsigned(1), Cbor::Array(children))]);
+        entries.push((Cbor::Unsigned(7), Cbor::Tag(TAG_KEYPATH, Box::new(children))));
+    }
+    if xpub.depth > 0 {
+        let parent = u32::from_be_bytes(xpub.parent_fingerprint.to_bytes()) as u64;
+        entries.push((Cbor::Unsigned(8), Cbor::Unsigned(parent)));
+    }
+    Cbor::Tag(TAG_HDKEY, Box::new(Cbor::Map(entries)))
+}
+
+fn encode_multi(tag: u64, threshold: usize, keys: &[DescriptorPublicKey]) -> Cbor {
+    Cbor::Tag(
+        tag,
+        Box::new(Cbor::Map(vec![
+            (Cbor::Unsigned(1), Cbor::Unsigned(threshold as u64)),
+            (Cbor::Unsigned(2), Cbor::Array(keys.iter().map(encode_key).collect())),
+        ])),
+    )
+}
+
+fn tagged(tag: u64, item: Cbor) -> Cbor {
+    Cbor::Tag(tag, Box::new(item))
+}
+
+/// Encodes a public descriptor as the script expression of a crypto-output.
+/// Single key scripts, multi and sortedmulti (in sh, wsh or sh-wsh) and key path only tr are supported.
+pub fn encode_output(descriptor: &str) -> Result<Cbor, S5Error> {
+    let unsupported = || S5Error::new(ErrorKind::Input, "Unsupported UR Output");
+    let descriptor = match Descriptor::<DescriptorPublicKey>::from_str(descriptor.trim()) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Public Descriptor")),
+    };
+    let multi = |node: &Terminal<DescriptorPublicKey, _>| match node {
+        Terminal::Multi(threshold, keys) => Ok(encode_multi(406, *threshold, keys)),
+        _ => Err(unsupported()),
+    };
+    match &descriptor {
+        Descriptor::Pkh(pkh) => Ok(tagged(403, encode_key(pkh.as_inner()))),
+        Descriptor::Wpkh(wpkh) => Ok(tagged(404, encode_key(wpkh.as_inner()))),
+        Descriptor::Tr(tr) if tr.taptree().is_none() => Ok(tagged(409, encode_key(tr.internal_key()))),
+        Descriptor::Wsh(wsh) => Ok(tagged(
+            401,
+            match wsh.as_inner() {
+                WshInner::SortedMulti(sorted) => encode_multi(407, sorted.k, &sorted.pks),
+                WshInner::Ms(ms) => multi(&ms.node)?,
+            },
+        )),
+        Descriptor::Sh(sh) => Ok(tagged(
+            400,
+            match sh.as_inner() {
+                ShInner::Wpkh(wpkh) => tagged(404, encode_key(wpkh.as_inner())),
+                ShInner::Wsh(wsh) => tagged(
+                    401,
+                    match wsh.as_inner() {
+                        WshInner::SortedMulti(sorted) => encode_multi(407, sorted.k, &sorted.pks),
+                        WshInner::Ms(ms) => match &ms.node {
+                            Terminal::Multi(threshold, keys) => encode_multi(406, *threshold, keys),
+                            _ => return Err(unsupported()),
+                        },
+                    },
+                ),
+                ShInner::SortedMulti(sorted) => encode_multi(407, sorted.k, &sorted.pks),
+                ShInner::Ms(ms) => match &ms.node {
+                    Terminal::Multi(threshold, keys) => encode_multi(406, *threshold, keys),
+                    _ => return Err(unsupported()),
+                },
+            },
+        )),
+        _ => Err(unsupported()),
+    }
+}
+
+/// Writes the key of a crypto-hdkey or crypto-eckey as a descriptor key, i.e. [fingerprint/path]xpub/0/*
+fn key_text(key: &Cbor) -> Result<String, S5Error> {
+    match key.untag() {
+        (Some(TAG_HDKEY), hdkey) => {
+            let key = decode_hdkey(hdkey)?;
+            let origin = match (key.fingerprint, &key.path) {
+                (Some(fingerprint), Some(path)) => {
+                    format!("[{}{}]", fingerprint, path.to_string().trim_start_matches('m'))
+                }
+                _ => String::new(),
+            };
+            Ok(format!("{}{}{}", origin, key.xpub, key.children.unwrap_or_default()))
+        }
+        (Some(TAG_ECKEY), eckey) => match eckey.get(3) {
+            Some(Cbor::Bytes(data)) => Ok(data.to_hex()),
+            _ => Err(invalid_key()),
+        },
+        _ => Err(invalid_key()),
+    }
+}
+
+/// Writes the script expression of a crypto-output as a descriptor (without checksum).
+pub fn output_descriptor(output: &Cbor) -> Result<String, S5Error> {
+    let (tag, inner) = output.untag();
+    let multi = |name: &str| -> Result<String, S5Error> {
+        let threshold = inner.get(1).and_then(Cbor::as_u64);
+        match (threshold, inner.get(2)) {
+            (Some(threshold), Some(Cbor::Array(keys))) => Ok(format!(
+                "{}({},{})",
+                name,
+                threshold,
+                keys.iter().map(key_text).collect::<Result<Vec<String>, S5Error>>()?.join(",")
+            )),
+            _ => Err(S5Error::new(ErrorKind::Input, "Invalid UR Output")),
+        }
+    };
+    match tag {
+        Some(TAG_OUTPUT) => output_descriptor(inner),
+        Some(400) => Ok(format!("sh({})", output_descriptor(inner)?)),
+        Some(401) => Ok(format!("wsh({})", output_descriptor(inner)?)),
+        Some(402) => Ok(format!("pk({})", output_descriptor(inner)?)),
+        Some(403) => Ok(format!("pkh({})", output_descriptor(inner)?)),
+        Some(404) => Ok(format!("wpkh({})", output_descriptor(inner)?)),
+        Some(409) => Ok(format!("tr({})", output_descriptor(inner)?)),
+        Some(406) => multi("multi"),
+        Some(407) => multi("sortedmulti"),
+        Some(TAG_HDKEY) | Some(TAG_ECKEY) => key_text(output),
+        _ => Err(S5Error::new(ErrorKind::Input, "Unsupported UR Output")),
+    }
+}
+
+/// Unwraps the script expression tags of an output descriptor down to its key.
+/// sh 400, wsh 401, pkh 403, wpkh 404 and tr 409 are supported.
+pub fn decode_output(output: &Cbor) -> Result<UrKey, S5Error> {
+    let mut scripts: Vec<&str> = vec![];
+    let mut item = output;
+    loop {
+        let (tag, inner) = item.untag();
+        match tag {
+            Some(TAG_OUTPUT) => (),
+            Some(400) => scripts.push("sh"),
+            Some(401) => scripts.push("wsh"),
+            Some(403) => scripts.push("pkh"),
+            Some(404) => scripts.push("wpkh"),
+            Some(409) => scripts.push("tr"),
+            Some(TAG_HDKEY) => {
+                let mut key = decode_hdkey(inner)?;
+                key.script_type = match scripts.join("-").as_str() {
+                    "" => None,
+                    script_type => Some(script_type.to_string()),
+                };
+                return Ok(key);
+            }
+            _ => return Err(S5Error::new(ErrorKind::Input, "Unsupported UR Output")),
+        }
+        item = inner;
+    }
+}
+
+/// Reads a crypto-account into the keys of its output descriptors, with the master fingerprint as origin.
+pub fn decode_account(account: &Cbor) -> Result<Vec<UrKey>, S5Error> {
+    let account = account.untag().1;
+    let master = account
+        .get(1)
+        .and_then(Cbor::as_u64)
+        .map(|fingerprint| Fingerprint::from(&(fingerprint as u32).to_be_bytes()[..]));
+    let outputs = match account.get(2) {
+        Some(Cbor::Array(outputs)) => outputs,
+        _ => return Err(S5Error::new(ErrorKind::Input, "Invalid UR Account")),
+    };
+    let mut keys = vec![];
+    for output in outputs {
+        match decode_output(output) {
+            Ok(mut key) => {
+                key.fingerprint = key.fingerprint.or(master);
+                keys.push(key);
+            }
+            Err(e) if e.message == "Unsupported UR Output" => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::FromHex;
+
+    #[test]
+    fn test_bytewords() {
+        let data = vec![0x00, 0x01, 0xa2, 0x1a, 0xff];
+        let words = bytewords_encode(&data);
+        assert!(words.starts_with("aeadoecyzm"));
+        assert_eq!(bytewords_decode(&words.to_uppercase()).unwrap(), data);
+        let mut tampered = words.clone();
+        tampered.replace_range(0..2, "ad");
+        assert_eq!(bytewords_decode(&tampered).err().unwrap().message, "Invalid UR Checksum");
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_crypto_account() {
+        // crypto-account of 3442193e with wpkh and tr account keys (84'/1'/0' and 86'/1'/0')
+        let cbor = Vec::<u8>::from_hex(ACCOUNT_CBOR).unwrap();
+        let ur = format!("ur:crypto-account/{}", bytewords_encode(&cbor));
+        let (ur_type, payload) = decode(&ur.to_uppercase()).unwrap();
+        assert_eq!(ur_type, "crypto-account");
+        let keys = decode_account(&Cbor::decode(&payload).unwrap()).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].fingerprint.unwrap().to_string(), "3442193e");
+        assert_eq!(keys[0].path, Some(DerivationPath::from_str("m/84'/1'/0'").unwrap()));
+        assert_eq!(keys[0].script_type, Some("wpkh".to_string()));
+        assert_eq!(
+            keys[0].xpub.to_string(),
+            "tpubDDNRbZGvdA33cgpY5uy2mmphT7sK4uciRjcQScSd64S5KRyZDxHcPuzs24or84Hywugb2JbEEt2jWH8fduiN9cmZzkSj8sSSx6txXkhXyZs"
+        );
+        assert_eq!(keys[1].script_type, Some("tr".to_string()));
+
+        assert_eq!(Cbor::decode(&payload).unwrap().encode(), payload);
+    }
+
+    #[test]
+    fn test_animated_ur() {
+        let payload = Cbor::Bytes(Vec::<u8>::from_hex(ACCOUNT_CBOR).unwrap()).encode();
+        let mut encoder = UrEncoder::new("crypto-psbt", &payload, 40).unwrap();
+        assert_eq!(encoder.seq_len(), 6);
+        let parts: Vec<String> = (0..24).map(|_| encoder.next_part()).collect();
+        assert!(parts[0].starts_with("ur:crypto-psbt/1-6/"));
+        assert!(parts[9].starts_with("ur:crypto-psbt/10-6/"));
+
+        // the scanner misses the first 4 parts and sees some twice
+        let mut decoder = UrDecoder::new();
+        for part in parts[4..].iter().chain(parts[4..].iter()) {
+            decoder.receive(&part.to_uppercase()).unwrap();
+            if decoder.is_complete() {
+                break;
+            }
+        }
+        assert!(decoder.is_complete());
+        assert_eq!(decoder.payload(), Some(&payload));
+        assert_eq!(decoder.progress(), (6, 6));
+        assert_eq!(decode(&parts[..6].join("\n")).unwrap().1, payload);
+        assert_eq!(decode(&parts[..3].join(" ")).err().unwrap().message, "Incomplete UR");
+
+        let mut single = UrEncoder::new("crypto-psbt", &payload, 1000).unwrap();
+        assert_eq!(decode(&single.next_part()).unwrap(), ("crypto-psbt".to_string(), payload));
+
+        let mut decoder = UrDecoder::new();
+        decoder.receive(&parts[0]).unwrap();
+        assert_eq!(
+            decoder.receive(&parts[1].replace("crypto-psbt", "bytes")).err().unwrap().message,
+            "UR Part Of Another Message"
+        );
+        assert_eq!(
+            decoder.receive(&parts[1].replace("/2-6/", "/3-6/")).err().unwrap().message,
+            "Invalid UR Part"
+        );
+        let part = decode_part(&bytewords_decode(parts[1].rsplit('/').next().unwrap()).unwrap()).unwrap();
+        let zero = Cbor::Array(vec![
+            Cbor::Unsigned(0),
+            Cbor::Unsigned(part.seq_len as u64),
+            Cbor::Unsigned(part.message_len as u64),
+            Cbor::Unsigned(part.checksum as u64),
+            Cbor::Bytes(part.data),
+        ]);
+        assert_eq!(
+            decoder.receive(&format!("ur:crypto-psbt/0-6/{}", bytewords_encode(&zero.encode()))).err().unwrap().message,
+            "Invalid UR Part"
+        );
+    }
+
+    const ACCOUNT_CBOR: &str = "a2011a3442193e0282d90194d9012fa5035821037250fb8bc368072d81f1619d6e1d174c0b276f615ab7a8b00660168f3863268804582000b43c8f69fc37de32691dd408fec9e2df389a4a8ca97a6b9158d357af36cb6a05d90131a1020106d90130a301861854f501f500f5021a3442193e0303081ab706ea64d90199d9012fa503582103ce3a35a807271b1d2fbbb442bbd4777987367e1a6a18e5a6e0eb22385cfda86c0458201c4304310d93112c92580cd2bb783ab776e70da4f87e2e3758e86a9341c2eaf105d90131a1020106d90130a301861856f501f500f5021a3442193e0303081acc225a2e";
+}
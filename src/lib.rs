@@ -43,12 +43,24 @@ mod key;
 use crate::key::derivation;
 use crate::key::ec;
 use crate::key::seed;
+use crate::key::slip132;
+use crate::key::ur::UrDecoder;
 
 mod wallet;
 use crate::wallet::address;
+use crate::wallet::amount;
+use crate::wallet::bip21;
+use crate::wallet::coldcard;
+use crate::wallet::hardware;
+use crate::wallet::custom;
+use crate::wallet::descriptor;
 use crate::wallet::history;
+use crate::wallet::multisig;
 use crate::wallet::policy;
 use crate::wallet::psbt;
+use crate::wallet::qr;
+use crate::wallet::refresh;
+use crate::wallet::sweep;
 use crate::wallet::utxo;
 
 mod network;
@@ -244,6 +256,40 @@ pub unsafe extern "C" fn derive_to_path(
         Err(e) => e.c_stringify(),
     }
 }
+
+/// Converts an extended public key between SLIP-132 versions, i.e. zpub to xpub or tpub to vpub.
+/// script_type (pkh, sh-wpkh, wpkh, sh-wsh, wsh, tr) selects the version of key in the output, pkh, sh and tr use xpub (tpub).
+/// An empty script_type only converts into xpub (tpub) and keeps key as it is.
+/// - *OUTPUT*
+/// ```
+/// ConvertedKey {
+///   xpub: String,
+///   script_type: Option<String>, (implied by the version of the given key)
+///   key: String,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn convert_xpub(key: *const c_char, script_type: *const c_char) -> *mut c_char {
+    let key_cstr = CStr::from_ptr(key);
+    let key: &str = match key_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Key").c_stringify(),
+    };
+    let script_type_cstr = CStr::from_ptr(script_type);
+    let script_type: &str = match script_type_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => "",
+    };
+
+    match slip132::convert(key, script_type) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
 /// Converts an xprv into EC keys with XOnlyPub..
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
@@ -355,6 +401,9 @@ pub unsafe extern "C" fn verify_signature(
 /// Compiles a policy into a descriptor of the specified script type.
 /// Use wpkh for a single signature Native native wallet (default).
 /// Use wsh for a scripted Native native wallet.
+/// Use tr for a taproot wallet, with the most likely single key branch as key path and all other branches as script leaves.
+/// Errors if the policy does not fit the script type, i.e. wpkh only fits a single key.
+/// The descriptor includes its checksum i.e. wsh(...)#checksum
 /// - *OUTPUT*
 /// ```
 /// WalletPolicy {
@@ -385,47 +434,98 @@ pub unsafe extern "C" fn compile(policy: *const c_char, script_type: *const c_ch
     }
 }
 
-/// Gets the policy id from a given descriptor.
+/// Compiles a policy into every script type and recommends the one with the lowest weight.
+/// Weight is the max satisfaction weight plus the script pubkey weight (cost to receive and spend).
+/// Options that do not fit the policy contain an error instead of a weight.
 /// - *OUTPUT*
 /// ```
-/// String,String
+/// ScriptTypeRecommendation {
+///   script_type: String,
+///   options: Vec<ScriptTypeFit{
+///     script_type: String,
+///     weight: Option<usize>,
+///     error: Option<String>,
+///   }>,
+/// }
 /// ```
-/// First string is whether specifying a policy id is required.
-/// Second string is the policy id.
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn policy_id(descriptor: *const c_char) -> *mut c_char {
+pub unsafe extern "C" fn recommend_script_type(policy: *const c_char) -> *mut c_char {
+    let policy_cstr = CStr::from_ptr(policy);
+    let policy_str: &str = match policy_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Policy").c_stringify(),
+    };
+
+    match policy::recommend(policy_str) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Gets the policy tree of both keychains of a descriptor.
+/// Use node ids and item indexes to build the policy_path for build_tx.
+/// - *OUTPUT*
+/// ```
+/// PolicyTree {
+///   external: Option<KeychainPolicy{
+///     id: String,
+///     requires_path: bool,
+///     policy: Policy{ id, type, items?, threshold?, fingerprint?, value?, satisfaction, contribution },
+///   }>,
+///   internal: Option<KeychainPolicy>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn policy_tree(descriptor: *const c_char) -> *mut c_char {
     let descriptor_cstr = CStr::from_ptr(descriptor);
     let descriptor_str: &str = match descriptor_cstr.to_str() {
         Ok(string) => string,
         Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
     };
-    let config = match WalletConfig::new_offline(descriptor_str,None) {
+    let config = match WalletConfig::new_offline(descriptor_str, None) {
         Ok(conf) => conf,
         Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
     };
-    match policy::id(config) {
-        Ok(result) => CString::new(format!("{},{}", result.0, result.1))
-            .unwrap()
-            .into_raw(),
+    match policy::tree(config) {
+        Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
 
-/// Syncs to a remote node and populates an SQLite db at a given path
-/// Use before other SQLite Wallet functions.
+/// Checks which keys have signed each input of a psbt and what is still missing to satisfy the descriptor policy.
+/// Syncs the descriptor to get input confirmation heights and checks timelocks against the current height.
+/// - *OUTPUT*
+/// ```
+/// PsbtSatisfaction {
+///   height: u32,
+///   inputs: Vec<InputSatisfaction{
+///     outpoint: String,
+///     signed: Vec<String>, (fingerprints)
+///     unsigned: Vec<String>, (fingerprints still missing, empty once satisfied)
+///     timelocks: Vec<TimelockStatus{kind: String, value: u32, is_ready: bool, blocks_remaining: Option<u32>}>,
+///     is_satisfied: bool,
+///     is_finalized: bool,
+///     policy: Option<Policy>,
+///   }>,
+///   is_satisfied: bool,
+/// }
+/// ```
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn sqlite_sync(
-    db_path: *const c_char,    
+pub unsafe extern "C" fn psbt_satisfaction(
     descriptor: *const c_char,
     node_address: *const c_char,
-    socks5: *const c_char
-) -> *mut c_char{
+    socks5: *const c_char,
+    psbt: *const c_char,
+) -> *mut c_char {
     let descriptor_cstr = CStr::from_ptr(descriptor);
     let descriptor: &str = match descriptor_cstr.to_str() {
         Ok(string) => string,
@@ -446,7 +546,7 @@ pub unsafe extern "C" fn sqlite_sync(
     let socks5_cstr = CStr::from_ptr(socks5);
     let socks5_option = match socks5_cstr.to_str() {
         Ok(string) => {
-            if string.to_lowercase() == "none" || string == "" {
+            if string.to_lowercase() == "none" || string.is_empty() {
                 None
             } else {
                 Some(string.to_string())
@@ -454,295 +554,1021 @@ pub unsafe extern "C" fn sqlite_sync(
         }
         Err(_) => None,
     };
-    let db_path_cstr = CStr::from_ptr(db_path);
-    let db_path: String = match db_path_cstr.to_str() {
-        Ok(string) => string.to_string(),
-        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
-    };
-    let config = match WalletConfig::new(descriptor, node_address, socks5_option,Some(db_path)) {
+    let config = match WalletConfig::new(descriptor, node_address, socks5_option, None) {
         Ok(conf) => conf,
-        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+        Err(e) => return e.c_stringify(),
     };
-    match wallet::sync::sqlite(config){
-        Ok(_)=> CString::new("DONE").unwrap().into_raw(),
-        Err(e)=>e.c_stringify()
+
+    let psbt_cstr = CStr::from_ptr(psbt);
+    let psbt: &str = match psbt_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "PSBT").c_stringify(),
+    };
+
+    match policy::sync_satisfaction(config, psbt) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
     }
 }
 
-/// Fetches balance of a descriptor wallet from Sqlite db path.
+/// Creates a wallet from a template for a common custody setup.
+/// template: solo (1 key), escrow (buyer,seller,escrow agent - 2 of 3), team (threshold of keys),
+/// raft (owner,custodian - custodian can spend alone after the timelock block height) or custom (policy).
+/// keys are comma separated [fingerprint/path]xpub, use an xprv in place of your own xpub for a spending descriptor.
+/// script_type defaults to wpkh for solo and wsh for all others.
 /// - *OUTPUT*
 /// ```
-/// WalletBalance {
-///   balance: u64
+/// CustomWalletPolicy {
+///   template: String,
+///   policy: String,
+///   descriptor: String,
+///   policy_id: String,
+///   requires_path: bool,
+///   recovery: Vec<String>,
 /// }
 /// ```
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn sqlite_balance(
-    descriptor: *const c_char,
-    db_path: *const c_char
+pub unsafe extern "C" fn wallet_template(
+    template: *const c_char,
+    keys: *const c_char,
+    threshold: *const c_char,
+    timelock: *const c_char,
+    policy: *const c_char,
+    script_type: *const c_char,
 ) -> *mut c_char {
-    let descriptor_cstr = CStr::from_ptr(descriptor);
-    let descriptor: &str = match descriptor_cstr.to_str() {
-        Ok(string) => string,
-        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    let template_cstr = CStr::from_ptr(template);
+    let template = match template_cstr.to_str() {
+        Ok(string) => match custom::CustomWallet::from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Template").c_stringify(),
+    };
+    let keys_cstr = CStr::from_ptr(keys);
+    let keys: Vec<String> = match keys_cstr.to_str() {
+        Ok(string) => string
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(|key| key.trim().to_string())
+            .collect(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Keys").c_stringify(),
+    };
+    let threshold_cstr = CStr::from_ptr(threshold);
+    let threshold: Option<usize> = match threshold_cstr.to_str() {
+        Ok(string) => string.parse::<usize>().ok(),
+        Err(_) => None,
     };
-    let db_path_cstr = CStr::from_ptr(db_path);
-    let db_path: String = match db_path_cstr.to_str() {
-        Ok(string) => string.to_string(),
-        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    let timelock_cstr = CStr::from_ptr(timelock);
+    let timelock: Option<u32> = match timelock_cstr.to_str() {
+        Ok(string) => string.parse::<u32>().ok(),
+        Err(_) => None,
+    };
+    let policy_cstr = CStr::from_ptr(policy);
+    let policy: Option<String> = match policy_cstr.to_str() {
+        Ok(string) if !string.is_empty() => Some(string.to_string()),
+        _ => None,
+    };
+    let script_type_cstr = CStr::from_ptr(script_type);
+    let script_type: Option<policy::ScriptType> = match script_type_cstr.to_str() {
+        Ok(string) if !string.is_empty() => Some(policy::ScriptType::from_str(string)),
+        _ => None,
     };
 
-    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
-        Ok(conf) => conf,
-        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    let params = custom::TemplateParams {
+        keys,
+        threshold,
+        timelock,
+        policy,
+        script_type,
     };
-    match history::sqlite_balance(config) {
+    match custom::create(template, params) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
 
-/// Syncs to a remote node and fetches balance of a descriptor wallet.
+/// Creates a threshold of N multisig wallet with sortedmulti, so the key order does not change the addresses.
+/// keys are comma separated [fingerprint/path]xpub, use an xprv in place of your own xpub for a spending descriptor.
+/// All keys must be on the same network and have unique fingerprints.
+/// script_type is wsh (default), sh-wsh or sh (legacy).
 /// - *OUTPUT*
 /// ```
-/// WalletBalance {
-///   balance: u64
+/// MultisigWallet {
+///   script_type: String,
+///   threshold: usize,
+///   fingerprints: Vec<String>,
+///   network: String, ("main" || "test")
+///   descriptor: String,
 /// }
 /// ```
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn sync_balance(
-    descriptor: *const c_char,
-    node_address: *const c_char,
-    socks5: *const c_char,
+pub unsafe extern "C" fn sorted_multisig(
+    keys: *const c_char,
+    threshold: *const c_char,
+    script_type: *const c_char,
 ) -> *mut c_char {
-    let descriptor_cstr = CStr::from_ptr(descriptor);
-    let descriptor: &str = match descriptor_cstr.to_str() {
-        Ok(string) => string,
-        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
-    };
-
-    let node_address_cstr = CStr::from_ptr(node_address);
-    let node_address: &str = match node_address_cstr.to_str() {
-        Ok(string) => {
-            if string.contains("electrum") || string.contains("http") {
-                string
-            } else {
-                DEFAULT
-            }
-        }
-        Err(_) => DEFAULT,
+    let keys_cstr = CStr::from_ptr(keys);
+    let keys: Vec<String> = match keys_cstr.to_str() {
+        Ok(string) => string
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(|key| key.trim().to_string())
+            .collect(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Keys").c_stringify(),
+    };
+    let threshold_cstr = CStr::from_ptr(threshold);
+    let threshold: usize = match threshold_cstr.to_str() {
+        Ok(string) => match string.parse::<usize>() {
+            Ok(result) => result,
+            Err(_) => return S5Error::new(ErrorKind::Input, "Threshold").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Threshold").c_stringify(),
     };
-    let socks5_cstr = CStr::from_ptr(socks5);
-    let socks5_option = match socks5_cstr.to_str() {
-        Ok(string) => {
-            if string.to_lowercase() == "none" || string == "" {
-                None
-            } else {
-                Some(string.to_string())
-            }
-        }
-        Err(_) => None,
+    let script_type_cstr = CStr::from_ptr(script_type);
+    let script_type = match script_type_cstr.to_str() {
+        Ok(string) if !string.is_empty() => policy::ScriptType::from_str(string),
+        _ => policy::ScriptType::WSH,
     };
 
-    let config = match WalletConfig::new(descriptor, node_address, socks5_option,None) {
-        Ok(conf) => conf,
-        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
-    };
-    match history::sync_balance(config) {
+    match multisig::sortedmulti(&keys, threshold, script_type) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
 
-/// Fetches history of a descriptor wallet from a SQLite DB at a specified path.
+/// Imports a multisig wallet from a Coldcard multisig setup file or a Specter/Sparrow wallet JSON.
+/// content is the text of the file, JSON is detected by its opening brace.
 /// - *OUTPUT*
 /// ```
-///  WalletHistory{
-///    history: Vec<Transaction {
-///      timestamp: u64,
-///      height: u32,
-///      verified: bool,
-///      txid: String,
-///      received: u64,
-///      sent: u64,
-///      fee: u64,
-///    }>;
-///  }
+/// MultisigConfig {
+///   name: String,
+///   script_type: String,
+///   threshold: usize,
+///   sorted: bool,
+///   keys: Vec<MultisigKey{fingerprint: String, derivation: String, xpub: String}>,
+///   descriptor: String,
+/// }
 /// ```
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn sqlite_history(
-    descriptor: *const c_char,
-    db_path: *const c_char
-) -> *mut c_char {
-    let descriptor_cstr = CStr::from_ptr(descriptor);
-    let descriptor: &str = match descriptor_cstr.to_str() {
+pub unsafe extern "C" fn import_multisig(content: *const c_char) -> *mut c_char {
+    let content_cstr = CStr::from_ptr(content);
+    let content: &str = match content_cstr.to_str() {
         Ok(string) => string,
-        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
-    };
-    let db_path_cstr = CStr::from_ptr(db_path);
-    let db_path: String = match db_path_cstr.to_str() {
-        Ok(string) => string.to_string(),
-        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Content").c_stringify(),
     };
 
-    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
-        Ok(conf) => conf,
-        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
-    };
-    match history::sqlite_history(config) {
+    match multisig::import(content) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
 
-/// Syncs to a remote node and fetches history of a descriptor wallet.
+/// Exports a multisig descriptor as a wallet file for other coordinators and signing devices.
+/// format is coldcard (default, sortedmulti only) or specter (also imported by Sparrow).
 /// - *OUTPUT*
 /// ```
-///  WalletHistory{
-///    history: Vec<Transaction {
-///      timestamp: u64,
-///      height: u32,
-///      verified: bool,
-///      txid: String,
-///      received: u64,
-///      sent: u64,
-///      fee: u64,
-///    }>;
-///  }
+/// MultisigFile {
+///   format: String,
+///   content: String,
+/// }
 /// ```
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn sync_history(
+pub unsafe extern "C" fn export_multisig(
     descriptor: *const c_char,
-    node_address: *const c_char,
-    socks5: *const c_char,
+    name: *const c_char,
+    format: *const c_char,
 ) -> *mut c_char {
     let descriptor_cstr = CStr::from_ptr(descriptor);
     let descriptor: &str = match descriptor_cstr.to_str() {
         Ok(string) => string,
         Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
     };
-
-    let node_address_cstr = CStr::from_ptr(node_address);
-    let node_address: &str = match node_address_cstr.to_str() {
-        Ok(string) => {
-            if string.contains("electrum") || string.contains("http") {
-                string
-            } else {
-                DEFAULT
-            }
-        }
-        Err(_) => DEFAULT,
+    let name_cstr = CStr::from_ptr(name);
+    let name: &str = match name_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Name").c_stringify(),
     };
-    let socks5_cstr = CStr::from_ptr(socks5);
-    let socks5_option = match socks5_cstr.to_str() {
-        Ok(string) => {
-            if string.to_lowercase() == "none" || string == "" {
-                None
-            } else {
-                Some(string.to_string())
-            }
-        }
-        Err(_) => None,
+    let format_cstr = CStr::from_ptr(format);
+    let format = match format_cstr.to_str() {
+        Ok(string) => multisig::MultisigFormat::from_str(string),
+        Err(_) => multisig::MultisigFormat::Coldcard,
     };
 
-    let config = match WalletConfig::new(descriptor, node_address, socks5_option,None) {
-        Ok(conf) => conf,
-        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
-    };
-    match history::sync_history(config) {
+    match multisig::export(descriptor, name, format) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
 
-/// Syncs to a remote node and fetches utxos of a descriptor wallet.
+/// Imports a Coldcard as a watch-only wallet from the content of its generic JSON export.
+/// Returns the bip44, bip49, bip84 and (if exported) bip86 descriptors, each verified against its first address.
 /// - *OUTPUT*
 /// ```
-///  WalletUtxo{
-///    utxos: Vec<WalletUtxo {
-///      txid: String,
-///      vout: u32,
-///      value: u64,
-///      script_pubkey: String,
-///      keychain: String,
-///    }>;
-///  }
+/// ColdCardWallet {
+///   fingerprint: String,
+///   network: String, ("main" || "test")
+///   descriptors: Vec<ColdCardDescriptor{bip: String, script_type: String, descriptor: String, first: String}>,
+/// }
 /// ```
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn list_unspent(
-    descriptor: *const c_char,
-    node_address: *const c_char,
-    socks5: *const c_char,
-) -> *mut c_char {
-    let descriptor_cstr = CStr::from_ptr(descriptor);
-    let descriptor: &str = match descriptor_cstr.to_str() {
+pub unsafe extern "C" fn import_coldcard(content: *const c_char) -> *mut c_char {
+    let content_cstr = CStr::from_ptr(content);
+    let content: &str = match content_cstr.to_str() {
         Ok(string) => string,
-        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Content").c_stringify(),
     };
 
-    let node_address_cstr = CStr::from_ptr(node_address);
-    let node_address: &str = match node_address_cstr.to_str() {
-        Ok(string) => {
-            if string.contains("electrum") || string.contains("http") {
-                string
-            } else {
-                DEFAULT
-            }
+    match coldcard::import(content) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Imports the xpubs of a hardware wallet from its export: UR (crypto-account, crypto-output, crypto-hdkey),
+/// Coldcard, Passport or Keystone JSON, or one [fingerprint/path]xpub per line (ypub, zpub, Ypub and Zpub are converted).
+/// Single sig keys come with a watch-only descriptor; multisig cosigner keys are meant for sorted_multisig.
+/// - *OUTPUT*
+/// ```
+/// HardwareExport {
+///   format: String,
+///   keys: Vec<WatchOnlyKey{fingerprint: Option<String>, derivation: Option<String>, xpub: String, key: String, script_type: String, descriptor: Option<String>}>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn import_hardware_wallet(content: *const c_char) -> *mut c_char {
+    let content_cstr = CStr::from_ptr(content);
+    let content: &str = match content_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Content").c_stringify(),
+    };
+
+    match hardware::import(content) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Creates an inheritance wallet where the owner can always spend and the heir can spend alone after a delay.
+/// delay is a number of days or blocks (unit "days" || "blocks"). Any other unit or timelock_type is an Input error.
+/// timelock_type "after" locks the heir until a block height, computed from the current height fetched from node_address.
+/// timelock_type "older" locks each utxo for delay blocks after it confirms (max 65535 blocks) and needs no node.
+/// script_type defaults to wsh.
+/// - *OUTPUT*
+/// ```
+/// InheritanceWallet {
+///   policy: String,
+///   descriptor: String,
+///   policy_id: String,
+///   timelock_type: String,
+///   timelock: u32,
+///   delay_blocks: u32,
+///   current_height: Option<u32>,
+///   recovery_height: Option<u32>,
+///   recovery: Vec<String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn inheritance_wallet(
+    owner: *const c_char,
+    heir: *const c_char,
+    delay: *const c_char,
+    unit: *const c_char,
+    timelock_type: *const c_char,
+    script_type: *const c_char,
+    node_address: *const c_char,
+    socks5: *const c_char,
+) -> *mut c_char {
+    let owner_cstr = CStr::from_ptr(owner);
+    let owner: &str = match owner_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Owner").c_stringify(),
+    };
+    let heir_cstr = CStr::from_ptr(heir);
+    let heir: &str = match heir_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Heir").c_stringify(),
+    };
+    let delay_cstr = CStr::from_ptr(delay);
+    let delay: u32 = match delay_cstr.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(result) => result,
+            Err(_) => return S5Error::new(ErrorKind::Input, "Delay").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Delay").c_stringify(),
+    };
+    let unit_cstr = CStr::from_ptr(unit);
+    let unit = match unit_cstr.to_str() {
+        Ok(string) => match custom::DelayUnit::from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Unit").c_stringify(),
+    };
+    let timelock_type_cstr = CStr::from_ptr(timelock_type);
+    let timelock_type = match timelock_type_cstr.to_str() {
+        Ok(string) => match custom::TimelockType::from_str(string) {
+            Ok(result) => result,
+            Err(e) => return e.c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Timelock-Type").c_stringify(),
+    };
+    let script_type_cstr = CStr::from_ptr(script_type);
+    let script_type: Option<policy::ScriptType> = match script_type_cstr.to_str() {
+        Ok(string) if !string.is_empty() => Some(policy::ScriptType::from_str(string)),
+        _ => None,
+    };
+
+    let current_height = if timelock_type == custom::TimelockType::Absolute {
+        let network = descriptor::key_network(owner);
+        let node_address_cstr = CStr::from_ptr(node_address);
+        let node_address: &str = match node_address_cstr.to_str() {
+            Ok(string) if string != DEFAULT && !string.is_empty() => string,
+            _ => match network {
+                Network::Bitcoin => DEFAULT_MAINNET_NODE,
+                _ => DEFAULT_TESTNET_NODE,
+            },
+        };
+        let socks5_cstr = CStr::from_ptr(socks5);
+        let socks5_option = match socks5_cstr.to_str() {
+            Ok(string) => {
+                if string.to_lowercase() == "none" || string.is_empty() {
+                    None
+                } else {
+                    Some(string.to_string())
+                }
+            }
+            Err(_) => None,
+        };
+        let config = match WalletConfig::new("*", node_address, socks5_option, None) {
+            Ok(conf) => conf,
+            Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+        };
+        match height::get_height(config) {
+            Ok(result) => Some(result.height),
+            Err(e) => return e.c_stringify(),
+        }
+    } else {
+        None
+    };
+
+    match custom::inheritance(owner, heir, delay, unit, timelock_type, current_height, script_type) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Checks a timelocked (inheritance) descriptor for utxos the heir can spend within margin_blocks
+/// and builds a psbt moving them with the owner's key before the timelock matures.
+/// For after(height), next_height is required and the utxos move to the next epoch descriptor,
+/// which replaces the current one in the app and in backups.
+/// For older(blocks), the utxos move to a new address of the same wallet and next_height is ignored.
+/// margin_blocks defaults to 1008 (~1 week) and fee_rate (sats/vbyte) to 1.0.
+/// psbt is null when no utxo is due. It is signed if the descriptor holds the owner's private key.
+/// - *OUTPUT*
+/// ```
+/// TimelockRefresh {
+///   timelock_type: String, (after or older)
+///   timelock: u32,
+///   current_height: u32,
+///   next_descriptor: Option<String>,
+///   address: String,
+///   utxos: Vec<RefreshUtxo{outpoint: String, value: u64, confirmation_height: Option<u32>, blocks_remaining: u32, is_due: bool}>,
+///   psbt: Option<String>,
+///   is_finalized: bool,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn refresh_timelock(
+    descriptor: *const c_char,
+    node_address: *const c_char,
+    socks5: *const c_char,
+    next_height: *const c_char,
+    margin_blocks: *const c_char,
+    fee_rate: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let node_address_cstr = CStr::from_ptr(node_address);
+    let node_address: &str = match node_address_cstr.to_str() {
+        Ok(string) => {
+            if string.contains("electrum") || string.contains("http") {
+                string
+            } else {
+                DEFAULT
+            }
+        }
+        Err(_) => DEFAULT,
+    };
+    let socks5_cstr = CStr::from_ptr(socks5);
+    let socks5_option = match socks5_cstr.to_str() {
+        Ok(string) => {
+            if string.to_lowercase() == "none" || string.is_empty() {
+                None
+            } else {
+                Some(string.to_string())
+            }
+        }
+        Err(_) => None,
+    };
+    let config = match WalletConfig::new(descriptor, node_address, socks5_option, None) {
+        Ok(conf) => conf,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let next_height_cstr = CStr::from_ptr(next_height);
+    let next_height: Option<u32> = match next_height_cstr.to_str() {
+        Ok(string) if !string.is_empty() => match string.parse::<u32>() {
+            Ok(result) => Some(result),
+            Err(_) => return S5Error::new(ErrorKind::Input, "Next-Height").c_stringify(),
+        },
+        _ => None,
+    };
+    let margin_blocks_cstr = CStr::from_ptr(margin_blocks);
+    let margin_blocks: u32 = match margin_blocks_cstr.to_str() {
+        Ok(string) => string.parse::<u32>().unwrap_or(1008),
+        Err(_) => 1008,
+    };
+    let fee_rate_cstr = CStr::from_ptr(fee_rate);
+    let fee_rate: f32 = match fee_rate_cstr.to_str() {
+        Ok(string) => string.parse::<f32>().unwrap_or(1.0),
+        Err(_) => 1.0,
+    };
+
+    match refresh::refresh(config, next_height, margin_blocks, fee_rate) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Gets the policy id from a given descriptor.
+/// - *OUTPUT*
+/// ```
+/// String,String
+/// ```
+/// First string is whether specifying a policy id is required.
+/// Second string is the policy id.
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn policy_id(descriptor: *const c_char) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor_str: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+    let config = match WalletConfig::new_offline(descriptor_str,None) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+    match policy::id(config) {
+        Ok(result) => CString::new(format!("{},{}", result.0, result.1))
+            .unwrap()
+            .into_raw(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Computes the checksum of a descriptor and returns the descriptor with it appended.
+/// If the descriptor already has a checksum, it is verified first.
+/// All functions accepting a descriptor reject one with an invalid checksum.
+/// - *OUTPUT*
+/// ```
+/// DescriptorChecksum {
+///   descriptor: String,
+///   checksum: String,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn descriptor_checksum(descriptor: *const c_char) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor_str: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+    match descriptor::with_checksum(descriptor_str) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Analyzes the deposit keychain of a descriptor.
+/// threshold is the number of top level conditions required. is_multisig is set when those are all keys.
+/// - *OUTPUT*
+/// ```
+/// DescriptorAnalysis {
+///   script_type: String, ("pkh" || "sh-wpkh" || "wpkh" || "sh" || "wsh" || "sh-wsh" || "tr")
+///   network: String, ("main" || "test")
+///   keys: Vec<DescriptorKeyInfo {
+///     fingerprint: String,
+///     origin_path: Option<String>,
+///     key: String,
+///     derivation_path: Option<String>,
+///     is_private: bool,
+///   }>,
+///   has_private_keys: bool,
+///   policy: String,
+///   threshold: Option<usize>,
+///   is_multisig: bool,
+///   timelocks: Vec<Timelock {
+///     kind: String, ("absolute" || "relative")
+///     value: u32,
+///     unit: String, ("height" || "timestamp" || "blocks" || "time")
+///   }>,
+///   max_satisfaction_weight: Option<usize>,
+///   policy_id: String,
+///   requires_path: bool,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn analyze_descriptor(descriptor: *const c_char) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor_str: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+    let config = match WalletConfig::new_offline(descriptor_str, None) {
+        Ok(conf) => conf,
+        Err(e) => return e.c_stringify(),
+    };
+    match descriptor::analyze(config) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Converts a descriptor with private keys into a watch-only descriptor to share with cosigners and watch-only devices.
+/// xprvs are replaced by their xpubs with origins preserved. The result includes a checksum.
+/// - *OUTPUT*
+/// ```
+/// PublicDescriptor {
+///   descriptor: String,
+///   had_private_keys: bool,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn public_descriptor(descriptor: *const c_char) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor_str: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+    match descriptor::to_public(descriptor_str) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Syncs to a remote node and populates an SQLite db at a given path
+/// Use before other SQLite Wallet functions.
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite_sync(
+    db_path: *const c_char,    
+    descriptor: *const c_char,
+    node_address: *const c_char,
+    socks5: *const c_char
+) -> *mut c_char{
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let node_address_cstr = CStr::from_ptr(node_address);
+    let node_address: &str = match node_address_cstr.to_str() {
+        Ok(string) => {
+            if string.contains("electrum") || string.contains("http") {
+                string
+            } else {
+                DEFAULT
+            }
+        }
+        Err(_) => DEFAULT,
+    };
+    let socks5_cstr = CStr::from_ptr(socks5);
+    let socks5_option = match socks5_cstr.to_str() {
+        Ok(string) => {
+            if string.to_lowercase() == "none" || string == "" {
+                None
+            } else {
+                Some(string.to_string())
+            }
+        }
+        Err(_) => None,
+    };
+    let db_path_cstr = CStr::from_ptr(db_path);
+    let db_path: String = match db_path_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    };
+    let config = match WalletConfig::new(descriptor, node_address, socks5_option,Some(db_path)) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+    match wallet::sync::sqlite(config){
+        Ok(_)=> CString::new("DONE").unwrap().into_raw(),
+        Err(e)=>e.c_stringify()
+    }
+}
+
+/// Fetches balance of a descriptor wallet from Sqlite db path.
+/// - *OUTPUT*
+/// ```
+/// WalletBalance {
+///   balance: u64
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite_balance(
+    descriptor: *const c_char,
+    db_path: *const c_char
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+    let db_path_cstr = CStr::from_ptr(db_path);
+    let db_path: String = match db_path_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+    match history::sqlite_balance(config) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Syncs to a remote node and fetches balance of a descriptor wallet.
+/// - *OUTPUT*
+/// ```
+/// WalletBalance {
+///   balance: u64
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sync_balance(
+    descriptor: *const c_char,
+    node_address: *const c_char,
+    socks5: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let node_address_cstr = CStr::from_ptr(node_address);
+    let node_address: &str = match node_address_cstr.to_str() {
+        Ok(string) => {
+            if string.contains("electrum") || string.contains("http") {
+                string
+            } else {
+                DEFAULT
+            }
+        }
+        Err(_) => DEFAULT,
+    };
+    let socks5_cstr = CStr::from_ptr(socks5);
+    let socks5_option = match socks5_cstr.to_str() {
+        Ok(string) => {
+            if string.to_lowercase() == "none" || string == "" {
+                None
+            } else {
+                Some(string.to_string())
+            }
+        }
+        Err(_) => None,
+    };
+
+    let config = match WalletConfig::new(descriptor, node_address, socks5_option,None) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+    match history::sync_balance(config) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Fetches history of a descriptor wallet from a SQLite DB at a specified path.
+/// - *OUTPUT*
+/// ```
+///  WalletHistory{
+///    history: Vec<Transaction {
+///      timestamp: u64,
+///      height: u32,
+///      verified: bool,
+///      txid: String,
+///      received: u64,
+///      sent: u64,
+///      fee: u64,
+///    }>;
+///  }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite_history(
+    descriptor: *const c_char,
+    db_path: *const c_char
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+    let db_path_cstr = CStr::from_ptr(db_path);
+    let db_path: String = match db_path_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+    match history::sqlite_history(config) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Syncs to a remote node and fetches history of a descriptor wallet.
+/// - *OUTPUT*
+/// ```
+///  WalletHistory{
+///    history: Vec<Transaction {
+///      timestamp: u64,
+///      height: u32,
+///      verified: bool,
+///      txid: String,
+///      received: u64,
+///      sent: u64,
+///      fee: u64,
+///    }>;
+///  }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sync_history(
+    descriptor: *const c_char,
+    node_address: *const c_char,
+    socks5: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let node_address_cstr = CStr::from_ptr(node_address);
+    let node_address: &str = match node_address_cstr.to_str() {
+        Ok(string) => {
+            if string.contains("electrum") || string.contains("http") {
+                string
+            } else {
+                DEFAULT
+            }
+        }
+        Err(_) => DEFAULT,
+    };
+    let socks5_cstr = CStr::from_ptr(socks5);
+    let socks5_option = match socks5_cstr.to_str() {
+        Ok(string) => {
+            if string.to_lowercase() == "none" || string == "" {
+                None
+            } else {
+                Some(string.to_string())
+            }
+        }
+        Err(_) => None,
+    };
+
+    let config = match WalletConfig::new(descriptor, node_address, socks5_option,None) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+    match history::sync_history(config) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Syncs to a remote node and fetches utxos of a descriptor wallet.
+/// - *OUTPUT*
+/// ```
+///  WalletUtxo{
+///    utxos: Vec<WalletUtxo {
+///      txid: String,
+///      vout: u32,
+///      value: u64,
+///      script_pubkey: String,
+///      keychain: String,
+///    }>;
+///  }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn list_unspent(
+    descriptor: *const c_char,
+    node_address: *const c_char,
+    socks5: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let node_address_cstr = CStr::from_ptr(node_address);
+    let node_address: &str = match node_address_cstr.to_str() {
+        Ok(string) => {
+            if string.contains("electrum") || string.contains("http") {
+                string
+            } else {
+                DEFAULT
+            }
         }
         Err(_) => DEFAULT,
     };
 
-    let socks5_cstr = CStr::from_ptr(socks5);
-    let socks5_option = match socks5_cstr.to_str() {
-        Ok(string) => {
-            if string.to_lowercase() == "none" || string == "" {
-                None
-            } else {
-                Some(string.to_string())
-            }
-        }
-        Err(_) => None,
+    let socks5_cstr = CStr::from_ptr(socks5);
+    let socks5_option = match socks5_cstr.to_str() {
+        Ok(string) => {
+            if string.to_lowercase() == "none" || string == "" {
+                None
+            } else {
+                Some(string.to_string())
+            }
+        }
+        Err(_) => None,
+    };
+    let config = match WalletConfig::new(descriptor, node_address, socks5_option,None) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+    match utxo::list_unspent(config) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Gets the last unused address from an SQLite DB at a given path.
+/// - *OUTPUT*
+/// ```
+/// WalletAddress {
+///   address: String,
+///   index: String,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite_last_unused_address(
+    descriptor: *const c_char,
+    db_path: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let db_path_cstr = CStr::from_ptr(db_path);
+    let db_path: String = match db_path_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+
+    match address::sqlite_generate(config) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Reserves a new deposit address for a descriptor wallet from SQLite DB.
+/// The index is incremented in the db, so the same address is never handed out twice.
+/// warning is set if the address is beyond the gap limit of the wallet history.
+/// - *OUTPUT*
+/// ```
+/// WalletAddress {
+///   address: String,
+///   index: String,
+///   warning: Option<String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite_new_address(
+    descriptor: *const c_char,
+    db_path: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
     };
-    let config = match WalletConfig::new(descriptor, node_address, socks5_option,None) {
+
+    let db_path_cstr = CStr::from_ptr(db_path);
+    let db_path: String = match db_path_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
         Ok(conf) => conf,
         Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
     };
-    match utxo::list_unspent(config) {
+
+    match address::sqlite_reserve(config) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
 }
 
-/// Gets the last unused address from an SQLite DB at a given path.
+/// Gets the deposit address at a given index for a descriptor wallet from SQLite DB, without reserving it.
+/// warning is set if the index is beyond the gap limit of the wallet history.
 /// - *OUTPUT*
 /// ```
 /// WalletAddress {
 ///   address: String,
 ///   index: String,
+///   warning: Option<String>,
 /// }
 /// ```
 /// # Safety
 /// - This function is unsafe because it dereferences and a returns raw pointer.
 /// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
 #[no_mangle]
-pub unsafe extern "C" fn sqlite_last_unused_address(
+pub unsafe extern "C" fn sqlite_peek_address(
     descriptor: *const c_char,
     db_path: *const c_char,
+    index: *const c_char,
 ) -> *mut c_char {
     let descriptor_cstr = CStr::from_ptr(descriptor);
     let descriptor: &str = match descriptor_cstr.to_str() {
@@ -761,7 +1587,63 @@ pub unsafe extern "C" fn sqlite_last_unused_address(
         Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
     };
 
-    match address::sqlite_generate(config) {
+    let index_cstr = CStr::from_ptr(index);
+    let address_index: u32 = match index_cstr.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(i) => i,
+            Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+    };
+
+    match address::sqlite_peek(config, address_index) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Lists deposit and change addresses of a descriptor wallet from SQLite DB up to the highest revealed or used index.
+/// Sync the db first for up to date usage.
+/// - *OUTPUT*
+/// ```
+/// AddressBook {
+///   deposit: Vec<AddressEntry {
+///     address: String,
+///     index: u32,
+///     used: bool,
+///     reused: bool,
+///     received: u64,
+///     tx_count: usize,
+///   }>,
+///   change: Vec<AddressEntry>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite_address_book(
+    descriptor: *const c_char,
+    db_path: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let db_path_cstr = CStr::from_ptr(db_path);
+    let db_path: String = match db_path_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+
+    match address::sqlite_list(config) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
@@ -796,20 +1678,240 @@ pub unsafe extern "C" fn get_address(
         Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
     };
 
-    let index_cstr = CStr::from_ptr(index);
-    let address_index: u32 = match index_cstr.to_str() {
-        Ok(string) => match string.parse::<u32>() {
-            Ok(i) => i,
-            Err(_) => {
-                return CString::new("Error: Address Index Input.")
-                    .unwrap()
-                    .into_raw()
-            }
-        },
-        Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+    let index_cstr = CStr::from_ptr(index);
+    let address_index: u32 = match index_cstr.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(i) => i,
+            Err(_) => {
+                return CString::new("Error: Address Index Input.")
+                    .unwrap()
+                    .into_raw()
+            }
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+    };
+
+    match address::generate(config, address_index) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Gets the change address for a descriptor wallet at a given index.
+/// - *OUTPUT*
+/// ```
+/// WalletAddress {
+///   address: String,
+///   index: String,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_change_address(
+    descriptor: *const c_char,
+    index: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, None) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+
+    let index_cstr = CStr::from_ptr(index);
+    let address_index: u32 = match index_cstr.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(i) => i,
+            Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+    };
+
+    match address::generate_change(config, address_index) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Checks whether an address belongs to a descriptor wallet, searching deposit and change indexes from start to end.
+/// start and end can be empty strings and default to 0 and 1000.
+/// - *OUTPUT*
+/// ```
+/// AddressOwnership {
+///   address: String,
+///   is_mine: bool,
+///   keychain: Option<String>, ("deposit" || "change")
+///   index: Option<u32>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn find_address(
+    descriptor: *const c_char,
+    address: *const c_char,
+    start: *const c_char,
+    end: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, None) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+
+    let address_cstr = CStr::from_ptr(address);
+    let address: &str = match address_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Address").c_stringify(),
+    };
+
+    let start_cstr = CStr::from_ptr(start);
+    let start: u32 = match start_cstr.to_str() {
+        Ok("") => 0,
+        Ok(string) => match string.parse::<u32>() {
+            Ok(i) => i,
+            Err(_) => return S5Error::new(ErrorKind::Input, "Start-Index").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Start-Index").c_stringify(),
+    };
+
+    let end_cstr = CStr::from_ptr(end);
+    let end: u32 = match end_cstr.to_str() {
+        Ok("") => address::DEFAULT_SEARCH_RANGE,
+        Ok(string) => match string.parse::<u32>() {
+            Ok(i) => i,
+            Err(_) => return S5Error::new(ErrorKind::Input, "End-Index").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "End-Index").c_stringify(),
+    };
+
+    match address::find(config, address, start, end) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Gets a BIP21 payment uri for the deposit address of a descriptor wallet at a given index.
+/// Amount is in sats and is optional, as are label and message. Use an empty string to skip them.
+/// - *OUTPUT*
+/// ```
+/// PaymentUri {
+///   uri: String,
+///   address: String,
+///   index: String,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn get_payment_uri(
+    descriptor: *const c_char,
+    index: *const c_char,
+    amount: *const c_char,
+    label: *const c_char,
+    message: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor,None) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+
+    let index_cstr = CStr::from_ptr(index);
+    let address_index: u32 = match index_cstr.to_str() {
+        Ok(string) => match string.parse::<u32>() {
+            Ok(i) => i,
+            Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Address-Index").c_stringify(),
+    };
+
+    let amount_cstr = CStr::from_ptr(amount);
+    let amount: Option<u64> = match amount_cstr.to_str() {
+        Ok("") => None,
+        Ok(string) => match string.parse::<u64>() {
+            Ok(i) => Some(i),
+            Err(_) => return S5Error::new(ErrorKind::Input, "Amount").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Amount").c_stringify(),
+    };
+
+    let label_cstr = CStr::from_ptr(label);
+    let label: Option<String> = match label_cstr.to_str() {
+        Ok("") => None,
+        Ok(string) => Some(string.to_string()),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Label").c_stringify(),
+    };
+
+    let message_cstr = CStr::from_ptr(message);
+    let message: Option<String> = match message_cstr.to_str() {
+        Ok("") => None,
+        Ok(string) => Some(string.to_string()),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Message").c_stringify(),
+    };
+
+    match bip21::generate(config, address_index, amount, label, message) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Parses a BIP21 payment uri and checks that its address belongs to the given network.
+/// Amount is converted into sats. All other params (eg. lightning, pj) are returned in params.
+/// - *OUTPUT*
+/// ```
+/// PaymentRequest {
+///   address: String,
+///   amount: Option<u64>,
+///   label: Option<String>,
+///   message: Option<String>,
+///   params: BTreeMap<String, String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn parse_payment_uri(
+    network: *const c_char,
+    uri: *const c_char,
+) -> *mut c_char {
+    let network_cstr = CStr::from_ptr(network);
+    let network_str: &str = match network_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => "test",
+    };
+    let network = match network_str {
+        "main" => Network::Bitcoin,
+        "test" => Network::Testnet,
+        _ => Network::Testnet,
+    };
+
+    let uri_cstr = CStr::from_ptr(uri);
+    let uri: &str = match uri_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Payment-Uri").c_stringify(),
     };
 
-    match address::generate(config, address_index) {
+    match bip21::parse(network, uri) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
@@ -980,6 +2082,86 @@ pub unsafe extern "C" fn get_weight(descriptor: *const c_char, psbt: *const c_ch
 }
 
 
+/// Validates tx outputs for a given descriptor wallet against its balance in an SQLite DB.
+/// Use before building a transaction to catch invalid addresses, dust and insufficient funds.
+/// TxOutputs follow the same format as sqlite_build_tx. Amounts can include a unit (btc, sats, bits).
+/// - *OUTPUT*
+/// ```
+///  TxValidation {
+///    outputs: Vec<OutputValidation {
+///      address: String,
+///      amount: Option<u64>,
+///      dust_limit: u64,
+///      valid: bool,
+///      error: Option<String>,
+///    }>,
+///    total: u64,
+///    fee: u64,
+///    balance: u64,
+///    valid: bool,
+///    error: Option<String>,
+///  }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite_validate_tx_outputs(
+    descriptor: *const c_char,
+    db_path: *const c_char,
+    tx_outputs: *const c_char,
+    fee_absolute: *const c_char,
+    sweep: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let db_path_cstr = CStr::from_ptr(db_path);
+    let db_path: String = match db_path_cstr.to_str() {
+        Ok(string) => string.to_string(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "DB Path").c_stringify(),
+    };
+
+    let config = match WalletConfig::new_offline(descriptor, Some(db_path)) {
+        Ok(conf) => conf,
+        Err(e) => return S5Error::new(ErrorKind::Internal, &e.message).c_stringify(),
+    };
+
+    let tx_outputs_cstr = CStr::from_ptr(tx_outputs);
+    let tx_outputs_str: &str = match tx_outputs_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "To-Address").c_stringify(),
+    };
+
+    let tx_outputs = match psbt::TxOutput::vec_from_str(tx_outputs_str) {
+        Ok(result) => result,
+        Err(e) => return S5Error::new(ErrorKind::Input, &e.message).c_stringify(),
+    };
+
+    let sweep_cstr = CStr::from_ptr(sweep);
+    let sweep: bool = match sweep_cstr.to_str() {
+        Ok(string) => string == "true",
+        Err(_) => false,
+    };
+
+    let fee_absolute_cstr = CStr::from_ptr(fee_absolute);
+    let fee_absolute: u64 = match fee_absolute_cstr.to_str() {
+        Ok(string) => match string.parse::<u64>() {
+            Ok(i) => i,
+            Err(_) => return S5Error::new(ErrorKind::Input, "Fee Rate").c_stringify(),
+        },
+        Err(_) => return S5Error::new(ErrorKind::Input, "Fee Rate").c_stringify(),
+    };
+
+    match amount::sqlite_validate(config, tx_outputs, fee_absolute, sweep) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
 /// Builds a transaction for a given descriptor wallet from SQLite DB history.
 /// Supports sending to multiple outputs.
 /// TxOutputs have to be provided as a stringified JSON array.
@@ -992,8 +2174,12 @@ pub unsafe extern "C" fn get_weight(descriptor: *const c_char, psbt: *const c_ch
 /// TxOutputs = Vec<TxOutput>
 /// ```
 ///
-/// If sweep is set to true, amount value is ignored and will default to None.
-/// Set amount to 0 for sweep.
+/// If sweep is set to true with a single output, its amount is ignored and the wallet is drained to it.
+/// Sweeping to multiple outputs either sends fixed amounts and drains the remainder to the one output
+/// with amount 0, or splits the funds by percentage i.e. address:60%,address:40%.
+/// utxos restricts the tx to spend only the given txid:vout,txid:vout - can be empty string.
+/// policy_path is either {"id":..,"path":[..]}, a map of node ids {"id":[..],"id":[..]}
+/// or per keychain {"external":{"id":[..]},"internal":{"id":[..]}} - can be empty string.
 /// - *OUTPUT*
 /// ```
 ///  WalletPSBT {
@@ -1012,6 +2198,7 @@ pub unsafe extern "C" fn sqlite_build_tx(
     fee_absolute: *const c_char,
     policy_path: *const c_char,
     sweep: *const c_char,
+    utxos: *const c_char,
 ) -> *mut c_char {
     let descriptor_cstr = CStr::from_ptr(descriptor);
     let descriptor: &str = match descriptor_cstr.to_str() {
@@ -1046,10 +2233,7 @@ pub unsafe extern "C" fn sqlite_build_tx(
         Ok(string) => string,
         Err(_) => return S5Error::new(ErrorKind::Input, "Policy-Path").c_stringify(),
     };
-    let policy_path = match psbt::PolicyPath::from_json_str(policy_path_str) {
-        Ok(result) => Some(result.to_btreemap()),
-        Err(_) => None,
-    };
+    let policy_path = psbt::KeychainPolicyPath::from_json_str(policy_path_str).ok();
 
     let sweep_cstr = CStr::from_ptr(sweep);
     let sweep: bool = match sweep_cstr.to_str() {
@@ -1066,7 +2250,22 @@ pub unsafe extern "C" fn sqlite_build_tx(
         Err(_) => return S5Error::new(ErrorKind::Input, "Fee Rate").c_stringify(),
     };
 
-    match psbt::sqlite_build(config, tx_outputs, fee_absolute, policy_path, sweep) {
+    let utxos_cstr = CStr::from_ptr(utxos);
+    let utxos = match utxos_cstr.to_str() {
+        Ok(string) => {
+            if string.is_empty() {
+                None
+            } else {
+                match psbt::outpoints_from_str(string) {
+                    Ok(result) => Some(result),
+                    Err(e) => return e.c_stringify(),
+                }
+            }
+        }
+        Err(_) => return S5Error::new(ErrorKind::Input, "Utxos").c_stringify(),
+    };
+
+    match psbt::sqlite_build(config, tx_outputs, fee_absolute, policy_path, sweep, utxos) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
@@ -1084,8 +2283,12 @@ pub unsafe extern "C" fn sqlite_build_tx(
 /// TxOutputs = Vec<TxOutput>
 /// ```
 ///
-/// If sweep is set to true, amount value is ignored and will default to None.
-/// Set amount to 0 for sweep.
+/// If sweep is set to true with a single output, its amount is ignored and the wallet is drained to it.
+/// Sweeping to multiple outputs either sends fixed amounts and drains the remainder to the one output
+/// with amount 0, or splits the funds by percentage i.e. address:60%,address:40%.
+/// utxos restricts the tx to spend only the given txid:vout,txid:vout - can be empty string.
+/// policy_path is either {"id":..,"path":[..]}, a map of node ids {"id":[..],"id":[..]}
+/// or per keychain {"external":{"id":[..]},"internal":{"id":[..]}} - can be empty string.
 /// - *OUTPUT*
 /// ```
 ///  WalletPSBT {
@@ -1105,6 +2308,7 @@ pub unsafe extern "C" fn build_tx(
     fee_absolute: *const c_char,
     policy_path: *const c_char,
     sweep: *const c_char,
+    utxos: *const c_char,
 ) -> *mut c_char {
     let descriptor_cstr = CStr::from_ptr(descriptor);
     let descriptor: &str = match descriptor_cstr.to_str() {
@@ -1155,10 +2359,7 @@ pub unsafe extern "C" fn build_tx(
         Ok(string) => string,
         Err(_) => return S5Error::new(ErrorKind::Input, "Policy-Path").c_stringify(),
     };
-    let policy_path = match psbt::PolicyPath::from_json_str(policy_path_str) {
-        Ok(result) => Some(result.to_btreemap()),
-        Err(_) => None,
-    };
+    let policy_path = psbt::KeychainPolicyPath::from_json_str(policy_path_str).ok();
 
     let sweep_cstr = CStr::from_ptr(sweep);
     let sweep: bool = match sweep_cstr.to_str() {
@@ -1175,7 +2376,22 @@ pub unsafe extern "C" fn build_tx(
         Err(_) => return S5Error::new(ErrorKind::Input, "Fee Rate").c_stringify(),
     };
 
-    match psbt::build(config, tx_outputs, fee_absolute, policy_path, sweep) {
+    let utxos_cstr = CStr::from_ptr(utxos);
+    let utxos = match utxos_cstr.to_str() {
+        Ok(string) => {
+            if string.is_empty() {
+                None
+            } else {
+                match psbt::outpoints_from_str(string) {
+                    Ok(result) => Some(result),
+                    Err(e) => return e.c_stringify(),
+                }
+            }
+        }
+        Err(_) => return S5Error::new(ErrorKind::Input, "Utxos").c_stringify(),
+    };
+
+    match psbt::build(config, tx_outputs, fee_absolute, policy_path, sweep, utxos) {
         Ok(result) => result.c_stringify(),
         Err(e) => e.c_stringify(),
     }
@@ -1337,6 +2553,219 @@ pub unsafe extern "C" fn decode_psbt(network: *const c_char, psbt: *const c_char
     }
 }
 
+/// Encodes a base64 PSBT (ur:crypto-psbt) or a public descriptor (ur:crypto-output) as BC-UR parts for an animated QR code.
+/// max_fragment_length is the number of payload bytes per part (default 200); payloads that fit return a single part.
+/// part_count defaults to twice the number of fragments; the mixed parts after seq_len let a scanner recover missed ones.
+/// - *OUTPUT*
+/// ```
+/// UrParts {
+///   ur_type: String,
+///   seq_len: usize,
+///   parts: Vec<String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn ur_encode(
+    content: *const c_char,
+    max_fragment_length: *const c_char,
+    part_count: *const c_char,
+) -> *mut c_char {
+    let content_cstr = CStr::from_ptr(content);
+    let content: &str = match content_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Content").c_stringify(),
+    };
+    let max_fragment_length_cstr = CStr::from_ptr(max_fragment_length);
+    let max_fragment_length: usize = match max_fragment_length_cstr.to_str() {
+        Ok(string) => match string.parse::<usize>() {
+            Ok(result) if result > 0 => result,
+            _ => qr::DEFAULT_FRAGMENT_LENGTH,
+        },
+        Err(_) => qr::DEFAULT_FRAGMENT_LENGTH,
+    };
+    let part_count_cstr = CStr::from_ptr(part_count);
+    let part_count: Option<usize> = match part_count_cstr.to_str() {
+        Ok(string) => string.parse::<usize>().ok(),
+        Err(_) => None,
+    };
+
+    match qr::encode(content, max_fragment_length, part_count) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Decodes all the UR parts scanned so far, one per line, in any order and with repeats.
+/// The library keeps no state between calls: append each newly scanned part and call again until is_complete.
+/// Use ur_decoder_new to pass each part only once instead.
+/// A crypto-psbt completes into a base64 psbt, a crypto-output or crypto-account into descriptors.
+/// - *OUTPUT*
+/// ```
+/// UrScan {
+///   ur_type: Option<String>,
+///   received: usize,
+///   expected: usize,
+///   is_complete: bool,
+///   psbt: Option<String>,
+///   descriptors: Vec<String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn ur_decode(parts: *const c_char) -> *mut c_char {
+    let parts_cstr = CStr::from_ptr(parts);
+    let parts: Vec<String> = match parts_cstr.to_str() {
+        Ok(string) => string.split_whitespace().map(|part| part.to_string()).collect(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Parts").c_stringify(),
+    };
+
+    match qr::decode(&parts) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Creates a UR decoder that keeps the parts received so far, for scanners that pass each part once as it is scanned.
+/// Pass the decoder to ur_decoder_receive for every scanned part.
+/// # Safety
+/// - This function is unsafe because it returns a raw pointer.
+/// - ENSURE that the decoder is passed into ur_decoder_free(decoder: *mut UrDecoder) after use.
+#[no_mangle]
+pub unsafe extern "C" fn ur_decoder_new() -> *mut UrDecoder {
+    Box::into_raw(Box::new(UrDecoder::new()))
+}
+
+/// Adds a scanned UR part to a decoder created with ur_decoder_new and returns its progress.
+/// Call again with each new part until is_complete.
+/// - *OUTPUT*
+/// ```
+/// UrScan {
+///   ur_type: Option<String>,
+///   received: usize,
+///   expected: usize,
+///   is_complete: bool,
+///   psbt: Option<String>,
+///   descriptors: Vec<String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn ur_decoder_receive(decoder: *mut UrDecoder, part: *const c_char) -> *mut c_char {
+    let decoder = match decoder.as_mut() {
+        Some(decoder) => decoder,
+        None => return S5Error::new(ErrorKind::Input, "Decoder").c_stringify(),
+    };
+    let part_cstr = CStr::from_ptr(part);
+    let part: &str = match part_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Part").c_stringify(),
+    };
+
+    if let Err(e) = decoder.receive(part) {
+        return e.c_stringify();
+    }
+    match qr::scan(decoder) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Frees a decoder created with ur_decoder_new.
+/// # Safety
+/// - This function is unsafe because it deferences a raw pointer.
+/// - The decoder must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn ur_decoder_free(decoder: *mut UrDecoder) {
+    if decoder.is_null() {
+        return;
+    }
+    drop(Box::from_raw(decoder));
+}
+
+/// Splits a base64 PSBT, a hex transaction or a JSON document into BBQr parts for a Coldcard Q.
+/// encoding is Z (compressed, default), 2 (base32) or H (hex); Z falls back to 2 when compression does not help.
+/// max_part_length is the number of characters per part, header included (default 600).
+/// - *OUTPUT*
+/// ```
+/// BbqrParts {
+///   encoding: String,
+///   file_type: String,
+///   parts: Vec<String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn bbqr_encode(
+    content: *const c_char,
+    encoding: *const c_char,
+    max_part_length: *const c_char,
+) -> *mut c_char {
+    let content_cstr = CStr::from_ptr(content);
+    let content: &str = match content_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Content").c_stringify(),
+    };
+    let encoding_cstr = CStr::from_ptr(encoding);
+    let encoding: &str = match encoding_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => "Z",
+    };
+    let max_part_length_cstr = CStr::from_ptr(max_part_length);
+    let max_part_length: usize = match max_part_length_cstr.to_str() {
+        Ok(string) => match string.parse::<usize>() {
+            Ok(result) if result > 0 => result,
+            _ => qr::DEFAULT_BBQR_PART_LENGTH,
+        },
+        Err(_) => qr::DEFAULT_BBQR_PART_LENGTH,
+    };
+
+    match qr::bbqr_encode(content, encoding, max_part_length) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
+/// Joins all the BBQr parts scanned so far, one per line, in any order and with repeats.
+/// The library keeps no state between calls: append each newly scanned part and call again until is_complete.
+/// A complete file is returned as a base64 psbt, a hex tx or json.
+/// - *OUTPUT*
+/// ```
+/// BbqrScan {
+///   file_type: Option<String>,
+///   received: usize,
+///   expected: usize,
+///   is_complete: bool,
+///   psbt: Option<String>,
+///   tx: Option<String>,
+///   json: Option<String>,
+/// }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn bbqr_decode(parts: *const c_char) -> *mut c_char {
+    let parts_cstr = CStr::from_ptr(parts);
+    let parts: Vec<String> = match parts_cstr.to_str() {
+        Ok(string) => string.split_whitespace().map(|part| part.to_string()).collect(),
+        Err(_) => return S5Error::new(ErrorKind::Input, "Parts").c_stringify(),
+    };
+
+    match qr::bbqr_decode(&parts) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
 /// Signs a PSBT with a descriptor.
 /// Can only be used with descriptors containing private key(s).
 /// - *OUTPUT*
@@ -1439,6 +2868,86 @@ pub unsafe extern "C" fn broadcast_tx(
     }
 }
 
+/// Sweeps an external WIF, xprv or descriptor with private keys into the next unused address of descriptor.
+/// Scans pkh, sh-wpkh, wpkh and tr for funds and builds one signed tx per funded script type.
+/// Each psbt can be broadcasted with broadcast_tx.
+/// fee_rate is in sats/vbyte and defaults to 1.0.
+/// - *OUTPUT*
+/// ```
+///  KeySweep {
+///    address: String,
+///    txs: Vec<SweepTx {
+///      script_type: String,
+///      balance: u64, (swept)
+///      amount: u64, (received, balance - fee)
+///      fee: u64,
+///      psbt: String,
+///      is_finalized: bool,
+///    }>,
+///  }
+/// ```
+/// # Safety
+/// - This function is unsafe because it dereferences and a returns raw pointer.
+/// - ENSURE that result is passed into cstring_free(ptr: *mut c_char) after use.
+#[no_mangle]
+pub unsafe extern "C" fn sweep_private_key(
+    descriptor: *const c_char,
+    node_address: *const c_char,
+    socks5: *const c_char,
+    private_key: *const c_char,
+    fee_rate: *const c_char,
+) -> *mut c_char {
+    let descriptor_cstr = CStr::from_ptr(descriptor);
+    let descriptor: &str = match descriptor_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Descriptor").c_stringify(),
+    };
+
+    let node_address_cstr = CStr::from_ptr(node_address);
+    let node_address: &str = match node_address_cstr.to_str() {
+        Ok(string) => {
+            if string.contains("electrum") || string.contains("http") {
+                string
+            } else {
+                DEFAULT
+            }
+        }
+        Err(_) => DEFAULT,
+    };
+    let socks5_cstr = CStr::from_ptr(socks5);
+    let socks5_option = match socks5_cstr.to_str() {
+        Ok(string) => {
+            if string.to_lowercase() == "none" || string.is_empty() {
+                None
+            } else {
+                Some(string.to_string())
+            }
+        }
+        Err(_) => None,
+    };
+    let config = match WalletConfig::new(descriptor, node_address, socks5_option, None) {
+        Ok(conf) => conf,
+        Err(e) => return e.c_stringify(),
+    };
+
+    let private_key_cstr = CStr::from_ptr(private_key);
+    let private_key: &str = match private_key_cstr.to_str() {
+        Ok(string) => string,
+        Err(_) => return S5Error::new(ErrorKind::Input, "Private-Key").c_stringify(),
+    };
+
+    let fee_rate_cstr = CStr::from_ptr(fee_rate);
+    let fee_rate: f32 = match fee_rate_cstr.to_str() {
+        Ok(string) => string.parse::<f32>().unwrap_or(1.0),
+        Err(_) => 1.0,
+    };
+
+    match sweep::sweep(config, private_key, fee_rate) {
+        Ok(result) => result.c_stringify(),
+        Err(e) => e.c_stringify(),
+    }
+}
+
 /// Broadcasts a signed transaction to a remote node.
 /// - *OUTPUT*
 /// ```
@@ -1501,7 +3010,7 @@ pub unsafe extern "C" fn broadcast_hex(
     }
 }
 
-/// Checks if an extended public key is valid.
+/// Checks if an extended public key is valid. SLIP-132 ypub, zpub, Ypub, Zpub (upub, vpub, Upub, Vpub) are valid.
 /// Do not use the key source while checking an xpub i.e. remove [fingerprint/derivation/path/values] and only provide the xpub/tpub.
 /// - *OUTPUT*
 /// ```
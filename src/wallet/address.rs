@@ -1,17 +1,29 @@
 use crate::config::WalletConfig;
 use crate::e::{ErrorKind, S5Error};
-use bdk::database::{MemoryDatabase, SqliteDatabase};
-use bdk::wallet::AddressIndex::Peek;
-use bdk::Wallet;
+use bdk::database::{BatchDatabase, Database, MemoryDatabase, SqliteDatabase};
+use bdk::wallet::AddressIndex::{New, Peek};
+use bdk::{KeychainKind, Wallet};
+use bitcoin::util::address::Address;
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::os::raw::c_char;
 
+/// Number of consecutive unused addresses a restoring wallet scans before it stops looking.
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+/// Number of indexes searched per keychain when looking up an address.
+pub const DEFAULT_SEARCH_RANGE: u32 = 1_000;
+/// Largest range that can be searched at once.
+pub const MAX_SEARCH_RANGE: u32 = 100_000;
+
 /// FFI Output
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WalletAddress {
     pub address: String,
     pub index: String,
+    #[serde(default)]
+    pub warning: Option<String>,
 }
 impl WalletAddress {
     pub fn c_stringify(&self) -> *mut c_char {
@@ -41,11 +53,120 @@ pub fn generate(config: WalletConfig, index: u32) -> Result<WalletAddress, S5Err
     match wallet.get_address(Peek(index)) {
         Ok(address) => Ok(WalletAddress {
             address: address.to_string(),
-            index: index.to_string()
+            index: index.to_string(),
+            warning: None,
+        }),
+        Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    }
+}
+/// Gets the change address of a descriptor wallet at a given index.
+pub fn generate_change(config: WalletConfig, index: u32) -> Result<WalletAddress, S5Error> {
+    let wallet = match Wallet::new(
+        &config.deposit_desc,
+        Some(&config.change_desc),
+        config.network,
+        MemoryDatabase::default(),
+    ) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    match wallet.get_internal_address(Peek(index)) {
+        Ok(address) => Ok(WalletAddress {
+            address: address.to_string(),
+            index: index.to_string(),
+            warning: None,
         }),
         Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
     }
 }
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AddressOwnership {
+    pub address: String,
+    pub is_mine: bool,
+    pub keychain: Option<String>,
+    pub index: Option<u32>,
+}
+impl AddressOwnership {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Searches the deposit and change keychains from index start to end (exclusive) for an address.
+/// Use to verify that a change output of a psbt, i.e. from a hardware wallet, belongs to the wallet.
+pub fn find(
+    config: WalletConfig,
+    address: &str,
+    start: u32,
+    end: u32,
+) -> Result<AddressOwnership, S5Error> {
+    if end <= start {
+        return Err(S5Error::new(ErrorKind::Input, "Invalid Search Range"));
+    }
+    if end - start > MAX_SEARCH_RANGE {
+        return Err(S5Error::new(ErrorKind::Input, "Search Range Too Large"));
+    }
+    let target = match Address::from_str(address) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Address")),
+    };
+    if !target.is_valid_for_network(config.network) {
+        return Err(S5Error::new(ErrorKind::Input, "Address Network Mismatch"));
+    }
+    let wallet = match Wallet::new(
+        &config.deposit_desc,
+        Some(&config.change_desc),
+        config.network,
+        MemoryDatabase::default(),
+    ) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+
+    let target_script = target.script_pubkey();
+    for index in start..end {
+        for keychain in [KeychainKind::External, KeychainKind::Internal] {
+            let derived = match keychain {
+                KeychainKind::External => wallet.get_address(Peek(index)),
+                KeychainKind::Internal => wallet.get_internal_address(Peek(index)),
+            };
+            let derived = match derived {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+            };
+            if derived.script_pubkey() == target_script {
+                return Ok(AddressOwnership {
+                    address: target.to_string(),
+                    is_mine: true,
+                    keychain: Some(match keychain {
+                        KeychainKind::External => "deposit".to_string(),
+                        KeychainKind::Internal => "change".to_string(),
+                    }),
+                    index: Some(index),
+                });
+            }
+        }
+    }
+
+    Ok(AddressOwnership {
+        address: target.to_string(),
+        is_mine: false,
+        keychain: None,
+        index: None,
+    })
+}
+
 pub fn sqlite_generate(config: WalletConfig) -> Result<WalletAddress, S5Error> {
     if config.db_path.is_none(){
         return Err(S5Error::new(ErrorKind::Input, "SQLite Requires a Db Path."));
@@ -62,12 +183,210 @@ pub fn sqlite_generate(config: WalletConfig) -> Result<WalletAddress, S5Error> {
     match wallet.get_address(bdk::wallet::AddressIndex::LastUnused) {
         Ok(address) => Ok(WalletAddress {
             address: address.to_string(),
-            index: address.index.to_string()
+            index: address.index.to_string(),
+            warning: None,
+        }),
+        Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AddressEntry {
+    pub address: String,
+    pub index: u32,
+    pub used: bool,
+    pub reused: bool,
+    pub received: u64,
+    pub tx_count: usize,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AddressBook {
+    pub deposit: Vec<AddressEntry>,
+    pub change: Vec<AddressEntry>,
+}
+impl AddressBook {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    received: u64,
+    txids: HashSet<String>,
+}
+
+/// Collects the value received and the txs seen per address from the wallet history.
+fn address_usage<D: BatchDatabase>(
+    wallet: &Wallet<D>,
+) -> Result<HashMap<(KeychainKind, u32), Usage>, S5Error> {
+    let txs = match wallet.list_transactions(true) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    let mut usage: HashMap<(KeychainKind, u32), Usage> = HashMap::new();
+    for tx in txs {
+        let transaction = match tx.transaction {
+            Some(transaction) => transaction,
+            None => continue,
+        };
+        for output in transaction.output {
+            let path = match wallet
+                .database()
+                .get_path_from_script_pubkey(&output.script_pubkey)
+            {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+            };
+            if let Some(path) = path {
+                let entry = usage.entry(path).or_default();
+                entry.received += output.value;
+                entry.txids.insert(tx.txid.to_string());
+            }
+        }
+    }
+    Ok(usage)
+}
+
+fn last_used(usage: &HashMap<(KeychainKind, u32), Usage>, keychain: KeychainKind) -> Option<u32> {
+    usage
+        .keys()
+        .filter(|(kind, _)| *kind == keychain)
+        .map(|(_, index)| *index)
+        .max()
+}
+
+/// Warns when an index leaves more than gap_limit unused addresses after the last used one.
+/// Funds sent to such an address will not be found when restoring from seed.
+pub fn gap_warning(index: u32, last_used: Option<u32>, gap_limit: u32) -> Option<String> {
+    let first_unused = match last_used {
+        Some(last) => last + 1,
+        None => 0,
+    };
+    if index >= first_unused + gap_limit {
+        Some(format!(
+            "Index {} Is Beyond The Gap Limit Of {}. Funds May Not Be Found On Recovery.",
+            index, gap_limit
+        ))
+    } else {
+        None
+    }
+}
+
+fn sqlite_wallet(config: WalletConfig) -> Result<Wallet<SqliteDatabase>, S5Error> {
+    if config.db_path.is_none(){
+        return Err(S5Error::new(ErrorKind::Input, "SQLite Requires a Db Path."));
+    }
+    match Wallet::new(
+        &config.deposit_desc,
+        Some(&config.change_desc),
+        config.network,
+        SqliteDatabase::new(config.db_path.unwrap()),
+    ) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    }
+}
+
+/// Reserves a new deposit address, persistently incrementing the index in the db.
+/// Includes a warning if the new index is beyond the gap limit.
+pub fn sqlite_reserve(config: WalletConfig) -> Result<WalletAddress, S5Error> {
+    let wallet = sqlite_wallet(config)?;
+    let usage = address_usage(&wallet)?;
+    match wallet.get_address(New) {
+        Ok(address) => Ok(WalletAddress {
+            address: address.to_string(),
+            index: address.index.to_string(),
+            warning: gap_warning(
+                address.index,
+                last_used(&usage, KeychainKind::External),
+                DEFAULT_GAP_LIMIT,
+            ),
         }),
         Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
     }
 }
 
+/// Gets the deposit address at a given index without reserving it.
+/// Includes a warning if the index is beyond the gap limit of the wallet history.
+pub fn sqlite_peek(config: WalletConfig, index: u32) -> Result<WalletAddress, S5Error> {
+    let wallet = sqlite_wallet(config)?;
+    let usage = address_usage(&wallet)?;
+    match wallet.get_address(Peek(index)) {
+        Ok(address) => Ok(WalletAddress {
+            address: address.to_string(),
+            index: index.to_string(),
+            warning: gap_warning(
+                index,
+                last_used(&usage, KeychainKind::External),
+                DEFAULT_GAP_LIMIT,
+            ),
+        }),
+        Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    }
+}
+
+/// Lists every deposit and change address up to the highest revealed or used index,
+/// with how much each received and in how many txs. reused is set for deposit
+/// addresses that received in more than one tx.
+pub fn sqlite_list(config: WalletConfig) -> Result<AddressBook, S5Error> {
+    let wallet = sqlite_wallet(config)?;
+    let usage = address_usage(&wallet)?;
+
+    let list = |keychain: KeychainKind| -> Result<Vec<AddressEntry>, S5Error> {
+        let revealed = match wallet.database().get_last_index(keychain) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+        };
+        let upto = match (revealed, last_used(&usage, keychain)) {
+            (Some(revealed), Some(used)) => revealed.max(used),
+            (Some(index), None) | (None, Some(index)) => index,
+            (None, None) => return Ok(vec![]),
+        };
+        (0..=upto)
+            .map(|index| {
+                let address = match keychain {
+                    KeychainKind::External => wallet.get_address(Peek(index)),
+                    KeychainKind::Internal => wallet.get_internal_address(Peek(index)),
+                };
+                let address = match address {
+                    Ok(result) => result.address.to_string(),
+                    Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+                };
+                let (received, tx_count) = match usage.get(&(keychain, index)) {
+                    Some(used) => (used.received, used.txids.len()),
+                    None => (0, 0),
+                };
+                Ok(AddressEntry {
+                    address,
+                    index,
+                    used: tx_count > 0,
+                    reused: keychain == KeychainKind::External && tx_count > 1,
+                    received,
+                    tx_count,
+                })
+            })
+            .collect()
+    };
+
+    Ok(AddressBook {
+        deposit: list(KeychainKind::External)?,
+        change: list(KeychainKind::Internal)?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,4 +434,88 @@ mod tests {
         .expect("File delete failed");
     }
 
+    #[test]
+    fn test_sqlite_address_book() {
+        let xkey = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
+        let descriptor = format!("wpkh({}/*)", xkey);
+        let mut rng = thread_rng();
+        let random: u16 = rng.gen();
+        let db_path: String = env::var("CARGO_MANIFEST_DIR").unwrap() + &random.to_string() + "book.db";
+        let config = WalletConfig::new_offline(&descriptor,Some(db_path.clone())).unwrap();
+        let book = sqlite_list(config).unwrap();
+        assert!(book.deposit.is_empty());
+
+        let config = WalletConfig::new_offline(&descriptor,Some(db_path.clone())).unwrap();
+        let address0 = sqlite_reserve(config).unwrap();
+        assert_eq!(address0.index, "0");
+        assert_eq!(
+            "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3".to_string(),
+            address0.address
+        );
+        let config = WalletConfig::new_offline(&descriptor,Some(db_path.clone())).unwrap();
+        let address1 = sqlite_reserve(config).unwrap();
+        assert_eq!(
+            "tb1qzdwqxt8l2s47vl4fp4ft6w67fcxel4qf5j96ld".to_string(),
+            address1.address
+        );
+        assert!(address1.warning.is_none());
+
+        let config = WalletConfig::new_offline(&descriptor,Some(db_path.clone())).unwrap();
+        let book = sqlite_list(config).unwrap();
+        assert_eq!(book.deposit.len(), 2);
+        assert_eq!(book.deposit[1].address, address1.address);
+        assert!(!book.deposit[1].used);
+        assert_eq!(book.deposit[1].received, 0);
+
+        let config = WalletConfig::new_offline(&descriptor,Some(db_path.clone())).unwrap();
+        let far = sqlite_peek(config, 25).unwrap();
+        assert!(far.warning.is_some());
+        let config = WalletConfig::new_offline(&descriptor,Some(db_path.clone())).unwrap();
+        assert!(sqlite_peek(config, 19).unwrap().warning.is_none());
+
+        fs::remove_file(Path::new(&db_path))
+        .expect("File delete failed");
+    }
+
+    #[test]
+    fn test_gap_warning() {
+        assert!(gap_warning(19, None, DEFAULT_GAP_LIMIT).is_none());
+        assert!(gap_warning(20, None, DEFAULT_GAP_LIMIT).is_some());
+        assert!(gap_warning(25, Some(5), DEFAULT_GAP_LIMIT).is_none());
+        assert!(gap_warning(26, Some(5), DEFAULT_GAP_LIMIT).is_some());
+    }
+
+    #[test]
+    fn test_change_and_find() {
+        let xkey = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
+        let descriptor = format!("wpkh({}/*)", xkey);
+        let config = WalletConfig::new_offline(&descriptor,None).unwrap();
+        let change5 = generate_change(config, 5).unwrap();
+        let config = WalletConfig::new_offline(&descriptor,None).unwrap();
+        assert_ne!(change5.address, generate(config, 5).unwrap().address);
+
+        let config = WalletConfig::new_offline(&descriptor,None).unwrap();
+        let found = find(config, &change5.address, 0, DEFAULT_SEARCH_RANGE).unwrap();
+        assert!(found.is_mine);
+        assert_eq!(found.keychain, Some("change".to_string()));
+        assert_eq!(found.index, Some(5));
+
+        let config = WalletConfig::new_offline(&descriptor,None).unwrap();
+        let found = find(config, "tb1qzdwqxt8l2s47vl4fp4ft6w67fcxel4qf5j96ld", 0, 10).unwrap();
+        assert_eq!(found.keychain, Some("deposit".to_string()));
+        assert_eq!(found.index, Some(1));
+
+        let config = WalletConfig::new_offline(&descriptor,None).unwrap();
+        let found = find(config, &change5.address, 0, 5).unwrap();
+        assert!(!found.is_mine);
+
+        let config = WalletConfig::new_offline(&descriptor,None).unwrap();
+        let found = find(config, "mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt", 0, 10).unwrap();
+        assert!(!found.is_mine);
+        let config = WalletConfig::new_offline(&descriptor,None).unwrap();
+        assert_eq!(
+            find(config, "mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt", 10, 10).err().unwrap().message,
+            "Invalid Search Range"
+        );
+    }
 }
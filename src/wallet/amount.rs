@@ -0,0 +1,332 @@
+use crate::config::WalletConfig;
+use crate::e::{ErrorKind, S5Error};
+use crate::wallet::psbt::TxOutput;
+use bdk::database::SqliteDatabase;
+use bdk::Wallet;
+use bitcoin::blockdata::script::Script;
+use bitcoin::consensus::encode::VarInt;
+use bitcoin::network::constants::Network;
+use bitcoin::util::address::Address;
+use bitcoin::util::amount::{Amount, Denomination};
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+/// Default dust relay fee used by Bitcoin Core (sats/kvB).
+pub const DUST_RELAY_FEE: u64 = 3_000;
+
+/// Parses an amount string into sats.
+/// Accepts a plain integer (sats) or a value followed by a unit: BTC, sats or bits.
+/// eg. "0.001 BTC", "0.001btc", "100000sats", "1000 bits", "100000"
+pub fn parse(amount: &str) -> Result<u64, S5Error> {
+    let amount = amount.trim();
+    if let Ok(sats) = amount.parse::<u64>() {
+        return Ok(sats);
+    }
+    let split_at = match amount.find(|c: char| c.is_alphabetic()) {
+        Some(index) => index,
+        None => return Err(S5Error::new(ErrorKind::Input, "Invalid Amount")),
+    };
+    let (value, unit) = amount.split_at(split_at);
+    let denomination = match unit.trim().to_lowercase().as_str() {
+        "btc" => Denomination::Bitcoin,
+        "sat" | "sats" | "satoshi" | "satoshis" => Denomination::Satoshi,
+        "bit" | "bits" => Denomination::Bit,
+        _ => return Err(S5Error::new(ErrorKind::Input, "Invalid Amount Unit")),
+    };
+    match Amount::from_str_in(value.trim(), denomination) {
+        Ok(result) => Ok(result.as_sat()),
+        Err(e) => Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    }
+}
+
+/// Gets the minimum non-dust value of an output for a given script pubkey.
+/// Follows Bitcoin Core's GetDustThreshold at the default dust relay fee.
+pub fn dust_limit(script_pubkey: &Script) -> u64 {
+    if script_pubkey.is_provably_unspendable() {
+        return 0;
+    }
+    let script_len = script_pubkey.len() as u64;
+    let output_size = 8 + VarInt(script_len).len() as u64 + script_len;
+    let spend_size = if script_pubkey.is_witness_program() {
+        output_size + 32 + 4 + 1 + (107 / 4) + 4
+    } else {
+        output_size + 32 + 4 + 1 + 107 + 4
+    };
+    spend_size * DUST_RELAY_FEE / 1_000
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutputValidation {
+    pub address: String,
+    pub amount: Option<u64>,
+    pub dust_limit: u64,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TxValidation {
+    pub outputs: Vec<OutputValidation>,
+    pub total: u64,
+    pub fee: u64,
+    pub balance: u64,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+impl TxValidation {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+    /// Returns the first validation error found as an S5Error.
+    pub fn to_error(&self) -> Option<S5Error> {
+        if self.valid {
+            return None;
+        }
+        let message = match self.outputs.iter().find(|output| !output.valid) {
+            Some(output) => output.error.clone(),
+            None => self.error.clone(),
+        };
+        Some(S5Error::new(
+            ErrorKind::Input,
+            &message.unwrap_or_else(|| "Invalid Output Set".to_string()),
+        ))
+    }
+}
+
+/// check_amount is false for outputs whose value is decided by a sweep.
+fn validate_output(network: Network, output: &TxOutput, sweep: bool, check_amount: bool) -> OutputValidation {
+    let invalid = |dust_limit: u64, error: &str| OutputValidation {
+        address: output.address.clone(),
+        amount: output.amount,
+        dust_limit,
+        valid: false,
+        error: Some(error.to_string()),
+    };
+    let address = match Address::from_str(&output.address) {
+        Ok(result) => result,
+        Err(_) => return invalid(0, "Invalid Address"),
+    };
+    if !address.is_valid_for_network(network) {
+        return invalid(0, "Address Network Mismatch");
+    }
+    let dust_limit = dust_limit(&address.script_pubkey());
+    if let Some(percent) = output.percent {
+        if !sweep {
+            return invalid(dust_limit, "Percent Outputs Require Sweep");
+        }
+        if !(percent > 0.0 && percent <= 100.0) {
+            return invalid(dust_limit, "Invalid Percent");
+        }
+    } else if check_amount {
+        match output.amount {
+            Some(amount) => {
+                if amount < dust_limit {
+                    return invalid(dust_limit, "Amount Below Dust Limit");
+                }
+            }
+            None => return invalid(dust_limit, "Invalid Amount"),
+        }
+    }
+    OutputValidation {
+        address: output.address.clone(),
+        amount: output.amount,
+        dust_limit,
+        valid: true,
+        error: None,
+    }
+}
+
+/// Validates a set of outputs against the network, dust limits and the current wallet balance.
+/// When sweeping, only fixed amounts sent alongside a drain or percentage outputs are checked.
+/// A sweep to a single output ignores its amount.
+pub fn validate(
+    network: Network,
+    outputs: &[TxOutput],
+    fee_absolute: u64,
+    balance: u64,
+    sweep: bool,
+) -> TxValidation {
+    let single_sweep = sweep && outputs.len() == 1;
+    let validated: Vec<OutputValidation> = outputs
+        .iter()
+        .map(|output| {
+            let check_amount = !sweep || (!single_sweep && !output.is_drain());
+            validate_output(network, output, sweep, check_amount)
+        })
+        .collect();
+    let total: u64 = if single_sweep {
+        0
+    } else {
+        outputs
+            .iter()
+            .filter(|output| output.percent.is_none())
+            .map(|output| output.amount.unwrap_or(0))
+            .fold(0, |acc, amount| acc.saturating_add(amount))
+    };
+    let required = total.saturating_add(fee_absolute);
+    let error = if outputs.is_empty() {
+        Some("No Outputs".to_string())
+    } else if validated.iter().any(|output| !output.valid) {
+        Some("Invalid Output Set".to_string())
+    } else if (sweep && balance <= required) || required > balance {
+        Some("Insufficient Funds".to_string())
+    } else {
+        None
+    };
+
+    TxValidation {
+        outputs: validated,
+        total,
+        fee: fee_absolute,
+        balance,
+        valid: error.is_none(),
+        error,
+    }
+}
+
+/// Validates outputs against the balance of a descriptor wallet from an SQLite DB.
+pub fn sqlite_validate(
+    config: WalletConfig,
+    outputs: Vec<TxOutput>,
+    fee_absolute: u64,
+    sweep: bool,
+) -> Result<TxValidation, S5Error> {
+    if config.db_path.is_none() {
+        return Err(S5Error::new(ErrorKind::Input, "SQLite Requires a Db Path."));
+    }
+    let wallet = match Wallet::new(
+        &config.deposit_desc,
+        Some(&config.change_desc),
+        config.network,
+        SqliteDatabase::new(config.db_path.unwrap()),
+    ) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    let balance = match wallet.get_balance() {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    Ok(validate(config.network, &outputs, fee_absolute, balance, sweep))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse("100000").unwrap(), 100_000);
+        assert_eq!(parse("0.001 BTC").unwrap(), 100_000);
+        assert_eq!(parse("0.001btc").unwrap(), 100_000);
+        assert_eq!(parse("100000 sats").unwrap(), 100_000);
+        assert_eq!(parse("1 sat").unwrap(), 1);
+        assert_eq!(parse("1000 bits").unwrap(), 100_000);
+        assert_eq!(parse("0.5 bits").unwrap(), 50);
+        assert_eq!(parse("0.000000001 BTC").err().unwrap().kind, "Input");
+        assert_eq!(parse("1.5 sats").err().unwrap().kind, "Input");
+        assert_eq!(parse("12 eth").err().unwrap().message, "Invalid Amount Unit");
+        assert_eq!(parse("lots").err().unwrap().message, "Invalid Amount Unit");
+        assert_eq!(parse("").err().unwrap().message, "Invalid Amount");
+    }
+
+    #[test]
+    fn test_dust_limit() {
+        let p2pkh = Address::from_str("mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt").unwrap();
+        let p2sh = Address::from_str("2MvWazupEQxP8RTeY3eUD2a37Htj3w8rc1d").unwrap();
+        let p2wpkh = Address::from_str("tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3").unwrap();
+        let p2wsh = Address::from_str(
+            "tb1q5f3jl5lzlxtmhptfe9crhmv4wh392ku5ztkpt6xxmqqx2c3jyxrs8vgat7",
+        )
+        .unwrap();
+        let p2tr = Address::from_str(
+            "tb1pyky6jtr8amxr726he4qejpcdrq9yh86kq3vqjvmfguw8ty6hwf8s5y0zdj",
+        )
+        .unwrap();
+        assert_eq!(dust_limit(&p2pkh.script_pubkey()), 546);
+        assert_eq!(dust_limit(&p2sh.script_pubkey()), 540);
+        assert_eq!(dust_limit(&p2wpkh.script_pubkey()), 294);
+        assert_eq!(dust_limit(&p2wsh.script_pubkey()), 330);
+        assert_eq!(dust_limit(&p2tr.script_pubkey()), 330);
+    }
+
+    #[test]
+    fn test_validate() {
+        let outputs = vec![
+            TxOutput {
+                address: "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3".to_string(),
+                amount: Some(5_000),
+                percent: None,
+            },
+            TxOutput {
+                address: "mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt".to_string(),
+                amount: Some(1_000),
+                percent: None,
+            },
+        ];
+        let validation = validate(Network::Testnet, &outputs, 300, 10_000, false);
+        assert!(validation.valid);
+        assert_eq!(validation.total, 6_000);
+        assert!(validation.to_error().is_none());
+
+        let validation = validate(Network::Testnet, &outputs, 300, 6_000, false);
+        assert!(!validation.valid);
+        assert_eq!(validation.to_error().unwrap().message, "Insufficient Funds");
+
+        let validation = validate(Network::Bitcoin, &outputs, 300, 10_000, false);
+        assert!(!validation.valid);
+        assert_eq!(
+            validation.to_error().unwrap().message,
+            "Address Network Mismatch"
+        );
+
+        let dust = vec![TxOutput {
+            address: "mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt".to_string(),
+            amount: Some(545),
+            percent: None,
+        }];
+        let validation = validate(Network::Testnet, &dust, 300, 10_000, false);
+        assert_eq!(validation.outputs[0].dust_limit, 546);
+        assert_eq!(
+            validation.to_error().unwrap().message,
+            "Amount Below Dust Limit"
+        );
+        let validation = validate(Network::Testnet, &dust, 300, 10_000, true);
+        assert!(validation.valid);
+
+        let split = TxOutput::vec_from_str(
+            "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:200,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:0",
+        )
+        .unwrap();
+        let validation = validate(Network::Testnet, &split, 300, 10_000, true);
+        assert_eq!(
+            validation.to_error().unwrap().message,
+            "Amount Below Dust Limit"
+        );
+        let split = TxOutput::vec_from_str(
+            "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:60%,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:40%",
+        )
+        .unwrap();
+        assert!(validate(Network::Testnet, &split, 300, 10_000, true).valid);
+        assert_eq!(
+            validate(Network::Testnet, &split, 300, 10_000, false)
+                .to_error()
+                .unwrap()
+                .message,
+            "Percent Outputs Require Sweep"
+        );
+    }
+}
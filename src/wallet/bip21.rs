@@ -0,0 +1,317 @@
+use crate::config::WalletConfig;
+use crate::e::{ErrorKind, S5Error};
+use crate::wallet::address;
+use bitcoin::network::constants::Network;
+use bitcoin::util::address::Address;
+use bitcoin::util::amount::{Amount, Denomination};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+pub const BIP21_SCHEME: &str = "bitcoin:";
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PaymentRequest {
+    pub address: String,
+    pub amount: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    pub params: BTreeMap<String, String>,
+}
+impl PaymentRequest {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentUri {
+    pub uri: String,
+    pub address: String,
+    pub index: String,
+}
+impl PaymentUri {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Parses a BIP21 uri and checks that the address belongs to the given network.
+/// Amounts are converted from BTC into sats.
+/// Unknown params, including lightning= and pj=, are returned as is in params.
+pub fn parse(network: Network, uri: &str) -> Result<PaymentRequest, S5Error> {
+    let uri = uri.trim();
+    if !uri
+        .get(..BIP21_SCHEME.len())
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case(BIP21_SCHEME))
+    {
+        return Err(S5Error::new(ErrorKind::Input, "Invalid BIP21 Scheme"));
+    }
+    let body = &uri[BIP21_SCHEME.len()..];
+    let (address_str, query) = match body.split_once('?') {
+        Some((address, query)) => (address, query),
+        None => (body, ""),
+    };
+
+    let address = match Address::from_str(address_str) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Address")),
+    };
+    if !address.is_valid_for_network(network) {
+        return Err(S5Error::new(ErrorKind::Input, "Address Network Mismatch"));
+    }
+
+    let mut amount = None;
+    let mut label = None;
+    let mut message = None;
+    let mut params = BTreeMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = match pair.split_once('=') {
+            Some((key, value)) => (key.to_lowercase(), value),
+            None => (pair.to_lowercase(), ""),
+        };
+        let value = percent_decode(value)?;
+        match key.as_str() {
+            "amount" => {
+                if amount.is_some() {
+                    return Err(S5Error::new(ErrorKind::Input, "Duplicate Amount"));
+                }
+                amount = match Amount::from_str_in(&value, Denomination::Bitcoin) {
+                    Ok(result) => Some(result.as_sat()),
+                    Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Amount")),
+                };
+            }
+            "label" => label = Some(value),
+            "message" => message = Some(value),
+            _ => {
+                if key.starts_with("req-") {
+                    return Err(S5Error::new(
+                        ErrorKind::Input,
+                        &format!("Unsupported Required Param: {}", key),
+                    ));
+                }
+                params.insert(key, value);
+            }
+        }
+    }
+
+    Ok(PaymentRequest {
+        address: address.to_string(),
+        amount,
+        label,
+        message,
+        params,
+    })
+}
+
+/// Builds a BIP21 uri for the deposit address at the given index.
+pub fn generate(
+    config: WalletConfig,
+    index: u32,
+    amount: Option<u64>,
+    label: Option<String>,
+    message: Option<String>,
+) -> Result<PaymentUri, S5Error> {
+    let wallet_address = address::generate(config, index)?;
+    let mut params: Vec<String> = vec![];
+    if let Some(amount) = amount {
+        params.push(format!("amount={}", format_btc(amount)));
+    }
+    if let Some(label) = label {
+        params.push(format!("label={}", percent_encode(&label)));
+    }
+    if let Some(message) = message {
+        params.push(format!("message={}", percent_encode(&message)));
+    }
+    let uri = if params.is_empty() {
+        format!("{}{}", BIP21_SCHEME, wallet_address.address)
+    } else {
+        format!(
+            "{}{}?{}",
+            BIP21_SCHEME,
+            wallet_address.address,
+            params.join("&")
+        )
+    };
+
+    Ok(PaymentUri {
+        uri,
+        address: wallet_address.address,
+        index: wallet_address.index,
+    })
+}
+
+/// Formats sats as a BTC decimal string without trailing zeros.
+fn format_btc(sats: u64) -> String {
+    let btc = Amount::from_sat(sats).to_string_in(Denomination::Bitcoin);
+    if btc.contains('.') {
+        btc.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        btc
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> Result<String, S5Error> {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex_str = match value.get(i + 1..i + 3) {
+                Some(result) if result.bytes().all(|c| c.is_ascii_hexdigit()) => result,
+                _ => return Err(S5Error::new(ErrorKind::Input, "Invalid URI Encoding")),
+            };
+            match u8::from_str_radix(hex_str, 16) {
+                Ok(byte) => decoded.push(byte),
+                Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid URI Encoding")),
+            };
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    match String::from_utf8(decoded) {
+        Ok(result) => Ok(result),
+        Err(_) => Err(S5Error::new(ErrorKind::Input, "Invalid URI Encoding")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let uri = "bitcoin:tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3?amount=0.0005&label=Luke%20Jr&message=Donation%20for%20project%20xyz&lightning=lntb1u1pwz5w78pp5e8w8&pj=https://example.com/pj";
+        let request = parse(Network::Testnet, uri).unwrap();
+        assert_eq!(request.address, "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3");
+        assert_eq!(request.amount, Some(50_000));
+        assert_eq!(request.label, Some("Luke Jr".to_string()));
+        assert_eq!(
+            request.message,
+            Some("Donation for project xyz".to_string())
+        );
+        assert_eq!(request.params.get("lightning").unwrap(), "lntb1u1pwz5w78pp5e8w8");
+        assert_eq!(request.params.get("pj").unwrap(), "https://example.com/pj");
+
+        let uri = "BITCOIN:TB1Q093GL5YXWW0HLVLKAJDMF8WH3A6RLVSDK9E6D3";
+        let request = parse(Network::Testnet, uri).unwrap();
+        assert_eq!(request.address, "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3");
+        assert_eq!(request.amount, None);
+        assert!(request.params.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let mainnet = parse(
+            Network::Bitcoin,
+            "bitcoin:tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(mainnet.message, "Address Network Mismatch");
+        let scheme = parse(
+            Network::Testnet,
+            "litecoin:tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(scheme.message, "Invalid BIP21 Scheme");
+        let multibyte = parse(
+            Network::Testnet,
+            "bitcoin€tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(multibyte.message, "Invalid BIP21 Scheme");
+        let short = parse(Network::Testnet, "bitcoi€").err().unwrap();
+        assert_eq!(short.message, "Invalid BIP21 Scheme");
+        let amount = parse(
+            Network::Testnet,
+            "bitcoin:tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3?amount=0.000000001",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(amount.message, "Invalid Amount");
+        let required = parse(
+            Network::Testnet,
+            "bitcoin:tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3?req-somethingyoudontunderstand=50",
+        )
+        .err()
+        .unwrap();
+        assert!(required.message.starts_with("Unsupported Required Param"));
+        let encoding = parse(
+            Network::Testnet,
+            "bitcoin:tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3?label=Luke%+1",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(encoding.message, "Invalid URI Encoding");
+    }
+
+    #[test]
+    fn test_generate() {
+        let xkey = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
+        let descriptor = format!("wpkh({}/*)", xkey);
+        let config = WalletConfig::new_offline(&descriptor, None).unwrap();
+        let payment_uri = generate(
+            config,
+            0,
+            Some(150_000),
+            Some("Coffee & Cake".to_string()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            payment_uri.uri,
+            "bitcoin:tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3?amount=0.0015&label=Coffee%20%26%20Cake"
+        );
+        let request = parse(Network::Testnet, &payment_uri.uri).unwrap();
+        assert_eq!(request.amount, Some(150_000));
+        assert_eq!(request.label, Some("Coffee & Cake".to_string()));
+
+        let config = WalletConfig::new_offline(&descriptor, None).unwrap();
+        let payment_uri = generate(config, 1, None, None, None).unwrap();
+        assert_eq!(
+            payment_uri.uri,
+            "bitcoin:tb1qzdwqxt8l2s47vl4fp4ft6w67fcxel4qf5j96ld"
+        );
+        assert_eq!(format_btc(100_000_000), "1");
+    }
+}
@@ -1,11 +1,16 @@
-use std::fs::File;
+use std::ffi::CString;
+use std::os::raw::c_char;
 
+use bitcoin::network::constants::Network;
 use serde_derive::{Serialize,Deserialize};
 
+use crate::config::WalletConfig;
 use crate::e::{S5Error,ErrorKind};
+use crate::wallet::address;
+use crate::wallet::descriptor;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize,Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct ColdCardKeys {
     pub chain: String,
     pub xpub: String,
@@ -14,10 +19,11 @@ pub struct ColdCardKeys {
     pub bip49: Bip49,
     pub bip44: Bip44,
     pub bip84: Bip84,
+    pub bip86: Option<Bip86>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Bip49 {
     pub xpub: String,
     pub first: String,
@@ -29,7 +35,7 @@ pub struct Bip49 {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Bip44 {
     pub xpub: String,
     pub first: String,
@@ -39,7 +45,7 @@ pub struct Bip44 {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Bip84 {
     pub xpub: String,
     pub first: String,
@@ -50,50 +56,195 @@ pub struct Bip84 {
     pub zpub: String,
 }
 
-impl ColdCardKeys{
-  pub fn _from_json_file(path: &str)->Result<ColdCardKeys,S5Error>{
-    let file = match File::open(path){
-      Ok(file)=>file,
-      Err(_)=> return Err(S5Error::new(ErrorKind::Internal,"File-Open-Cold-Card-Json."))
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Bip86 {
+    pub xpub: String,
+    pub first: String,
+    pub deriv: String,
+    pub xfp: String,
+    pub name: String,
+}
+
+/// FFI Output
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColdCardDescriptor {
+    pub bip: String,
+    pub script_type: String,
+    pub descriptor: String,
+    pub first: String,
+}
+
+/// FFI Output
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColdCardWallet {
+    pub fingerprint: String,
+    pub network: String,
+    pub descriptors: Vec<ColdCardDescriptor>,
+}
+impl ColdCardWallet {
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
     };
-    match serde_json::from_reader(file){
+
+    CString::new(stringified).unwrap().into_raw()
+  }
+}
+
+impl ColdCardKeys{
+  /// Reads the content of a Coldcard generic JSON export (Advanced > Export Wallet > Generic JSON).
+  pub fn from_json_str(content: &str)->Result<ColdCardKeys,S5Error>{
+    match serde_json::from_str(content){
       Ok(result)=>Ok(result),
-      Err(_)=>Err(S5Error::new(ErrorKind::Internal,"JSON-File-To-Struct"))
+      Err(_)=>Err(S5Error::new(ErrorKind::Input,"Invalid Coldcard JSON"))
+    }
+  }
+
+  fn network(&self)->Result<Network,S5Error>{
+    match self.chain.as_str(){
+      "BTC"=>Ok(Network::Bitcoin),
+      "XTN"=>Ok(Network::Testnet),
+      "XRT"=>Ok(Network::Regtest),
+      _=>Err(S5Error::new(ErrorKind::Input,"Unknown Coldcard Chain"))
+    }
+  }
+
+  /// Gets the exported single sig accounts as (bip, script type, derivation, xpub, first address).
+  /// Sections without an xpub are skipped; bip86 (taproot) is only exported by recent firmware.
+  pub fn sections(&self)->Vec<(&str,&str,&str,&str,&str)>{
+    let mut sections = vec![
+      ("bip44","pkh",self.bip44.deriv.as_str(),self.bip44.xpub.as_str(),self.bip44.first.as_str()),
+      ("bip49","sh-wpkh",self.bip49.deriv.as_str(),self.bip49.xpub.as_str(),self.bip49.first.as_str()),
+      ("bip84","wpkh",self.bip84.deriv.as_str(),self.bip84.xpub.as_str(),self.bip84.first.as_str()),
+    ];
+    if let Some(bip86) = &self.bip86 {
+      sections.push(("bip86","tr",bip86.deriv.as_str(),bip86.xpub.as_str(),bip86.first.as_str()));
     }
+    sections.retain(|(_,_,_,xpub,_)| !xpub.is_empty());
+    sections
+  }
+
+  /// Builds the single sig descriptors of the export and checks that each derives its first address.
+  pub fn descriptors(&self)->Result<ColdCardWallet,S5Error>{
+    let network = self.network()?;
+    let fingerprint = self.xfp.to_lowercase();
+    let mut descriptors: Vec<ColdCardDescriptor> = vec![];
+    for (bip,script_type,deriv,xpub,first) in self.sections(){
+      let key = format!("[{}{}]{}/*",fingerprint,deriv.trim_start_matches('m'),xpub);
+      if (descriptor::key_network(&key) == Network::Bitcoin) != (network == Network::Bitcoin) {
+        return Err(S5Error::new(ErrorKind::Input,&format!("Coldcard Chain Does Not Match Keys: {}",bip)));
+      }
+      let body = match script_type{
+        "pkh"=>format!("pkh({})",key),
+        "sh-wpkh"=>format!("sh(wpkh({}))",key),
+        "wpkh"=>format!("wpkh({})",key),
+        _=>format!("tr({})",key),
+      };
+      let mut config = WalletConfig::new_offline(&body,None)?;
+      config.network = network;
+      if first.is_empty() {
+        return Err(S5Error::new(ErrorKind::Input,&format!("Coldcard Export Missing First Address: {}",bip)));
+      }
+      if address::generate(config,0)?.address != first {
+        return Err(S5Error::new(ErrorKind::Key,&format!("Coldcard First Address Mismatch: {}",bip)));
+      }
+      descriptors.push(ColdCardDescriptor{
+        bip: bip.to_string(),
+        script_type: script_type.to_string(),
+        descriptor: descriptor::with_checksum(&body)?.descriptor,
+        first: first.to_string(),
+      });
+    }
+    if descriptors.is_empty(){
+      return Err(S5Error::new(ErrorKind::Input,"Coldcard Export Has No Keys"));
+    }
+
+    Ok(ColdCardWallet{
+      fingerprint,
+      network: match network {
+        Network::Bitcoin => "main".to_string(),
+        _ => "test".to_string(),
+      },
+      descriptors,
+    })
   }
 }
 
+/// Reads a Coldcard generic JSON export into verified watch-only descriptors.
+pub fn import(content: &str)->Result<ColdCardWallet,S5Error>{
+  ColdCardKeys::from_json_str(content)?.descriptors()
+}
+
 #[cfg(test)]
 
 mod tests {
   use super::*;
-  use std::path::PathBuf;
-  use crate::wallet::address;
-  use crate::config::{WalletConfig, DEFAULT_MAINNET_NODE}; 
 
-  #[test] #[ignore]
+  const CC_JSON: &str = r#"{
+    "chain": "XTN",
+    "xfp": "3442193E",
+    "account": 0,
+    "xpub": "tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp",
+    "bip44": {
+      "name": "p2pkh",
+      "xfp": "03265488",
+      "deriv": "m/44'/1'/0'",
+      "xpub": "tpubDDW4jVEAkwNoHumzePCtQ5FcxXVc8RG8ACszXP1HD1WThkZ19sAoyaNeiXswjTtAKM14zjo8rdhxadti7zuNSfJBMuG68oxQ3Bi1wgo88fD",
+      "first": "mr2WYNhNLNzTUmaSo9w5LKQDpth5umfk9Y"
+    },
+    "bip49": {
+      "name": "p2sh-p2wpkh",
+      "xfp": "562549C0",
+      "deriv": "m/49'/1'/0'",
+      "xpub": "tpubDCoQ5oeS57aZM35CpZuV5WagduPC7zzwiCm52q2oa1jHvuuwSUWxX1smc4G1zg1jB57HzqyehjSGdAZY9cAuProNxMfxqHFh6JWq5tvoxh9",
+      "first": "2NGXWDDCsPFXJ3M6TJHcvYBuGix3AgWF7Nh"
+    },
+    "bip84": {
+      "name": "p2wpkh",
+      "xfp": "C948860A",
+      "deriv": "m/84'/1'/0'",
+      "xpub": "tpubDDNRbZGvdA33cgpY5uy2mmphT7sK4uciRjcQScSd64S5KRyZDxHcPuzs24or84Hywugb2JbEEt2jWH8fduiN9cmZzkSj8sSSx6txXkhXyZs",
+      "first": "tb1q7f0pjwhc3jzzv0w4uurm589506glv2dg2qy7ze"
+    },
+    "bip86": {
+      "name": "p2tr",
+      "xfp": "764BCC61",
+      "deriv": "m/86'/1'/0'",
+      "xpub": "tpubDDXRVY4eRY4p4iqLUQNQokx89YLZEpTyA8UdLVYRMV8HacVdwHRi1TWKMSY3kh8WpNBA4kB4Xet3hCxxVvSw83DZ5fhqiSysSsYCWJ7k79E",
+      "first": "tb1pzh75rtx74l85v2xqfr5uln7mhy40vyqzm68ml4yngcqy9v085tqqvq73sa"
+    }
+  }"#;
+
+  #[test]
   fn test_coldcard_watcher() {
-    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    path.push("resources/cc.json");
-    let cckeys = ColdCardKeys::_from_json_file(&path.to_str().unwrap()).unwrap();
-  
-    let key_source_84 = cckeys.bip84.deriv.replace("m",&cckeys.xfp.to_lowercase());
-    let bip84_descriptor = format!("wpkh([{}]{}/*)",key_source_84,cckeys.bip84.xpub);
-    let config = WalletConfig::new(&bip84_descriptor,DEFAULT_MAINNET_NODE,None,None).unwrap();
-    let bip84_first_address = address::generate(config,0).unwrap();
-    assert_eq!(bip84_first_address.address,cckeys.bip84.first);
-
-    let key_source_49 = cckeys.bip49.deriv.replace("m",&cckeys.xfp.to_lowercase());
-    let bip49_descriptor = format!("sh(wpkh([{}]{}/*))",key_source_49,cckeys.bip49.xpub);
-    let config = WalletConfig::new(&bip49_descriptor, DEFAULT_MAINNET_NODE,None,None).unwrap();
-    let bip49_first_address = address::generate(config,0).unwrap();
-    assert_eq!(bip49_first_address.address,cckeys.bip49.first);
-
-    let key_source_44 = cckeys.bip49.deriv.replace("m",&cckeys.xfp.to_lowercase());
-    let bip44_descriptor = format!("pkh([{}]{}/*)",key_source_44,cckeys.bip44.xpub);
-    let config = WalletConfig::new(&bip44_descriptor,DEFAULT_MAINNET_NODE,None,None).unwrap();
-    let bip44_first_address = address::generate(config,0).unwrap();
-    assert_eq!(bip44_first_address.address,cckeys.bip44.first);
-    
+    let cckeys = ColdCardKeys::from_json_str(CC_JSON).unwrap();
+    assert_eq!(cckeys.bip84.xfp, "C948860A");
+
+    let wallet = import(CC_JSON).unwrap();
+    assert_eq!(wallet.fingerprint, "3442193e");
+    assert_eq!(wallet.network, "test");
+    assert_eq!(wallet.descriptors.len(), 4);
+    assert!(wallet.descriptors[0].descriptor.starts_with("pkh([3442193e/44'/1'/0']tpubDDW4jVEAkwNo"));
+    assert!(wallet.descriptors[1].descriptor.starts_with("sh(wpkh([3442193e/49'/1'/0']"));
+    assert!(wallet.descriptors[2].descriptor.starts_with("wpkh([3442193e/84'/1'/0']"));
+    assert_eq!(wallet.descriptors[3].script_type, "tr");
+    assert_eq!(wallet.descriptors[3].first, "tb1pzh75rtx74l85v2xqfr5uln7mhy40vyqzm68ml4yngcqy9v085tqqvq73sa");
+
+    let mut without_taproot = cckeys.clone();
+    without_taproot.bip86 = None;
+    assert_eq!(without_taproot.descriptors().unwrap().descriptors.len(), 3);
+
+    let wrong_first = CC_JSON.replace("mr2WYNhNLNzTUmaSo9w5LKQDpth5umfk9Y", "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn");
+    assert_eq!(import(&wrong_first).err().unwrap().message, "Coldcard First Address Mismatch: bip44");
+    let wrong_chain = CC_JSON.replace("\"XTN\"", "\"BTC\"");
+    assert_eq!(import(&wrong_chain).err().unwrap().message, "Coldcard Chain Does Not Match Keys: bip44");
+    assert_eq!(import("{}").err().unwrap().message, "Unknown Coldcard Chain");
+    assert_eq!(import("cc.json").err().unwrap().message, "Invalid Coldcard JSON");
   }
 }
@@ -1,277 +1,615 @@
-// use std::fmt::Debug;
-// use std::fmt::Display;
-// use std::fmt::Formatter;
-
-// #[derive(Debug)]
-// pub enum CustomWallet {
-//   Solo,
-//   Escrow,
-//   Team,
-//   Raft,
-//   Custom,
-// }
-// impl Display for CustomWallet {
-//   fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-//     match self {
-//       CustomWallet::Solo => write!(f, "Solo"),
-//       CustomWallet::Escrow => write!(f, "Escrow"),
-//       CustomWallet::Team => write!(f, "Team"),
-//       CustomWallet::Raft => write!(f, "Raft"),
-//       CustomWallet::Custom => write!(f, "Custom"),
-//     }
-//   }
-// }
-
-// #[cfg(test)]
-// mod tests {
-//   use std::collections::BTreeMap;
-//   use crate::config::{WalletConfig,DEFAULT_TESTNET_NODE};
-//   use crate::key::{
-//     derivation::{ChildKeys, DerivationPurpose},
-//     seed::MasterKey,
-//   };
-//   use crate::wallet::{policy, address, history, psbt};
-
-//   #[test]
-//   fn test_escrow_wallet_offline() {
-//     let a_master = MasterKey{
-//       fingerprint: "c2cb6b81".to_string(),
-//       mnemonic: "since tiger lend garment usage eyebrow drive staff grant force front swing medal scout side index kitchen marriage hockey habit smooth bread network giant".to_string(),
-//       xprv: "tprv8ZgxMBicQKsPejWK14KjNayPfWwudPKwfDx2YYB2XqSbPKWobGoicV4uYMt3aLHBXFcN3Zkf2rK2Nmy1XCTtH8VDtxAoy4GtRJphEnAzx6n".to_string()
-//     };
-//     let a_child =
-//       ChildKeys::from_hardened_account(&a_master.xprv, DerivationPurpose::Native, 0).unwrap();
-
-//     let b_master = MasterKey{
-//       fingerprint: "9eee95d3".to_string(),
-//       mnemonic: "flower increase image street chair wagon comic volume beyond vote tenant machine math modify rival example salute distance strategy trim joy gossip close remain".to_string(),
-//       xprv: "tprv8ZgxMBicQKsPd882YUvsSKW1f26NvXjtE4CyMbEotRmjsDJHAh9s2HHg7QjQW6SNo1xuoBRsFH68LcyPt5hJCCuUxQobpmpSu32AVrTf8JD".to_string()
-//     };
-//     let b_child =
-//       ChildKeys::from_hardened_account(&b_master.xprv, DerivationPurpose::Native, 0).unwrap();
-
-//     let e_master = MasterKey{
-//       fingerprint: "958b4ad7".to_string(),
-//       mnemonic: "digital middle letter fury rebuild conduct bronze radio mansion dinner assume world mind utility divert obscure fit entry cat soda mix breeze orchard document".to_string(),
-//       xprv: "tprv8ZgxMBicQKsPfBANiurYC1Jm3G9WmP8ZFq5QhyPASTBWuNo1sdS518negGTeD7ARdsQmob9WbKk6hNqHN4torUcCq7SpDpVfaVG7PefbmGe".to_string()
-//     };
-//     let e_child =
-//       ChildKeys::from_hardened_account(&e_master.xprv, DerivationPurpose::Native, 0).unwrap();
-
-//     let escrow_public_policy = format!(
-//       "thresh(2,pk({}),pk({}),pk({}))",
-//       a_child.to_extended_xpub_str(),
-//       b_child.to_extended_xpub_str(),
-//       e_child.to_extended_xpub_str()
-//     );
-
-//     let public_descriptor =
-//       policy::compile(&escrow_public_policy, policy::ScriptType::WSH).unwrap();
-
-//     let expected_descriptor = "wsh(multi(2,[c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg/*,[9eee95d3/84h/1h/0h]tpubDDmbx25X8ThKHXgMnAj4Tbka85SiisU2KapKp42foekYKYvJ7AiB7MWV5G9wZKpax4fbqaHhuL1MShri2ACA9UDeSmDXyajHQ5ohf8RiUW6/*,[958b4ad7/84h/1h/0h]tpubDD249riYEKPfTZrg9vTdwzmcF3ccLdgHQCp2vx4tuHFV4z6aq42xDZVG1EA3qQwNJkPRaZe6tb3jds65qMRYFgevbd6PXdUPutLbB5JQjft/*))";
-//     assert_eq!(public_descriptor, expected_descriptor);
-
-//     println!("{}", public_descriptor);
-//     let public_config = WalletConfig::new_offline(&public_descriptor).unwrap();
-//     let address0 = address::generate(public_config, 0).unwrap().address;
-//     let expected_address = "tb1q64kehk7zq7xnkhv9m4n800g0tuyn4xrdg68376kgzqsyml2246tq7nu8uq";
-//     // println!("{:#?}", address0);
-//     assert_eq!(address0, expected_address);
-//   }
-
-//   /// This test requires funding 5000 sats to the given address
-//   /// tb1q64kehk7zq7xnkhv9m4n800g0tuyn4xrdg68376kgzqsyml2246tq7nu8uq
-//   #[test]
-//   // #[ignore]
-//   fn test_escrow_wallet_online() {
-//     let return_address = "mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt";
-//     let output = psbt::TxOutput{
-//       address: return_address.to_string(),
-//       amount: Some(700)
-//     };
-//     let expected_address = "tb1q64kehk7zq7xnkhv9m4n800g0tuyn4xrdg68376kgzqsyml2246tq7nu8uq".to_string();
-//     let _starting_balance = 5_000;
-
-//     let public_descriptor = "wsh(multi(2,[c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg/*,[9eee95d3/84h/1h/0h]tpubDDmbx25X8ThKHXgMnAj4Tbka85SiisU2KapKp42foekYKYvJ7AiB7MWV5G9wZKpax4fbqaHhuL1MShri2ACA9UDeSmDXyajHQ5ohf8RiUW6/*,[958b4ad7/84h/1h/0h]tpubDD249riYEKPfTZrg9vTdwzmcF3ccLdgHQCp2vx4tuHFV4z6aq42xDZVG1EA3qQwNJkPRaZe6tb3jds65qMRYFgevbd6PXdUPutLbB5JQjft/*))";
-
-//     let a_xprv = "[c2cb6b81/84h/1h/0h]tprv8gooCbWUp5z9GWY95JWHgMSs28YCpKTdwL2G3GbYSch2ME8SPzVxAjiaHgCDdyHBLGkUB7Nh5U66G5uLwykSAvECA78Bx6T8mS3wVgQMAGf/*";
-//     let a_xpub = "[c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg/*";
-
-//     let b_xprv = "[9eee95d3/84h/1h/0h]tprv8h5Zoc3Gz61eQ4eZtX4U4C6TZ3vnZYH7kHDYXXzNPNx9V4fXUmtavrtcu7nXV253wY741whqjYTUaNR7on91nuB4ydVyfDVbrodzuxRRRQg/*";
-//     let b_xpub = "[9eee95d3/84h/1h/0h]tpubDDmbx25X8ThKHXgMnAj4Tbka85SiisU2KapKp42foekYKYvJ7AiB7MWV5G9wZKpax4fbqaHhuL1MShri2ACA9UDeSmDXyajHQ5ohf8RiUW6/*";
-
-//     let e_xprv = "[958b4ad7/84h/1h/0h]tprv8gL21SgJ5whza6ptGGo3Yb7Vg26gBJVNpuDFeS2bV1T6EVqpCfDN34sPq6rvHgdbfXMP4mUf7khyCRhLpCGCrHqNxDL3g8456KckGU3Q9gW/*";
-//     let e_xpub = "[958b4ad7/84h/1h/0h]tpubDD249riYEKPfTZrg9vTdwzmcF3ccLdgHQCp2vx4tuHFV4z6aq42xDZVG1EA3qQwNJkPRaZe6tb3jds65qMRYFgevbd6PXdUPutLbB5JQjft/*";
-
-//     let a_prv_policy = format!(
-//       "thresh(2,pk({}),pk({}),pk({}))",
-//       a_xprv,
-//       b_xpub,
-//       e_xpub
-//     );
-//     let a_prv_desc = policy::compile(&a_prv_policy, policy::ScriptType::WSH).unwrap();
-
-//     let b_prv_policy = format!(
-//       "thresh(2,pk({}),pk({}),pk({}))",
-//       a_xpub,
-//       b_xprv,
-//       e_xpub
-//     );
-//     let b_prv_desc = policy::compile(&b_prv_policy, policy::ScriptType::WSH).unwrap();
-
-//     let e_prv_policy = format!(
-//       "thresh(2,pk({}),pk({}),pk({}))",
-//       a_xpub,
-//       b_xpub,
-//       e_xprv
-//     );
-//     let e_prv_desc = policy::compile(&e_prv_policy, policy::ScriptType::WSH).unwrap();
-
-//     assert_eq!(
-//       address::generate(WalletConfig::new_offline(&a_prv_desc).unwrap(),0).unwrap().address,
-//       address::generate(WalletConfig::new_offline(&b_prv_desc).unwrap(),0).unwrap().address,
-//     );
-//     assert_eq!(
-//       address::generate(WalletConfig::new_offline(&a_prv_desc).unwrap(),0).unwrap().address,
-//       address::generate(WalletConfig::new_offline(&e_prv_desc).unwrap(),0).unwrap().address,
-//     );
-//     assert_eq!(
-//       address::generate(WalletConfig::new_offline(&a_prv_desc).unwrap(),0).unwrap().address,
-//       expected_address,
-//     );
-
-//     let _balance = history::sync_balance(
-//       WalletConfig::new(&public_descriptor,DEFAULT_TESTNET_NODE,None).unwrap()
-//     ).unwrap();
-    
-//     let policy_id = policy::id(
-//       WalletConfig::new_offline(&public_descriptor).unwrap()
-//     ).unwrap();
-//     assert!(!policy_id.0); // policy path not required
-    
-
-//     let init_psbt = psbt::build(
-//       WalletConfig::new(&public_descriptor,DEFAULT_TESTNET_NODE,None).unwrap(), 
-//       vec![output], 
-//       300,
-//       None,
-//       false
-//     ).unwrap();
-//     assert!(!init_psbt.is_finalized);
-    
-//     let a_signed = psbt::sign(
-//       WalletConfig::new_offline(&a_prv_desc).unwrap(),
-//       &init_psbt.psbt,
-//     ).unwrap();
-//     assert!(!a_signed.is_finalized);
-
-//     let b_signed = psbt::sign(
-//       WalletConfig::new_offline(&b_prv_desc).unwrap(),
-//       &a_signed.psbt,
-//     ).unwrap();
-//     assert!(b_signed.is_finalized);
-
-//     // let broadcast = psbt::broadcast(
-//     //   WalletConfig::new(&public_descriptor,DEFAULT_TESTNET_NODE,None).unwrap(), 
-//     //   &b_signed.psbt,
-//     // ).unwrap();
-//     // assert!(broadcast.txid.len() == 64);
-
-//     // let new_balance = history::sync_balance(
-//     //   WalletConfig::new(&public_descriptor,DEFAULT_TESTNET_NODE,None).unwrap()
-//     // ).unwrap();
-
-//     // println!("Balance: {}", new_balance.balance);
-
-//   }
-
-//   #[test]
-//   fn  test_raft_online(){
-//       let return_address = "mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt";
-//       let output = psbt::TxOutput{
-//         address: return_address.to_string(),
-//         amount: Some(700)
-//       };
-//       let expected_address = "tb1q4p6g4cs3e9wwyeg0jfwsrqx6j93h28zzumasvutfv684gmqlkl2qnx0ypf".to_string();
-//       let timelock = 600_000;
-  
-//       let public_descriptor = "wsh(or_d(pk([c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg/*),and_v(v:pk([958b4ad7/84h/1h/0h]tpubDD249riYEKPfTZrg9vTdwzmcF3ccLdgHQCp2vx4tuHFV4z6aq42xDZVG1EA3qQwNJkPRaZe6tb3jds65qMRYFgevbd6PXdUPutLbB5JQjft/*),after(600000))))";
-  
-//       let a_xprv = "[c2cb6b81/84h/1h/0h]tprv8gooCbWUp5z9GWY95JWHgMSs28YCpKTdwL2G3GbYSch2ME8SPzVxAjiaHgCDdyHBLGkUB7Nh5U66G5uLwykSAvECA78Bx6T8mS3wVgQMAGf/*";
-//       let a_xpub = "[c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg/*";
-
-//       let e_xprv = "[958b4ad7/84h/1h/0h]tprv8gL21SgJ5whza6ptGGo3Yb7Vg26gBJVNpuDFeS2bV1T6EVqpCfDN34sPq6rvHgdbfXMP4mUf7khyCRhLpCGCrHqNxDL3g8456KckGU3Q9gW/*";
-//       let e_xpub = "[958b4ad7/84h/1h/0h]tpubDD249riYEKPfTZrg9vTdwzmcF3ccLdgHQCp2vx4tuHFV4z6aq42xDZVG1EA3qQwNJkPRaZe6tb3jds65qMRYFgevbd6PXdUPutLbB5JQjft/*";
-
-//       let a_prv_policy = format!(
-//         "or(pk({}),and(pk({}),after({})))",
-//         a_xprv,
-//         e_xpub,
-//         timelock
-//       );
-//       let a_prv_desc = policy::compile(&a_prv_policy, policy::ScriptType::WSH).unwrap();
-  
-//       let e_prv_policy = format!(
-//         "or(pk({}),and(pk({}),after({})))",
-//         a_xpub,
-//         e_xprv,
-//         timelock
-//       );
-//       let e_prv_desc = policy::compile(&e_prv_policy, policy::ScriptType::WSH).unwrap();
-  
-//       assert_eq!(
-//         address::generate(WalletConfig::new_offline(&a_prv_desc).unwrap(),0).unwrap().address,
-//         address::generate(WalletConfig::new_offline(&e_prv_desc).unwrap(),0).unwrap().address,
-//       );
-//       assert_eq!(
-//         address::generate(WalletConfig::new_offline(&a_prv_desc).unwrap(),0).unwrap().address,
-//         address::generate(WalletConfig::new_offline(&public_descriptor).unwrap(),0).unwrap().address,
-//       );
-//       assert_eq!(
-//         address::generate(WalletConfig::new_offline(&a_prv_desc).unwrap(),0).unwrap().address,
-//         expected_address,
-//       );
-  
-//       let policy_id = policy::id(
-//         WalletConfig::new_offline(&public_descriptor).unwrap()
-//       ).unwrap();
-//       assert!(policy_id.0); // policy path IS required
-//       //single sig is only path 0
-//       //custodian uses path 1
-
-//       let init_psbt = psbt::build(
-//         WalletConfig::new(&public_descriptor,DEFAULT_TESTNET_NODE,None).unwrap(), 
-//         vec![output.clone()], 
-//         300,
-//         None,
-//         false
-//       ).unwrap_err();
-//       assert_eq!(init_psbt.message, "Spending Policy Required".to_string());
-
-//       let mut policy_path_both = BTreeMap::new();
-//       policy_path_both.insert(policy_id.1.clone(), vec![0,1]);
-
-//       let init_psbt = psbt::build(
-//         WalletConfig::new(&public_descriptor,DEFAULT_TESTNET_NODE,None).unwrap(), 
-//         vec![output], 
-//         300,
-//         Some(policy_path_both),
-//         false,
-//       ).unwrap();
-//       assert!(!init_psbt.is_finalized);
-
-//       let e_signed = psbt::sign(
-//         WalletConfig::new_offline(&e_prv_desc).unwrap(),
-//         &init_psbt.psbt,
-//       ).unwrap();
-//       assert!(e_signed.is_finalized);
-
-//       let a_signed = psbt::sign(
-//         WalletConfig::new_offline(&a_prv_desc).unwrap(),
-//         &init_psbt.psbt,
-//       ).unwrap();
-//       assert!(a_signed.is_finalized);
-      
-  
-//   }
-// }
+use crate::config::WalletConfig;
+use crate::e::{ErrorKind, S5Error};
+use crate::key::slip132;
+use crate::wallet::descriptor;
+use crate::wallet::policy::{self, ScriptType};
+use bdk::miniscript::descriptor::{DescriptorPublicKey, DescriptorSecretKey};
+use bitcoin::secp256k1::Secp256k1;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CustomWallet {
+  Solo,
+  Escrow,
+  Team,
+  Raft,
+  Custom,
+}
+impl Display for CustomWallet {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    match self {
+      CustomWallet::Solo => write!(f, "Solo"),
+      CustomWallet::Escrow => write!(f, "Escrow"),
+      CustomWallet::Team => write!(f, "Team"),
+      CustomWallet::Raft => write!(f, "Raft"),
+      CustomWallet::Custom => write!(f, "Custom"),
+    }
+  }
+}
+impl CustomWallet {
+  pub fn from_str(template: &str) -> Result<CustomWallet, S5Error> {
+    match template.to_lowercase().as_str() {
+      "solo" => Ok(CustomWallet::Solo),
+      "escrow" => Ok(CustomWallet::Escrow),
+      "team" => Ok(CustomWallet::Team),
+      "raft" => Ok(CustomWallet::Raft),
+      "custom" => Ok(CustomWallet::Custom),
+      _ => Err(S5Error::new(ErrorKind::Input, "Unknown Wallet Template")),
+    }
+  }
+}
+
+/// Parameters of a wallet template.
+/// keys are participant xpubs (or the user's own xprv) i.e. [fingerprint/path]xpub/*
+/// Escrow keys are buyer, seller, escrow agent. Raft keys are owner, custodian.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateParams {
+  pub keys: Vec<String>,
+  pub threshold: Option<usize>,
+  pub timelock: Option<u32>,
+  pub policy: Option<String>,
+  pub script_type: Option<ScriptType>,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomWalletPolicy {
+  pub template: String,
+  pub policy: String,
+  pub descriptor: String,
+  pub policy_id: String,
+  pub requires_path: bool,
+  pub recovery: Vec<String>,
+}
+impl CustomWalletPolicy {
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
+    };
+
+    CString::new(stringified).unwrap().into_raw()
+  }
+}
+
+/// Gets the master fingerprint of a public or private descriptor key.
+fn fingerprint(key: &str) -> Result<String, S5Error> {
+  if let Ok(public) = DescriptorPublicKey::from_str(key) {
+    return Ok(public.master_fingerprint().to_string());
+  }
+  match DescriptorSecretKey::from_str(key) {
+    Ok(secret) => match secret.as_public(&Secp256k1::new()) {
+      Ok(public) => Ok(public.master_fingerprint().to_string()),
+      Err(_) => Err(S5Error::new(ErrorKind::Key, "Invalid Key")),
+    },
+    Err(_) => Err(S5Error::new(ErrorKind::Key, "Invalid Key")),
+  }
+}
+
+/// Formats a unix timestamp as a UTC date and time, i.e. 2024-01-01 00:00 UTC.
+fn utc_date(timestamp: u32) -> String {
+  let days = (timestamp / 86_400) as i64;
+  let minutes = timestamp % 86_400 / 60;
+  // civil date from days since 1970-01-01 (proleptic gregorian calendar)
+  let z = days + 719_468;
+  let era = z / 146_097;
+  let day_of_era = z - era * 146_097;
+  let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let shifted_month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+  let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, minutes / 60, minutes % 60)
+}
+
+fn check_keys(template: CustomWallet, keys: &[String], count: Option<usize>) -> Result<Vec<String>, S5Error> {
+  if let Some(count) = count {
+    if keys.len() != count {
+      return Err(S5Error::new(
+        ErrorKind::Input,
+        &format!("{} Requires {} Keys", template, count),
+      ));
+    }
+  } else if keys.len() < 2 {
+    return Err(S5Error::new(
+      ErrorKind::Input,
+      &format!("{} Requires At Least 2 Keys", template),
+    ));
+  }
+  let mut fingerprints: Vec<String> = vec![];
+  for key in keys {
+    let fingerprint = fingerprint(key)?;
+    if fingerprints.contains(&fingerprint) {
+      return Err(S5Error::new(ErrorKind::Input, "Duplicate Key"));
+    }
+    fingerprints.push(fingerprint);
+  }
+  Ok(fingerprints)
+}
+
+/// Last step of every recovery text, since scripted wallets cannot be restored from keys alone.
+const DESCRIPTOR_BACKUP: &str =
+  "Keep a copy of the descriptor with every key backup. Keys alone cannot restore this wallet.";
+
+/// Builds the policy of a template and the instructions to recover funds from it.
+fn template_policy(
+  template: CustomWallet,
+  params: &TemplateParams,
+) -> Result<(String, ScriptType, Vec<String>), S5Error> {
+  let keys: Vec<String> = params.keys.iter().map(|key| descriptor::ranged(key)).collect();
+  let descriptor_backup = DESCRIPTOR_BACKUP.to_string();
+  match template {
+    CustomWallet::Solo => {
+      let fingerprints = check_keys(template, &keys, Some(1))?;
+      Ok((
+        format!("pk({})", keys[0]),
+        ScriptType::WPKH,
+        vec![format!(
+          "The mnemonic of {} alone restores and spends this wallet.",
+          fingerprints[0]
+        )],
+      ))
+    }
+    CustomWallet::Escrow => {
+      let fingerprints = check_keys(template, &keys, Some(3))?;
+      Ok((
+        format!("thresh(2,pk({}),pk({}),pk({}))", keys[0], keys[1], keys[2]),
+        ScriptType::WSH,
+        vec![
+          format!(
+            "Buyer {} and seller {} spend together to complete the trade.",
+            fingerprints[0], fingerprints[1]
+          ),
+          format!(
+            "In a dispute, escrow {} signs with either the buyer or the seller.",
+            fingerprints[2]
+          ),
+          descriptor_backup,
+        ],
+      ))
+    }
+    CustomWallet::Team => {
+      let fingerprints = check_keys(template, &keys, None)?;
+      let threshold = match params.threshold {
+        Some(threshold) if threshold >= 1 && threshold <= keys.len() => threshold,
+        _ => return Err(S5Error::new(ErrorKind::Input, "Invalid Threshold")),
+      };
+      let pks: Vec<String> = keys.iter().map(|key| format!("pk({})", key)).collect();
+      Ok((
+        format!("thresh({},{})", threshold, pks.join(",")),
+        ScriptType::WSH,
+        vec![
+          format!(
+            "Any {} of {} keys ({}) can spend.",
+            threshold,
+            keys.len(),
+            fingerprints.join(",")
+          ),
+          descriptor_backup,
+        ],
+      ))
+    }
+    CustomWallet::Raft => {
+      let fingerprints = check_keys(template, &keys, Some(2))?;
+      let timelock = match params.timelock {
+        Some(timelock) if timelock > 0 => timelock,
+        _ => return Err(S5Error::new(ErrorKind::Input, "Raft Requires A Timelock")),
+      };
+      // after() values from 500000000 are unix timestamps instead of block heights
+      let unlock = if timelock < 500_000_000 {
+        format!("block {}", timelock)
+      } else {
+        format!("{} (unix time {})", utc_date(timelock), timelock)
+      };
+      Ok((
+        format!("or(pk({}),and(pk({}),after({})))", keys[0], keys[1], timelock),
+        ScriptType::WSH,
+        vec![
+          format!("Owner {} can spend at any time with policy path [0].", fingerprints[0]),
+          format!(
+            "After {}, custodian {} can spend alone with policy path [1].",
+            unlock, fingerprints[1]
+          ),
+          descriptor_backup,
+        ],
+      ))
+    }
+    CustomWallet::Custom => match &params.policy {
+      Some(policy) if !policy.trim().is_empty() => Ok((
+        policy.trim().to_string(),
+        ScriptType::WSH,
+        vec![descriptor_backup],
+      )),
+      _ => Err(S5Error::new(ErrorKind::Input, "Custom Requires A Policy")),
+    },
+  }
+}
+
+/// Creates the policy, descriptor, policy id and recovery instructions of a wallet template.
+/// The script type defaults to wpkh for Solo and wsh for all others.
+pub fn create(template: CustomWallet, params: TemplateParams) -> Result<CustomWalletPolicy, S5Error> {
+  let params = TemplateParams {
+    keys: params.keys.iter().map(|key| slip132::normalize(key)).collect(),
+    ..params
+  };
+  let (policy, default_script_type, mut recovery) = template_policy(template, &params)?;
+  let descriptor = policy::compile(&policy, params.script_type.unwrap_or(default_script_type))?;
+  let (requires_path, policy_id) = policy::id(WalletConfig::new_offline(&descriptor, None)?)?;
+  if requires_path && template == CustomWallet::Custom {
+    recovery.push(format!(
+      "Spending requires a policy path for node {}. Use policy_tree to choose one.",
+      policy_id
+    ));
+  }
+  Ok(CustomWalletPolicy {
+    template: template.to_string(),
+    policy,
+    descriptor,
+    policy_id,
+    requires_path,
+    recovery,
+  })
+}
+
+/// Average number of blocks mined per day.
+pub const BLOCKS_PER_DAY: u32 = 144;
+/// Largest block based delay of a relative timelock.
+pub const MAX_RELATIVE_BLOCKS: u32 = 65_535;
+
+/// Unit of an inheritance delay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelayUnit {
+  Blocks,
+  Days,
+}
+impl DelayUnit {
+  pub fn from_str(unit: &str) -> Result<DelayUnit, S5Error> {
+    match unit.to_lowercase().as_str() {
+      "day" | "days" => Ok(DelayUnit::Days),
+      "block" | "blocks" => Ok(DelayUnit::Blocks),
+      _ => Err(S5Error::new(ErrorKind::Input, "Invalid Delay Unit")),
+    }
+  }
+}
+
+/// Whether the heir's delay counts from a fixed block height (after) or from each deposit's confirmation (older).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimelockType {
+  Absolute,
+  Relative,
+}
+impl Display for TimelockType {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    match self {
+      TimelockType::Absolute => write!(f, "after"),
+      TimelockType::Relative => write!(f, "older"),
+    }
+  }
+}
+impl TimelockType {
+  pub fn from_str(timelock_type: &str) -> Result<TimelockType, S5Error> {
+    match timelock_type.to_lowercase().as_str() {
+      "after" | "absolute" => Ok(TimelockType::Absolute),
+      "older" | "relative" => Ok(TimelockType::Relative),
+      _ => Err(S5Error::new(ErrorKind::Input, "Invalid Timelock Type")),
+    }
+  }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InheritanceWallet {
+  pub policy: String,
+  pub descriptor: String,
+  pub policy_id: String,
+  pub timelock_type: String,
+  pub timelock: u32,
+  pub delay_blocks: u32,
+  pub current_height: Option<u32>,
+  pub recovery_height: Option<u32>,
+  pub recovery: Vec<String>,
+}
+impl InheritanceWallet {
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
+    };
+
+    CString::new(stringified).unwrap().into_raw()
+  }
+}
+
+/// Creates an inheritance wallet where the owner can always spend and the heir can spend alone after a delay.
+/// An absolute delay is converted into a block height from current_height.
+/// A relative delay restarts with every deposit, so the heir can spend a utxo only once it is delay blocks old.
+pub fn inheritance(
+  owner: &str,
+  heir: &str,
+  delay: u32,
+  unit: DelayUnit,
+  timelock_type: TimelockType,
+  current_height: Option<u32>,
+  script_type: Option<ScriptType>,
+) -> Result<InheritanceWallet, S5Error> {
+  let keys = vec![
+    descriptor::ranged(&slip132::normalize(owner)),
+    descriptor::ranged(&slip132::normalize(heir)),
+  ];
+  let fingerprints = check_keys(CustomWallet::Raft, &keys, Some(2))?;
+  let delay_blocks = match unit {
+    DelayUnit::Blocks => delay,
+    DelayUnit::Days => delay.saturating_mul(BLOCKS_PER_DAY),
+  };
+  if delay_blocks == 0 {
+    return Err(S5Error::new(ErrorKind::Input, "Delay Must Be Positive"));
+  }
+  let days = delay_blocks / BLOCKS_PER_DAY;
+
+  let (timelock, recovery_height, heir_recovery) = match timelock_type {
+    TimelockType::Absolute => {
+      let current_height = match current_height {
+        Some(height) => height,
+        None => return Err(S5Error::new(ErrorKind::Input, "Absolute Delay Requires Current Height")),
+      };
+      let height = match current_height.checked_add(delay_blocks) {
+        Some(height) if height < 500_000_000 => height,
+        _ => return Err(S5Error::new(ErrorKind::Input, "Delay Too Large")),
+      };
+      (
+        height,
+        Some(height),
+        format!(
+          "From block {} (~{} days after block {}), heir {} can spend alone with policy path [1].",
+          height, days, current_height, fingerprints[1]
+        ),
+      )
+    }
+    TimelockType::Relative => {
+      if delay_blocks > MAX_RELATIVE_BLOCKS {
+        return Err(S5Error::new(ErrorKind::Input, "Relative Delay Exceeds 65535 Blocks"));
+      }
+      (
+        delay_blocks,
+        None,
+        format!(
+          "Heir {} can spend a utxo alone with policy path [1] once it has {} confirmations (~{} days).",
+          fingerprints[1], delay_blocks, days
+        ),
+      )
+    }
+  };
+
+  let policy = format!(
+    "or(pk({}),and(pk({}),{}({})))",
+    keys[0], keys[1], timelock_type, timelock
+  );
+  let descriptor = policy::compile(&policy, script_type.unwrap_or(ScriptType::WSH))?;
+  let (_, policy_id) = policy::id(WalletConfig::new_offline(&descriptor, None)?)?;
+  let mut recovery = vec![
+    format!("Owner {} can spend at any time with policy path [0].", fingerprints[0]),
+    heir_recovery,
+  ];
+  if timelock_type == TimelockType::Relative {
+    recovery.push(
+      "The owner keeps the heir locked out by moving funds to the wallet again before the delay passes."
+        .to_string(),
+    );
+  }
+  recovery.push(DESCRIPTOR_BACKUP.to_string());
+
+  Ok(InheritanceWallet {
+    policy,
+    descriptor,
+    policy_id,
+    timelock_type: timelock_type.to_string(),
+    timelock,
+    delay_blocks,
+    current_height,
+    recovery_height,
+    recovery,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::wallet::address;
+
+  const A_XPUB: &str = "[c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg/*";
+  const A_XPRV: &str = "[c2cb6b81/84h/1h/0h]tprv8gooCbWUp5z9GWY95JWHgMSs28YCpKTdwL2G3GbYSch2ME8SPzVxAjiaHgCDdyHBLGkUB7Nh5U66G5uLwykSAvECA78Bx6T8mS3wVgQMAGf/*";
+  const B_XPUB: &str = "[9eee95d3/84h/1h/0h]tpubDDmbx25X8ThKHXgMnAj4Tbka85SiisU2KapKp42foekYKYvJ7AiB7MWV5G9wZKpax4fbqaHhuL1MShri2ACA9UDeSmDXyajHQ5ohf8RiUW6/*";
+  const E_XPUB: &str = "[958b4ad7/84h/1h/0h]tpubDD249riYEKPfTZrg9vTdwzmcF3ccLdgHQCp2vx4tuHFV4z6aq42xDZVG1EA3qQwNJkPRaZe6tb3jds65qMRYFgevbd6PXdUPutLbB5JQjft/*";
+
+  fn params(keys: &[&str]) -> TemplateParams {
+    TemplateParams {
+      keys: keys.iter().map(|key| key.to_string()).collect(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_escrow_template() {
+    let escrow = create(CustomWallet::Escrow, params(&[A_XPUB, B_XPUB, E_XPUB])).unwrap();
+    assert!(escrow.descriptor.starts_with("wsh(multi(2,[c2cb6b81/84h/1h/0h]"));
+    assert!(!escrow.requires_path);
+    assert_eq!(escrow.template, "Escrow");
+    assert_eq!(escrow.recovery.len(), 3);
+    let address0 = address::generate(WalletConfig::new_offline(&escrow.descriptor, None).unwrap(), 0)
+      .unwrap()
+      .address;
+    assert_eq!(address0, "tb1q64kehk7zq7xnkhv9m4n800g0tuyn4xrdg68376kgzqsyml2246tq7nu8uq");
+
+    // a participant's own private template derives the same addresses
+    let a_escrow = create(CustomWallet::Escrow, params(&[A_XPRV, B_XPUB, E_XPUB])).unwrap();
+    let a_address0 = address::generate(WalletConfig::new_offline(&a_escrow.descriptor, None).unwrap(), 0)
+      .unwrap()
+      .address;
+    assert_eq!(a_address0, address0);
+
+    assert_eq!(
+      create(CustomWallet::Escrow, params(&[A_XPUB, B_XPUB])).err().unwrap().message,
+      "Escrow Requires 3 Keys"
+    );
+    assert_eq!(
+      create(CustomWallet::Escrow, params(&[A_XPUB, A_XPRV, E_XPUB])).err().unwrap().message,
+      "Duplicate Key"
+    );
+  }
+
+  #[test]
+  fn test_raft_template() {
+    let mut raft_params = params(&[A_XPUB, E_XPUB]);
+    assert_eq!(
+      create(CustomWallet::Raft, raft_params.clone()).err().unwrap().message,
+      "Raft Requires A Timelock"
+    );
+    raft_params.timelock = Some(600_000);
+    let raft = create(CustomWallet::Raft, raft_params).unwrap();
+    assert!(raft.requires_path);
+    assert!(raft.recovery[1].contains("After block 600000"));
+    let mut dated_params = params(&[A_XPUB, E_XPUB]);
+    dated_params.timelock = Some(1_735_689_600);
+    let dated = create(CustomWallet::Raft, dated_params).unwrap();
+    assert!(dated.recovery[1].starts_with("After 2025-01-01 00:00 UTC (unix time 1735689600), custodian"));
+    assert_eq!(utc_date(1_709_210_096), "2024-02-29 12:34 UTC");
+    let address0 = address::generate(WalletConfig::new_offline(&raft.descriptor, None).unwrap(), 0)
+      .unwrap()
+      .address;
+    assert_eq!(address0, "tb1q4p6g4cs3e9wwyeg0jfwsrqx6j93h28zzumasvutfv684gmqlkl2qnx0ypf");
+  }
+
+  #[test]
+  fn test_team_solo_custom_templates() {
+    let mut team_params = params(&[A_XPUB, B_XPUB, E_XPUB]);
+    assert_eq!(
+      create(CustomWallet::Team, team_params.clone()).err().unwrap().message,
+      "Invalid Threshold"
+    );
+    team_params.threshold = Some(3);
+    let team = create(CustomWallet::Team, team_params).unwrap();
+    assert!(team.descriptor.starts_with("wsh(multi(3,"));
+    assert!(team.recovery[0].starts_with("Any 3 of 3 keys"));
+
+    let solo = create(CustomWallet::Solo, params(&[A_XPUB])).unwrap();
+    assert!(solo.descriptor.starts_with("wpkh([c2cb6b81/84h/1h/0h]"));
+    let (a_origin, a_tpub) = A_XPUB.split_once(']').unwrap();
+    let a_vpub = format!("{}]{}/*", a_origin, slip132::from_xpub(a_tpub.trim_end_matches("/*"), "wpkh").unwrap());
+    assert_eq!(create(CustomWallet::Solo, params(&[&a_vpub])).unwrap().descriptor, solo.descriptor);
+    let mut tr_params = params(&[A_XPUB]);
+    tr_params.script_type = Some(ScriptType::TR);
+    assert!(create(CustomWallet::Solo, tr_params).unwrap().descriptor.starts_with("tr("));
+
+    let mut custom_params = params(&[]);
+    custom_params.policy = Some(format!("or(pk({}),and(pk({}),older(144)))", A_XPUB, B_XPUB));
+    let custom = create(CustomWallet::Custom, custom_params).unwrap();
+    assert!(custom.requires_path);
+    assert_eq!(custom.recovery.len(), 2);
+
+    assert_eq!(
+      create(CustomWallet::Custom, params(&[])).err().unwrap().message,
+      "Custom Requires A Policy"
+    );
+    assert_eq!(
+      CustomWallet::from_str("vault").err().unwrap().message,
+      "Unknown Wallet Template"
+    );
+    assert_eq!(
+      create(CustomWallet::Solo, params(&["notakey"])).err().unwrap().message,
+      "Invalid Key"
+    );
+  }
+
+  #[test]
+  fn test_inheritance() {
+    let absolute = inheritance(
+      A_XPUB,
+      E_XPUB,
+      30,
+      DelayUnit::Days,
+      TimelockType::Absolute,
+      Some(2_000_000),
+      None,
+    )
+    .unwrap();
+    assert_eq!(absolute.delay_blocks, 4_320);
+    assert_eq!(absolute.recovery_height, Some(2_004_320));
+    assert_eq!(absolute.timelock_type, "after");
+    assert!(absolute.policy.ends_with("after(2004320)))"));
+    assert!(absolute.descriptor.starts_with("wsh(or_d(pk([c2cb6b81/84h/1h/0h]"));
+    assert!(absolute.recovery[1].starts_with("From block 2004320 (~30 days after block 2000000)"));
+
+    let relative = inheritance(
+      A_XPUB,
+      E_XPUB,
+      1_000,
+      DelayUnit::Blocks,
+      TimelockType::Relative,
+      None,
+      Some(ScriptType::TR),
+    )
+    .unwrap();
+    assert_eq!(relative.timelock, 1_000);
+    assert_eq!(relative.recovery_height, None);
+    assert!(relative.descriptor.starts_with("tr([c2cb6b81/84h/1h/0h]"));
+    assert!(relative.descriptor.contains("older(1000)"));
+    assert_eq!(relative.recovery.len(), 4);
+
+    assert_eq!(
+      inheritance(A_XPUB, E_XPUB, 30, DelayUnit::Days, TimelockType::Absolute, None, None)
+        .err()
+        .unwrap()
+        .message,
+      "Absolute Delay Requires Current Height"
+    );
+    assert_eq!(
+      inheritance(A_XPUB, E_XPUB, 500, DelayUnit::Days, TimelockType::Relative, None, None)
+        .err()
+        .unwrap()
+        .message,
+      "Relative Delay Exceeds 65535 Blocks"
+    );
+    assert_eq!(
+      inheritance(A_XPUB, A_XPRV, 10, DelayUnit::Blocks, TimelockType::Relative, None, None)
+        .err()
+        .unwrap()
+        .message,
+      "Duplicate Key"
+    );
+  }
+
+  #[test]
+  fn test_inheritance_units() {
+    assert_eq!(DelayUnit::from_str("day").unwrap(), DelayUnit::Days);
+    assert_eq!(DelayUnit::from_str("Days").unwrap(), DelayUnit::Days);
+    assert_eq!(DelayUnit::from_str("block").unwrap(), DelayUnit::Blocks);
+    assert_eq!(DelayUnit::from_str("blocks").unwrap(), DelayUnit::Blocks);
+    assert_eq!(DelayUnit::from_str("Days ").err().unwrap().message, "Invalid Delay Unit");
+    assert_eq!(DelayUnit::from_str("dyas").err().unwrap().message, "Invalid Delay Unit");
+    assert_eq!(DelayUnit::from_str("").err().unwrap().kind, "Input");
+
+    assert_eq!(TimelockType::from_str("after").unwrap(), TimelockType::Absolute);
+    assert_eq!(TimelockType::from_str("Absolute").unwrap(), TimelockType::Absolute);
+    assert_eq!(TimelockType::from_str("older").unwrap(), TimelockType::Relative);
+    assert_eq!(TimelockType::from_str("relative").unwrap(), TimelockType::Relative);
+    assert_eq!(
+      TimelockType::from_str("before").err().unwrap().message,
+      "Invalid Timelock Type"
+    );
+  }
+}
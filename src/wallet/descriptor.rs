@@ -0,0 +1,488 @@
+use crate::config::WalletConfig;
+use crate::e::{ErrorKind, S5Error};
+use crate::key::slip132;
+use bdk::database::MemoryDatabase;
+use bdk::descriptor::checksum::get_checksum;
+use bdk::descriptor::Descriptor;
+use bdk::miniscript::descriptor::{
+    DescriptorPublicKey, DescriptorSecretKey, DescriptorTrait, DescriptorType, SinglePubKey, Wildcard,
+};
+use bdk::miniscript::policy::{Liftable, Semantic};
+use bdk::miniscript::{ForEach, ForEachKey};
+use bdk::{KeychainKind, Wallet};
+use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::Secp256k1;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DescriptorChecksum {
+    pub descriptor: String,
+    pub checksum: String,
+}
+impl DescriptorChecksum {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Computes the BIP380 checksum of a descriptor, ignoring any existing checksum.
+pub fn checksum(descriptor: &str) -> Result<String, S5Error> {
+    let descriptor = match descriptor.split_once('#') {
+        Some((descriptor, _)) => descriptor,
+        None => descriptor,
+    };
+    match get_checksum(descriptor.trim()) {
+        Ok(result) => Ok(result),
+        Err(_) => Err(S5Error::new(
+            ErrorKind::Input,
+            "Invalid Descriptor Character",
+        )),
+    }
+}
+
+/// Verifies the checksum of a descriptor if it has one and returns the descriptor without it.
+pub fn verify(descriptor: &str) -> Result<&str, S5Error> {
+    let (body, given) = match descriptor.split_once('#') {
+        Some((body, given)) => (body, given),
+        None => return Ok(descriptor),
+    };
+    let expected = checksum(body)?;
+    if given != expected {
+        return Err(S5Error::new(
+            ErrorKind::Input,
+            "Invalid Descriptor Checksum",
+        ));
+    }
+    Ok(body)
+}
+
+/// Returns the descriptor with its checksum appended, verifying any existing checksum first.
+pub fn with_checksum(descriptor: &str) -> Result<DescriptorChecksum, S5Error> {
+    let body = verify(descriptor.trim())?;
+    let checksum = checksum(body)?;
+    Ok(DescriptorChecksum {
+        descriptor: format!("{}#{}", body, checksum),
+        checksum,
+    })
+}
+
+/// Gets the network of an extended key, defaulting to testnet for single keys.
+pub fn key_network(key: &str) -> Network {
+    let key = slip132::normalize(key);
+    if let Ok(DescriptorPublicKey::XPub(xpub)) = DescriptorPublicKey::from_str(key.trim()) {
+        return xpub.xkey.network;
+    }
+    match DescriptorSecretKey::from_str(key.trim()) {
+        Ok(DescriptorSecretKey::XPrv(xprv)) => xprv.xkey.network,
+        _ => Network::Testnet,
+    }
+}
+
+/// Adds a wildcard to extended account keys so the wallet is ranged. Single keys are kept as they are.
+pub fn ranged(key: &str) -> String {
+    let key = key.trim();
+    if key.ends_with('*') || (!key.contains("pub") && !key.contains("prv")) {
+        key.to_string()
+    } else {
+        format!("{}/*", key)
+    }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DescriptorKeyInfo {
+    pub fingerprint: String,
+    pub origin_path: Option<String>,
+    pub key: String,
+    pub derivation_path: Option<String>,
+    pub is_private: bool,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Timelock {
+    pub kind: String,
+    pub value: u32,
+    pub unit: String,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DescriptorAnalysis {
+    pub script_type: String,
+    pub network: String,
+    pub keys: Vec<DescriptorKeyInfo>,
+    pub has_private_keys: bool,
+    pub policy: String,
+    pub threshold: Option<usize>,
+    pub is_multisig: bool,
+    pub timelocks: Vec<Timelock>,
+    pub max_satisfaction_weight: Option<usize>,
+    pub policy_id: String,
+    pub requires_path: bool,
+}
+impl DescriptorAnalysis {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+fn script_type(descriptor_type: DescriptorType) -> String {
+    match descriptor_type {
+        DescriptorType::Bare => "bare",
+        DescriptorType::Sh | DescriptorType::ShSortedMulti => "sh",
+        DescriptorType::Pkh => "pkh",
+        DescriptorType::Wpkh => "wpkh",
+        DescriptorType::Wsh | DescriptorType::WshSortedMulti => "wsh",
+        DescriptorType::ShWsh | DescriptorType::ShWshSortedMulti => "sh-wsh",
+        DescriptorType::ShWpkh => "sh-wpkh",
+        DescriptorType::Tr => "tr",
+    }
+    .to_string()
+}
+
+fn key_info(key: &DescriptorPublicKey, is_private: bool) -> DescriptorKeyInfo {
+    let wildcard = |wildcard: Wildcard| match wildcard {
+        Wildcard::None => "",
+        Wildcard::Unhardened => "/*",
+        Wildcard::Hardened => "/*'",
+    };
+    match key {
+        DescriptorPublicKey::SinglePub(single) => DescriptorKeyInfo {
+            fingerprint: key.master_fingerprint().to_string(),
+            origin_path: single.origin.as_ref().map(|(_, path)| path.to_string()),
+            key: match single.key {
+                SinglePubKey::FullKey(pubkey) => pubkey.to_string(),
+                SinglePubKey::XOnly(pubkey) => pubkey.to_string(),
+            },
+            derivation_path: None,
+            is_private,
+        },
+        DescriptorPublicKey::XPub(xpub) => DescriptorKeyInfo {
+            fingerprint: key.master_fingerprint().to_string(),
+            origin_path: xpub.origin.as_ref().map(|(_, path)| path.to_string()),
+            key: xpub.xkey.to_string(),
+            derivation_path: Some(format!(
+                "{}{}",
+                xpub.derivation_path,
+                wildcard(xpub.wildcard)
+            )),
+            is_private,
+        },
+    }
+}
+
+fn collect_timelocks(policy: &Semantic<DescriptorPublicKey>, timelocks: &mut Vec<Timelock>) {
+    match policy {
+        Semantic::After(value) => timelocks.push(Timelock {
+            kind: "absolute".to_string(),
+            value: *value,
+            unit: if *value < 500_000_000 {
+                "height".to_string()
+            } else {
+                "timestamp".to_string()
+            },
+        }),
+        Semantic::Older(value) => timelocks.push(Timelock {
+            kind: "relative".to_string(),
+            value: *value,
+            unit: if value & (1 << 22) != 0 {
+                "time".to_string()
+            } else {
+                "blocks".to_string()
+            },
+        }),
+        Semantic::Threshold(_, subs) => subs
+            .iter()
+            .for_each(|sub| collect_timelocks(sub, timelocks)),
+        _ => (),
+    }
+}
+
+/// Describes the deposit descriptor of a wallet: its script type, keys, spending structure and timelocks.
+pub fn analyze(config: WalletConfig) -> Result<DescriptorAnalysis, S5Error> {
+    let secp = Secp256k1::new();
+    let (parsed, keymap) = match Descriptor::parse_descriptor(&secp, &config.deposit_desc) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    };
+
+    let mut keys: Vec<DescriptorKeyInfo> = vec![];
+    parsed.for_each_key(|key| {
+        if let ForEach::Key(key) = key {
+            keys.push(key_info(key, keymap.contains_key(key)));
+        }
+        true
+    });
+
+    let semantic = match parsed.lift() {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    };
+    let threshold = match &semantic {
+        Semantic::Threshold(k, subs) => Some((*k, subs.len())),
+        _ => None,
+    };
+    let is_multisig = match &semantic {
+        Semantic::Threshold(_, subs) => subs
+            .iter()
+            .all(|sub| matches!(sub, Semantic::KeyHash(_))),
+        _ => false,
+    };
+    let mut timelocks = vec![];
+    collect_timelocks(&semantic, &mut timelocks);
+
+    let wallet = match Wallet::new(
+        &config.deposit_desc,
+        Some(&config.change_desc),
+        config.network,
+        MemoryDatabase::default(),
+    ) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    let policies = match wallet.policies(KeychainKind::External) {
+        Ok(Some(result)) => result,
+        Ok(None) => return Err(S5Error::new(ErrorKind::Wallet, "No Spending Policy")),
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+
+    Ok(DescriptorAnalysis {
+        script_type: script_type(parsed.desc_type()),
+        network: match config.network {
+            Network::Bitcoin => "main".to_string(),
+            _ => "test".to_string(),
+        },
+        has_private_keys: !keymap.is_empty(),
+        keys,
+        policy: semantic.to_string(),
+        threshold: threshold.map(|(k, _)| k),
+        is_multisig: is_multisig && threshold.is_some_and(|(_, n)| n > 1),
+        timelocks,
+        max_satisfaction_weight: parsed.max_satisfaction_weight().ok(),
+        policy_id: policies.id.clone(),
+        requires_path: policies.requires_path(),
+    })
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PublicDescriptor {
+    pub descriptor: String,
+    pub had_private_keys: bool,
+}
+impl PublicDescriptor {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Unhardened indexes standing in for multipath groups, which miniscript cannot parse.
+const MULTIPATH_PLACEHOLDER: u32 = 2_147_483_000;
+
+/// Converts a descriptor with private keys into its watch-only equivalent.
+/// xprvs become xpubs and keep their origin; hardened steps after an xprv move into the origin.
+/// Multipath groups are kept and a pair of descriptors is converted line by line.
+/// The result always includes a checksum.
+pub fn to_public(descriptor: &str) -> Result<PublicDescriptor, S5Error> {
+    let parts: Vec<&str> = descriptor.split_whitespace().collect();
+    if parts.is_empty() {
+        return Err(S5Error::new(ErrorKind::Input, "Empty Descriptor"));
+    }
+    let secp = Secp256k1::new();
+    let mut public: Vec<String> = vec![];
+    let mut had_private_keys = false;
+    for part in parts {
+        let body = verify(part)?;
+
+        let mut groups: Vec<&str> = vec![];
+        let mut placeheld = String::new();
+        let mut rest = body;
+        while let Some(start) = rest.find('<') {
+            let end = match rest[start..].find('>') {
+                Some(end) => start + end,
+                None => return Err(S5Error::new(ErrorKind::Input, "Invalid Multipath Descriptor")),
+            };
+            placeheld.push_str(&rest[..start]);
+            placeheld.push_str(&(MULTIPATH_PLACEHOLDER + groups.len() as u32).to_string());
+            groups.push(&rest[start..=end]);
+            rest = &rest[end + 1..];
+        }
+        placeheld.push_str(rest);
+
+        let (parsed, keymap) = match Descriptor::parse_descriptor(&secp, &placeheld) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        had_private_keys = had_private_keys || !keymap.is_empty();
+        let mut converted = verify(&parsed.to_string())?.to_string();
+        for (index, group) in groups.iter().enumerate() {
+            converted = converted.replace(
+                &format!("/{}", MULTIPATH_PLACEHOLDER + index as u32),
+                &format!("/{}", group),
+            );
+        }
+        public.push(with_checksum(&converted)?.descriptor);
+    }
+
+    Ok(PublicDescriptor {
+        descriptor: public.join("\n"),
+        had_private_keys,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        // test vector from BIP380
+        let descriptor = "raw(deadbeef)";
+        assert_eq!(checksum(descriptor).unwrap(), "89f8spxm");
+        assert_eq!(verify("raw(deadbeef)#89f8spxm").unwrap(), descriptor);
+        assert_eq!(
+            verify("raw(deadbeef)#89f8spxn").err().unwrap().message,
+            "Invalid Descriptor Checksum"
+        );
+        assert_eq!(verify(descriptor).unwrap(), descriptor);
+        let result = with_checksum(descriptor).unwrap();
+        assert_eq!(result.descriptor, "raw(deadbeef)#89f8spxm");
+        assert_eq!(with_checksum(&result.descriptor).unwrap(), result);
+        assert_eq!(
+            checksum("raw(deadbeef\u{e9})").err().unwrap().message,
+            "Invalid Descriptor Character"
+        );
+    }
+
+    #[test]
+    fn test_keys() {
+        let xprv = "[c2cb6b81/84h/1h/0h]tprv8gooCbWUp5z9GWY95JWHgMSs28YCpKTdwL2G3GbYSch2ME8SPzVxAjiaHgCDdyHBLGkUB7Nh5U66G5uLwykSAvECA78Bx6T8mS3wVgQMAGf";
+        let xpub = "[c2cb6b81/84h/1h/0h]xpub6CUGRUonZSQ4TWtTMmzXdrXDtypWKiKrhko4egpiMZbpiaQL2jkwSB1icqYh2cfDfVxdx4df189oLKnC5fSwqPfgyP3hooxujYzAu3fDVmz";
+        assert_eq!(key_network(xprv), Network::Testnet);
+        assert_eq!(key_network(xpub), Network::Bitcoin);
+        assert_eq!(
+            key_network("02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c"),
+            Network::Testnet
+        );
+        assert_eq!(ranged(xprv), format!("{}/*", xprv));
+        assert_eq!(ranged(&format!("{}/0/*", xprv)), format!("{}/0/*", xprv));
+        assert_eq!(
+            ranged("02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c"),
+            "02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c"
+        );
+    }
+
+    #[test]
+    fn test_analyze() {
+        let xkey = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
+        let config = WalletConfig::new_offline(&format!("wpkh({}/*)", xkey), None).unwrap();
+        let analysis = analyze(config).unwrap();
+        assert_eq!(analysis.script_type, "wpkh");
+        assert_eq!(analysis.network, "test");
+        assert!(!analysis.has_private_keys);
+        assert!(!analysis.is_multisig);
+        assert_eq!(analysis.keys.len(), 1);
+        assert_eq!(analysis.keys[0].fingerprint, "db7d25b5");
+        assert_eq!(analysis.keys[0].origin_path, Some("m/84'/1'/6'".to_string()));
+        assert_eq!(analysis.keys[0].derivation_path, Some("m/0/*".to_string()));
+        assert_eq!(analysis.max_satisfaction_weight, Some(112));
+        assert!(!analysis.requires_path);
+
+        let alice_xprv = "[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*";
+        let escrow_xpub = "[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*";
+        let raft = format!(
+            "wsh(or_d(pk({}),and_v(v:pk({}),after(595600))))",
+            alice_xprv, escrow_xpub
+        );
+        let config = WalletConfig::new_offline(&raft, None).unwrap();
+        let analysis = analyze(config).unwrap();
+        assert_eq!(analysis.script_type, "wsh");
+        assert!(analysis.has_private_keys);
+        assert!(analysis.keys[0].is_private);
+        assert!(!analysis.keys[1].is_private);
+        assert_eq!(analysis.keys[1].fingerprint, "66a0c105");
+        assert_eq!(
+            analysis.timelocks,
+            vec![Timelock {
+                kind: "absolute".to_string(),
+                value: 595_600,
+                unit: "height".to_string(),
+            }]
+        );
+        assert!(!analysis.is_multisig);
+        assert_eq!(analysis.policy_id, "hgl9rs6e");
+        assert!(analysis.requires_path);
+
+        let escrow = format!("wsh(multi(2,{},{}))", alice_xprv, escrow_xpub);
+        let config = WalletConfig::new_offline(&escrow, None).unwrap();
+        let analysis = analyze(config).unwrap();
+        assert!(analysis.is_multisig);
+        assert_eq!(analysis.threshold, Some(2));
+        assert!(analysis.timelocks.is_empty());
+    }
+
+    #[test]
+    fn test_to_public() {
+        let xprv = "[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49";
+        let xpub = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
+        let escrow_xpub = "[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R";
+
+        let public = to_public(&format!("wpkh({}/*)", xprv)).unwrap();
+        assert!(public.had_private_keys);
+        assert_eq!(
+            public.descriptor,
+            with_checksum(&format!("wpkh({}/*)", xpub)).unwrap().descriptor
+        );
+
+        let multi = format!("wsh(multi(2,{}/<0;1>/*,{}/<0;1>/*))", xprv, escrow_xpub);
+        let public = to_public(&multi).unwrap();
+        assert_eq!(
+            public.descriptor,
+            with_checksum(&format!("wsh(multi(2,{}/<0;1>/*,{}/<0;1>/*))", xpub, escrow_xpub))
+                .unwrap()
+                .descriptor
+        );
+
+        let watcher = with_checksum(&format!("wpkh({}/0/*)", xpub)).unwrap().descriptor;
+        let public = to_public(&watcher).unwrap();
+        assert!(!public.had_private_keys);
+        assert_eq!(public.descriptor, watcher);
+
+        let pair = format!("wpkh({}/0/*)\nwpkh({}/1/*)", xprv, xprv);
+        let public = to_public(&pair).unwrap();
+        assert_eq!(public.descriptor.lines().count(), 2);
+        assert!(!public.descriptor.contains("tprv"));
+    }
+}
@@ -0,0 +1,50 @@
+use bdk::database::{BatchOperations, MemoryDatabase};
+use bdk::wallet::AddressIndex;
+use bdk::{BlockTime, KeychainKind, LocalUtxo, TransactionDetails, Wallet};
+use bitcoin::{Network, OutPoint, Transaction, TxOut};
+
+/// Value of the utxo a funded wallet starts with.
+pub const FUNDED_AMOUNT: u64 = 50_000;
+
+/// Creates an offline testnet wallet with a single confirmed utxo (at height 100) on its first address.
+pub fn funded_wallet(descriptor: &str, change_descriptor: Option<&str>) -> Wallet<MemoryDatabase> {
+    let address = Wallet::new(descriptor, change_descriptor, Network::Testnet, MemoryDatabase::default())
+        .unwrap()
+        .get_address(AddressIndex::Peek(0))
+        .unwrap();
+    let funding = Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![],
+        output: vec![TxOut {
+            value: FUNDED_AMOUNT,
+            script_pubkey: address.script_pubkey(),
+        }],
+    };
+    let mut database = MemoryDatabase::default();
+    database
+        .set_utxo(&LocalUtxo {
+            outpoint: OutPoint::new(funding.txid(), 0),
+            txout: funding.output[0].clone(),
+            keychain: KeychainKind::External,
+            is_spent: false,
+        })
+        .unwrap();
+    database
+        .set_script_pubkey(&address.script_pubkey(), KeychainKind::External, 0)
+        .unwrap();
+    database
+        .set_tx(&TransactionDetails {
+            transaction: Some(funding.clone()),
+            txid: funding.txid(),
+            received: FUNDED_AMOUNT,
+            sent: 0,
+            fee: None,
+            confirmation_time: Some(BlockTime {
+                height: 100,
+                timestamp: 0,
+            }),
+        })
+        .unwrap();
+    Wallet::new(descriptor, change_descriptor, Network::Testnet, database).unwrap()
+}
@@ -0,0 +1,376 @@
+use crate::e::{ErrorKind, S5Error};
+use crate::key::slip132;
+use crate::key::ur::{self, Cbor, UrKey};
+use crate::wallet::coldcard::ColdCardKeys;
+use crate::wallet::descriptor;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchOnlyKey {
+    pub fingerprint: Option<String>,
+    pub derivation: Option<String>,
+    pub xpub: String,
+    pub key: String,
+    pub script_type: String,
+    pub descriptor: Option<String>,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HardwareExport {
+    pub format: String,
+    pub keys: Vec<WatchOnlyKey>,
+}
+impl HardwareExport {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Infers the script type of an account from its BIP44/45/48/49/84/86 derivation path.
+fn path_script_type(path: &DerivationPath) -> Option<&'static str> {
+    let hardened = |child: Option<&ChildNumber>| match child {
+        Some(ChildNumber::Hardened { index }) => Some(*index),
+        _ => None,
+    };
+    match hardened(path.as_ref().first()) {
+        Some(44) => Some("pkh"),
+        Some(45) => Some("sh"),
+        Some(49) => Some("sh-wpkh"),
+        Some(84) => Some("wpkh"),
+        Some(86) => Some("tr"),
+        Some(48) => match hardened(path.as_ref().get(3)) {
+            Some(1) => Some("sh-wsh"),
+            Some(2) => Some("wsh"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Builds a watch-only key with its origin and, for single sig script types, its descriptor.
+/// The script type is taken from the export, then from the SLIP-132 version, then from the path, defaulting to wpkh.
+/// Multisig (sh, sh-wsh, wsh) keys have no descriptor and are meant for sorted_multisig.
+fn watch_only(
+    fingerprint: Option<Fingerprint>,
+    path: Option<DerivationPath>,
+    xpub: ExtendedPubKey,
+    script_type: Option<String>,
+) -> Result<WatchOnlyKey, S5Error> {
+    let script_type = script_type
+        .or_else(|| path.as_ref().and_then(path_script_type).map(|script_type| script_type.to_string()))
+        .unwrap_or_else(|| "wpkh".to_string());
+    let key = match (fingerprint, &path) {
+        (Some(fingerprint), Some(path)) => format!(
+            "[{}{}]{}",
+            fingerprint,
+            path.to_string().trim_start_matches('m'),
+            xpub
+        ),
+        _ => xpub.to_string(),
+    };
+    let descriptor = match script_type.as_str() {
+        "pkh" => Some(format!("pkh({}/*)", key)),
+        "sh-wpkh" => Some(format!("sh(wpkh({}/*))", key)),
+        "wpkh" => Some(format!("wpkh({}/*)", key)),
+        "tr" => Some(format!("tr({}/*)", key)),
+        _ => None,
+    };
+    let descriptor = match descriptor {
+        Some(descriptor) => Some(descriptor::with_checksum(&descriptor)?.descriptor),
+        None => None,
+    };
+
+    Ok(WatchOnlyKey {
+        fingerprint: fingerprint.map(|fingerprint| fingerprint.to_string()),
+        derivation: path.map(|path| path.to_string()),
+        xpub: xpub.to_string(),
+        key,
+        script_type,
+        descriptor,
+    })
+}
+
+fn parse_fingerprint(fingerprint: &str) -> Result<Fingerprint, S5Error> {
+    match Vec::<u8>::from_hex(fingerprint.trim()) {
+        Ok(bytes) if bytes.len() == 4 => Ok(Fingerprint::from(&bytes[..])),
+        _ => Err(S5Error::new(ErrorKind::Key, "Invalid Fingerprint")),
+    }
+}
+
+fn parse_path(path: &str) -> Result<DerivationPath, S5Error> {
+    let path = path.trim().trim_start_matches('m').trim_start_matches('/');
+    match DerivationPath::from_str(&format!("m/{}", path).replace('h', "'")) {
+        Ok(path) => Ok(path),
+        Err(_) => Err(S5Error::new(ErrorKind::Key, "Invalid Derivation Path")),
+    }
+}
+
+/// Parses [fingerprint/path]xpub (or any SLIP-132 key), with or without its origin and a trailing /0/* or /*.
+fn parse_key(text: &str) -> Result<WatchOnlyKey, S5Error> {
+    let text = text.trim();
+    let (origin, key) = match text.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((origin, key)) => (Some(origin), key),
+        None => (None, text),
+    };
+    let key = key.split('/').next().unwrap_or("");
+    let (fingerprint, path) = match origin {
+        Some(origin) => {
+            let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+            (Some(parse_fingerprint(fingerprint)?), Some(parse_path(path)?))
+        }
+        None => (None, None),
+    };
+    let converted = slip132::to_xpub(key)?;
+    watch_only(fingerprint, path, converted.xpub, converted.script_type)
+}
+
+fn from_ur_key(key: UrKey) -> Result<WatchOnlyKey, S5Error> {
+    watch_only(key.fingerprint, key.path, key.xpub, key.script_type)
+}
+
+/// Reads the payload of a ur:crypto-account (all accounts of a device), ur:crypto-output or ur:crypto-hdkey.
+pub fn from_ur_payload(ur_type: &str, payload: &[u8]) -> Result<Vec<WatchOnlyKey>, S5Error> {
+    let cbor = Cbor::decode(payload)?;
+    match ur_type {
+        "crypto-account" | "account-descriptor" => ur::decode_account(&cbor)?
+            .into_iter()
+            .map(from_ur_key)
+            .collect(),
+        "crypto-output" | "output-descriptor" => Ok(vec![from_ur_key(ur::decode_output(&cbor)?)?]),
+        "crypto-hdkey" | "hdkey" => Ok(vec![from_ur_key(ur::decode_hdkey(&cbor)?)?]),
+        _ => Err(S5Error::new(ErrorKind::Input, "Unsupported UR Type")),
+    }
+}
+
+fn from_ur(content: &str) -> Result<Vec<WatchOnlyKey>, S5Error> {
+    let (ur_type, payload) = ur::decode(content)?;
+    from_ur_payload(&ur_type, &payload)
+}
+
+/// Maps the address type names used in Coldcard, Passport and Keystone key exports to script types.
+fn json_script_type(name: &str) -> Option<&'static str> {
+    match name {
+        "p2pkh" => Some("pkh"),
+        "p2sh_p2wpkh" | "p2wpkh_p2sh" => Some("sh-wpkh"),
+        "p2wpkh" => Some("wpkh"),
+        "p2tr" => Some("tr"),
+        "p2sh" => Some("sh"),
+        "p2sh_p2wsh" | "p2wsh_p2sh" => Some("sh-wsh"),
+        "p2wsh" => Some("wsh"),
+        _ => None,
+    }
+}
+
+/// Reads the JSON key exports of air-gapped signers:
+/// - Coldcard and Passport generic JSON (bip44, bip49, bip84, bip86 sections), verified against their first addresses
+/// - Coldcard and Passport multisig key exports (xfp with p2sh, p2sh_p2wsh, p2wsh and their _deriv paths)
+/// - Keystone and Wasabi style single keys (ExtPubKey, MasterFingerprint and AccountKeyPath, or xfp, xpub and path)
+fn from_json(content: &str) -> Result<(String, Vec<WatchOnlyKey>), S5Error> {
+    let json: Value = match serde_json::from_str(content) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid JSON")),
+    };
+    let field = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| json.get(name).and_then(Value::as_str))
+            .map(|value| value.to_string())
+    };
+
+    if ["bip44", "bip49", "bip84", "bip86"].iter().any(|bip| json.get(bip).is_some()) {
+        let coldcard = ColdCardKeys::from_json_str(content)?;
+        coldcard.descriptors()?;
+        let fingerprint = parse_fingerprint(&coldcard.xfp)?;
+        let keys = coldcard
+            .sections()
+            .into_iter()
+            .map(|(_, script_type, deriv, xpub, _)| {
+                watch_only(
+                    Some(fingerprint),
+                    Some(parse_path(deriv)?),
+                    slip132::to_xpub(xpub)?.xpub,
+                    Some(script_type.to_string()),
+                )
+            })
+            .collect::<Result<Vec<WatchOnlyKey>, S5Error>>()?;
+        return Ok(("coldcard".to_string(), keys));
+    }
+
+    let fingerprint = match field(&["xfp", "MasterFingerprint", "master_fingerprint"]) {
+        Some(fingerprint) => Some(parse_fingerprint(&fingerprint)?),
+        None => None,
+    };
+    let mut keys: Vec<WatchOnlyKey> = vec![];
+    if let Some(object) = json.as_object() {
+        for (name, value) in object {
+            let script_type = match json_script_type(name) {
+                Some(script_type) => script_type,
+                None => continue,
+            };
+            let (xpub, deriv) = match (value.as_str(), field(&[&format!("{}_deriv", name)])) {
+                (Some(xpub), Some(deriv)) => (xpub, deriv),
+                _ => continue,
+            };
+            keys.push(watch_only(
+                fingerprint,
+                Some(parse_path(&deriv)?),
+                slip132::to_xpub(xpub)?.xpub,
+                Some(script_type.to_string()),
+            )?);
+        }
+    }
+    if !keys.is_empty() {
+        return Ok(("multisig".to_string(), keys));
+    }
+
+    let xpub = match field(&["ExtPubKey", "xpub", "ext_pub_key"]) {
+        Some(xpub) => slip132::to_xpub(&xpub)?,
+        None => return Err(S5Error::new(ErrorKind::Input, "Unknown Export Format")),
+    };
+    let path = match field(&["AccountKeyPath", "path", "deriv"]) {
+        Some(path) => Some(parse_path(&path)?),
+        None => None,
+    };
+    Ok((
+        "json".to_string(),
+        vec![watch_only(fingerprint, path, xpub.xpub, xpub.script_type)?],
+    ))
+}
+
+/// Imports the xpubs of a hardware wallet from its export, pasted or scanned.
+/// Accepts UR (crypto-account, crypto-output, crypto-hdkey), Coldcard, Passport and Keystone JSON,
+/// and one [fingerprint/path]xpub per line, where xpub may also be a SLIP-132 ypub, zpub, Ypub or Zpub.
+/// The parts of an animated UR can be given together, one per line. BBQr QR codes must be joined into their content first.
+pub fn import(content: &str) -> Result<HardwareExport, S5Error> {
+    let content = content.trim();
+    let (format, keys) = if content.to_lowercase().starts_with("ur:") {
+        ("ur".to_string(), from_ur(content)?)
+    } else if content.starts_with('{') {
+        from_json(content)?
+    } else {
+        (
+            "key".to_string(),
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(parse_key)
+                .collect::<Result<Vec<WatchOnlyKey>, S5Error>>()?,
+        )
+    };
+    if keys.is_empty() {
+        return Err(S5Error::new(ErrorKind::Input, "Export Has No Keys"));
+    }
+    Ok(HardwareExport { format, keys })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::wallet::address;
+    use bitcoin::network::constants::Network;
+
+    const FINGERPRINT: &str = "3442193e";
+    const XPUB_84: &str = "tpubDDNRbZGvdA33cgpY5uy2mmphT7sK4uciRjcQScSd64S5KRyZDxHcPuzs24or84Hywugb2JbEEt2jWH8fduiN9cmZzkSj8sSSx6txXkhXyZs";
+    const FIRST_84: &str = "tb1q7f0pjwhc3jzzv0w4uurm589506glv2dg2qy7ze";
+
+    fn first_address(descriptor: &str) -> String {
+        let mut config = WalletConfig::new_offline(descriptor, None).unwrap();
+        config.network = Network::Testnet;
+        address::generate(config, 0).unwrap().address
+    }
+
+    #[test]
+    fn test_key_strings() {
+        let export = import(&format!("[{}/84h/1h/0h]{}/0/*", FINGERPRINT, XPUB_84)).unwrap();
+        assert_eq!(export.format, "key");
+        let key = &export.keys[0];
+        assert_eq!(key.script_type, "wpkh");
+        assert_eq!(key.derivation, Some("m/84'/1'/0'".to_string()));
+        assert_eq!(key.key, format!("[{}/84'/1'/0']{}", FINGERPRINT, XPUB_84));
+        assert_eq!(first_address(key.descriptor.as_ref().unwrap()), FIRST_84);
+
+        // BIP84 test vector: a bare zpub implies wpkh
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let export = import(&format!("\n{}\n", zpub)).unwrap();
+        assert_eq!(export.keys[0].fingerprint, None);
+        assert_eq!(
+            export.keys[0].descriptor.as_ref().unwrap().split('#').next().unwrap(),
+            "wpkh(xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/*)"
+        );
+
+        let cosigner = import(&format!("[{}/48'/1'/0'/2']{}", FINGERPRINT, XPUB_84)).unwrap();
+        assert_eq!(cosigner.keys[0].script_type, "wsh");
+        assert_eq!(cosigner.keys[0].descriptor, None);
+
+        assert_eq!(import("[zzzz/84h]tpub").err().unwrap().message, "Invalid Fingerprint");
+    }
+
+    #[test]
+    fn test_json_exports() {
+        let keystone = format!(
+            "{{\"ExtPubKey\": \"{}\", \"MasterFingerprint\": \"{}\", \"AccountKeyPath\": \"m/84'/1'/0'\"}}",
+            XPUB_84,
+            FINGERPRINT.to_uppercase()
+        );
+        let export = import(&keystone).unwrap();
+        assert_eq!(export.format, "json");
+        assert_eq!(export.keys[0].fingerprint, Some(FINGERPRINT.to_string()));
+        assert_eq!(first_address(export.keys[0].descriptor.as_ref().unwrap()), FIRST_84);
+
+        let passport_multisig = format!(
+            "{{\"xfp\": \"{}\", \"account\": 0, \"p2wsh_deriv\": \"m/48'/1'/0'/2'\", \"p2wsh\": \"{}\", \"p2sh_p2wsh_deriv\": \"m/48'/1'/0'/1'\", \"p2sh_p2wsh\": \"{}\"}}",
+            FINGERPRINT.to_uppercase(),
+            XPUB_84,
+            XPUB_84
+        );
+        let export = import(&passport_multisig).unwrap();
+        assert_eq!(export.format, "multisig");
+        assert_eq!(export.keys.len(), 2);
+        let wsh = export.keys.iter().find(|key| key.script_type == "wsh").unwrap();
+        assert_eq!(wsh.key, format!("[{}/48'/1'/0'/2']{}", FINGERPRINT, XPUB_84));
+
+        let coldcard = format!(
+            "{{\"chain\": \"XTN\", \"xfp\": \"{}\", \"bip84\": {{\"deriv\": \"m/84'/1'/0'\", \"xpub\": \"{}\", \"first\": \"{}\"}}}}",
+            FINGERPRINT, XPUB_84, FIRST_84
+        );
+        let export = import(&coldcard).unwrap();
+        assert_eq!(export.format, "coldcard");
+        assert_eq!(export.keys.len(), 1);
+        assert_eq!(export.keys[0].script_type, "wpkh");
+        assert!(import(&coldcard.replace(FIRST_84, "tb1qnotthefirstaddress")).is_err());
+
+        assert_eq!(import("{\"name\": \"x\"}").err().unwrap().message, "Unknown Export Format");
+    }
+
+    #[test]
+    fn test_ur_export() {
+        // crypto-account with the wpkh (84'/1'/0') and tr (86'/1'/0') accounts of 3442193e
+        let cbor = Vec::<u8>::from_hex("a2011a3442193e0282d90194d9012fa5035821037250fb8bc368072d81f1619d6e1d174c0b276f615ab7a8b00660168f3863268804582000b43c8f69fc37de32691dd408fec9e2df389a4a8ca97a6b9158d357af36cb6a05d90131a1020106d90130a301861854f501f500f5021a3442193e0303081ab706ea64d90199d9012fa503582103ce3a35a807271b1d2fbbb442bbd4777987367e1a6a18e5a6e0eb22385cfda86c0458201c4304310d93112c92580cd2bb783ab776e70da4f87e2e3758e86a9341c2eaf105d90131a1020106d90130a301861856f501f500f5021a3442193e0303081acc225a2e").unwrap();
+        let export = import(&format!("UR:CRYPTO-ACCOUNT/{}", ur::bytewords_encode(&cbor).to_uppercase())).unwrap();
+        assert_eq!(export.format, "ur");
+        assert_eq!(export.keys.len(), 2);
+        assert_eq!(first_address(export.keys[0].descriptor.as_ref().unwrap()), FIRST_84);
+        assert_eq!(
+            first_address(export.keys[1].descriptor.as_ref().unwrap()),
+            "tb1pzh75rtx74l85v2xqfr5uln7mhy40vyqzm68ml4yngcqy9v085tqqvq73sa"
+        );
+    }
+}
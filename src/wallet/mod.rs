@@ -1,9 +1,20 @@
 pub mod address;
+pub mod amount;
+pub mod bip21;
 pub mod history;
+pub mod multisig;
 pub mod coldcard;
+pub mod custom;
+pub mod descriptor;
+#[cfg(test)]
+pub mod fixtures;
+pub mod hardware;
 pub mod policy;
 pub mod psbt;
+pub mod qr;
+pub mod refresh;
 pub mod utxo;
+pub mod sweep;
 pub mod sync;
 
 
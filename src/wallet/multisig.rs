@@ -0,0 +1,606 @@
+use crate::config::split_descriptor;
+use crate::e::{ErrorKind, S5Error};
+use crate::key::slip132;
+use crate::wallet::descriptor;
+use crate::wallet::policy::ScriptType;
+use bdk::descriptor::Descriptor;
+use bdk::miniscript::descriptor::{
+  DescriptorPublicKey, DescriptorSecretKey, ShInner, Wsh, WshInner,
+};
+use bdk::miniscript::Terminal;
+use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::Secp256k1;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+/// Largest number of keys in a segwit multisig script.
+pub const MAX_MULTISIG_KEYS: usize = 20;
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MultisigWallet {
+  pub script_type: String,
+  pub threshold: usize,
+  pub fingerprints: Vec<String>,
+  pub network: String,
+  pub descriptor: String,
+}
+impl MultisigWallet {
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
+    };
+
+    CString::new(stringified).unwrap().into_raw()
+  }
+}
+
+/// Gets the fingerprint and network of a cosigner's [fingerprint/path]xpub (or xprv).
+/// The key origin is required so that signing devices can find their key in the wallet.
+fn cosigner(key: &str) -> Result<(String, Network), S5Error> {
+  let public = match DescriptorPublicKey::from_str(key) {
+    Ok(public) => public,
+    Err(_) => match DescriptorSecretKey::from_str(key) {
+      Ok(secret) => match secret.as_public(&Secp256k1::new()) {
+        Ok(public) => public,
+        Err(_) => return Err(S5Error::new(ErrorKind::Key, "Invalid Key")),
+      },
+      Err(_) => return Err(S5Error::new(ErrorKind::Key, "Invalid Key")),
+    },
+  };
+  match public {
+    DescriptorPublicKey::XPub(xpub) => match xpub.origin {
+      Some((fingerprint, _)) => Ok((fingerprint.to_string(), xpub.xkey.network)),
+      None => Err(S5Error::new(ErrorKind::Key, "Key Requires Origin")),
+    },
+    DescriptorPublicKey::SinglePub(_) => Err(S5Error::new(ErrorKind::Key, "Key Must Be Extended")),
+  }
+}
+
+/// Wraps a multi or sortedmulti of the given keys in the script type.
+fn multisig_descriptor(
+  script_type: ScriptType,
+  threshold: usize,
+  keys: &[String],
+  sorted: bool,
+) -> Result<String, S5Error> {
+  let multi = format!(
+    "{}({},{})",
+    if sorted { "sortedmulti" } else { "multi" },
+    threshold,
+    keys.join(",")
+  );
+  match script_type {
+    ScriptType::WSH => Ok(format!("wsh({})", multi)),
+    ScriptType::SHWSH => Ok(format!("sh(wsh({}))", multi)),
+    ScriptType::SH => Ok(format!("sh({})", multi)),
+    _ => Err(S5Error::new(
+      ErrorKind::Input,
+      "Multisig Requires wsh, sh-wsh Or sh",
+    )),
+  }
+}
+
+/// Creates a threshold of N multisig wallet with sortedmulti.
+/// Keys are sorted in each script, so every cosigner gets the same addresses whatever order they entered the keys in.
+pub fn sortedmulti(keys: &[String], threshold: usize, script_type: ScriptType) -> Result<MultisigWallet, S5Error> {
+  if keys.len() < 2 {
+    return Err(S5Error::new(ErrorKind::Input, "Multisig Requires At Least 2 Keys"));
+  }
+  if keys.len() > MAX_MULTISIG_KEYS {
+    return Err(S5Error::new(ErrorKind::Input, "Multisig Allows At Most 20 Keys"));
+  }
+  if threshold == 0 || threshold > keys.len() {
+    return Err(S5Error::new(ErrorKind::Input, "Invalid Threshold"));
+  }
+  let keys: Vec<String> = keys.iter().map(|key| descriptor::ranged(&slip132::normalize(key))).collect();
+  let mut fingerprints: Vec<String> = vec![];
+  let mut network: Option<Network> = None;
+  for key in keys.iter() {
+    let (fingerprint, key_network) = cosigner(key)?;
+    if network.is_some_and(|network| network != key_network) {
+      return Err(S5Error::new(ErrorKind::Input, "Key Network Mismatch"));
+    }
+    if fingerprints.contains(&fingerprint) {
+      return Err(S5Error::new(ErrorKind::Input, "Duplicate Fingerprint"));
+    }
+    network = Some(key_network);
+    fingerprints.push(fingerprint);
+  }
+
+  let descriptor = multisig_descriptor(script_type, threshold, &keys, true)?;
+  match Descriptor::parse_descriptor(&Secp256k1::new(), &descriptor) {
+    Ok(_) => (),
+    Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+  };
+
+  Ok(MultisigWallet {
+    script_type: script_type.to_string(),
+    threshold,
+    fingerprints,
+    network: match network {
+      Some(Network::Bitcoin) => "main".to_string(),
+      _ => "test".to_string(),
+    },
+    descriptor: descriptor::with_checksum(&descriptor)?.descriptor,
+  })
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultisigKey {
+  pub fingerprint: String,
+  pub derivation: String,
+  pub xpub: String,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MultisigConfig {
+  pub name: String,
+  pub script_type: String,
+  pub threshold: usize,
+  pub sorted: bool,
+  pub keys: Vec<MultisigKey>,
+  pub descriptor: String,
+}
+impl MultisigConfig {
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
+    };
+
+    CString::new(stringified).unwrap().into_raw()
+  }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MultisigFile {
+  pub format: String,
+  pub content: String,
+}
+impl MultisigFile {
+  pub fn c_stringify(&self) -> *mut c_char {
+    let stringified = match serde_json::to_string(self) {
+      Ok(result) => result,
+      Err(_) => {
+        return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+          .unwrap()
+          .into_raw()
+      }
+    };
+
+    CString::new(stringified).unwrap().into_raw()
+  }
+}
+
+/// Wallet file formats shared with other multisig coordinators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MultisigFormat {
+  /// Coldcard multisig setup text file, also read by Sparrow, Nunchuk and Passport.
+  Coldcard,
+  /// Specter Desktop wallet JSON, also read and written by Sparrow.
+  Specter,
+}
+impl Display for MultisigFormat {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    match self {
+      MultisigFormat::Coldcard => write!(f, "coldcard"),
+      MultisigFormat::Specter => write!(f, "specter"),
+    }
+  }
+}
+impl MultisigFormat {
+  pub fn from_str(format: &str) -> MultisigFormat {
+    match format.to_lowercase().as_str() {
+      "specter" | "sparrow" | "json" => MultisigFormat::Specter,
+      _ => MultisigFormat::Coldcard,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SpecterDevice {
+  #[serde(rename = "type", default)]
+  device_type: String,
+  #[serde(default)]
+  label: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SpecterWallet {
+  #[serde(default)]
+  label: String,
+  #[serde(default)]
+  blockheight: u32,
+  descriptor: String,
+  #[serde(default)]
+  devices: Vec<SpecterDevice>,
+}
+
+/// Gets the threshold, keys and whether they are sorted from a multi or sortedmulti inside wsh.
+fn wsh_multi(wsh: &Wsh<DescriptorPublicKey>) -> Option<(usize, Vec<DescriptorPublicKey>, bool)> {
+  match wsh.as_inner() {
+    WshInner::SortedMulti(multi) => Some((multi.k, multi.pks.clone(), true)),
+    WshInner::Ms(ms) => match &ms.node {
+      Terminal::Multi(k, pks) => Some((*k, pks.clone(), false)),
+      _ => None,
+    },
+  }
+}
+
+/// Reads the script type, threshold and cosigner keys of a multisig descriptor.
+/// Keys must be account xpubs with origins, the same as coordinators exchange them.
+pub fn describe(descriptor: &str, name: &str) -> Result<MultisigConfig, S5Error> {
+  let (deposit_desc, _) = split_descriptor(descriptor)?;
+  let parsed = match Descriptor::parse_descriptor(&Secp256k1::new(), &deposit_desc) {
+    Ok((parsed, _)) => parsed,
+    Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+  };
+  let multi = match &parsed {
+    Descriptor::Wsh(wsh) => wsh_multi(wsh).map(|multi| (ScriptType::WSH, multi)),
+    Descriptor::Sh(sh) => match sh.as_inner() {
+      ShInner::Wsh(wsh) => wsh_multi(wsh).map(|multi| (ScriptType::SHWSH, multi)),
+      ShInner::SortedMulti(multi) => Some((ScriptType::SH, (multi.k, multi.pks.clone(), true))),
+      ShInner::Ms(ms) => match &ms.node {
+        Terminal::Multi(k, pks) => Some((ScriptType::SH, (*k, pks.clone(), false))),
+        _ => None,
+      },
+      _ => None,
+    },
+    _ => None,
+  };
+  let (script_type, (threshold, pks, sorted)) = match multi {
+    Some(result) => result,
+    None => return Err(S5Error::new(ErrorKind::Input, "Not A Multisig Descriptor")),
+  };
+
+  let mut keys: Vec<MultisigKey> = vec![];
+  for pk in pks {
+    let xpub = match pk {
+      DescriptorPublicKey::XPub(xpub) => xpub,
+      DescriptorPublicKey::SinglePub(_) => return Err(S5Error::new(ErrorKind::Key, "Key Must Be Extended")),
+    };
+    let (fingerprint, derivation) = match xpub.origin {
+      Some(origin) => origin,
+      None => return Err(S5Error::new(ErrorKind::Key, "Key Requires Origin")),
+    };
+    if xpub.derivation_path.len() != 1 || u32::from(xpub.derivation_path[0]) != 0 {
+      return Err(S5Error::new(ErrorKind::Key, "Multisig Keys Must Be Account Keys"));
+    }
+    keys.push(MultisigKey {
+      fingerprint: fingerprint.to_string(),
+      derivation: derivation.to_string(),
+      xpub: xpub.xkey.to_string(),
+    });
+  }
+
+  let account_keys: Vec<String> = keys.iter().map(account_key).collect();
+  Ok(MultisigConfig {
+    name: name.to_string(),
+    script_type: script_type.to_string(),
+    threshold,
+    sorted,
+    descriptor: descriptor::with_checksum(&multisig_descriptor(script_type, threshold, &account_keys, sorted)?)?
+      .descriptor,
+    keys,
+  })
+}
+
+/// Formats a cosigner as [fingerprint/path]xpub/*.
+fn account_key(key: &MultisigKey) -> String {
+  format!(
+    "[{}{}]{}/*",
+    key.fingerprint,
+    key.derivation.trim_start_matches('m'),
+    key.xpub
+  )
+}
+
+/// Reads a Coldcard multisig setup file.
+/// Name, Policy (M of N), Format (P2WSH, P2SH-P2WSH or P2SH) and Derivation headers are followed by one
+/// FINGERPRINT: xpub line per cosigner. A Derivation line applies to the keys after it, so each key can have its own.
+/// Coldcard defaults to P2SH and m/45' when these headers are missing.
+pub fn from_coldcard(content: &str) -> Result<MultisigConfig, S5Error> {
+  let mut name = String::new();
+  let mut policy: Option<(usize, usize)> = None;
+  let mut script_type = ScriptType::SH;
+  let mut derivation = "m/45'".to_string();
+  let mut keys: Vec<MultisigKey> = vec![];
+
+  for line in content.lines() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let (label, value) = match line.split_once(':') {
+      Some((label, value)) => (label.trim(), value.trim()),
+      None => return Err(S5Error::new(ErrorKind::Input, "Invalid Coldcard Line")),
+    };
+    match label.to_lowercase().as_str() {
+      "name" => name = value.to_string(),
+      "policy" => {
+        let numbers: Vec<usize> = value
+          .split(|c: char| !c.is_ascii_digit())
+          .filter(|number| !number.is_empty())
+          .filter_map(|number| number.parse().ok())
+          .collect();
+        match numbers.as_slice() {
+          [m, n] => policy = Some((*m, *n)),
+          _ => return Err(S5Error::new(ErrorKind::Input, "Invalid Coldcard Policy")),
+        }
+      }
+      "format" => {
+        script_type = match value.to_uppercase().as_str() {
+          "P2WSH" => ScriptType::WSH,
+          "P2SH-P2WSH" | "P2WSH-P2SH" => ScriptType::SHWSH,
+          "P2SH" => ScriptType::SH,
+          _ => return Err(S5Error::new(ErrorKind::Input, "Invalid Coldcard Format")),
+        }
+      }
+      "derivation" => derivation = value.to_string(),
+      fingerprint if fingerprint.len() == 8 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()) => {
+        keys.push(MultisigKey {
+          fingerprint: fingerprint.to_string(),
+          derivation: derivation.replace('h', "'"),
+          xpub: value.to_string(),
+        })
+      }
+      _ => (),
+    }
+  }
+
+  let threshold = match policy {
+    Some((m, n)) if n == keys.len() => m,
+    Some(_) => return Err(S5Error::new(ErrorKind::Input, "Policy Does Not Match Keys")),
+    None => return Err(S5Error::new(ErrorKind::Input, "Missing Coldcard Policy")),
+  };
+  let account_keys: Vec<String> = keys.iter().map(account_key).collect();
+  let wallet = sortedmulti(&account_keys, threshold, script_type)?;
+  describe(&wallet.descriptor, &name)
+}
+
+/// Writes a Coldcard multisig setup file. Coldcard only supports sortedmulti.
+pub fn to_coldcard(config: &MultisigConfig) -> Result<String, S5Error> {
+  if !config.sorted {
+    return Err(S5Error::new(ErrorKind::Input, "Coldcard Requires sortedmulti"));
+  }
+  let format = match ScriptType::from_str(&config.script_type) {
+    ScriptType::WSH => "P2WSH",
+    ScriptType::SHWSH => "P2SH-P2WSH",
+    _ => "P2SH",
+  };
+  let name = if config.name.is_empty() { "stackmate" } else { &config.name };
+  let shared = config.keys.iter().all(|key| key.derivation == config.keys[0].derivation);
+
+  let mut content = format!(
+    "# Stackmate multisig setup file\n#\nName: {}\nPolicy: {} of {}\n",
+    name,
+    config.threshold,
+    config.keys.len()
+  );
+  if shared {
+    content.push_str(&format!("Derivation: {}\n", config.keys[0].derivation));
+  }
+  content.push_str(&format!("Format: {}\n", format));
+  for key in config.keys.iter() {
+    content.push('\n');
+    if !shared {
+      content.push_str(&format!("Derivation: {}\n", key.derivation));
+    }
+    content.push_str(&format!("{}: {}\n", key.fingerprint.to_uppercase(), key.xpub));
+  }
+  Ok(content)
+}
+
+/// Reads a Specter Desktop (or Sparrow) wallet JSON export.
+pub fn from_specter(content: &str) -> Result<MultisigConfig, S5Error> {
+  let wallet: SpecterWallet = match serde_json::from_str(content) {
+    Ok(result) => result,
+    Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Specter Wallet")),
+  };
+  describe(&wallet.descriptor, &wallet.label)
+}
+
+/// Writes a Specter Desktop wallet JSON, which Sparrow also imports.
+pub fn to_specter(config: &MultisigConfig) -> Result<String, S5Error> {
+  let keys: Vec<String> = config
+    .keys
+    .iter()
+    .map(|key| account_key(key).replace("/*", "/0/*"))
+    .collect();
+  let descriptor = multisig_descriptor(
+    ScriptType::from_str(&config.script_type),
+    config.threshold,
+    &keys,
+    config.sorted,
+  )?;
+  let wallet = SpecterWallet {
+    label: config.name.clone(),
+    blockheight: 0,
+    descriptor: descriptor::with_checksum(&descriptor)?.descriptor,
+    devices: config
+      .keys
+      .iter()
+      .map(|key| SpecterDevice {
+        device_type: "other".to_string(),
+        label: key.fingerprint.clone(),
+      })
+      .collect(),
+  };
+  match serde_json::to_string(&wallet) {
+    Ok(result) => Ok(result),
+    Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+  }
+}
+
+/// Imports a multisig wallet from a Coldcard setup file or a Specter/Sparrow JSON export.
+pub fn import(content: &str) -> Result<MultisigConfig, S5Error> {
+  if content.trim_start().starts_with('{') {
+    from_specter(content)
+  } else {
+    from_coldcard(content)
+  }
+}
+
+/// Exports a multisig descriptor as a wallet file for other coordinators and signing devices.
+pub fn export(descriptor: &str, name: &str, format: MultisigFormat) -> Result<MultisigFile, S5Error> {
+  let config = describe(descriptor, name)?;
+  let content = match format {
+    MultisigFormat::Coldcard => to_coldcard(&config)?,
+    MultisigFormat::Specter => to_specter(&config)?,
+  };
+  Ok(MultisigFile {
+    format: format.to_string(),
+    content,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::WalletConfig;
+  use crate::wallet::address;
+
+  const A_XPUB: &str = "[c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg";
+  const A_XPRV: &str = "[c2cb6b81/84h/1h/0h]tprv8gooCbWUp5z9GWY95JWHgMSs28YCpKTdwL2G3GbYSch2ME8SPzVxAjiaHgCDdyHBLGkUB7Nh5U66G5uLwykSAvECA78Bx6T8mS3wVgQMAGf";
+  const B_XPUB: &str = "[9eee95d3/84h/1h/0h]tpubDDmbx25X8ThKHXgMnAj4Tbka85SiisU2KapKp42foekYKYvJ7AiB7MWV5G9wZKpax4fbqaHhuL1MShri2ACA9UDeSmDXyajHQ5ohf8RiUW6";
+  const E_XPUB: &str = "[958b4ad7/84h/1h/0h]tpubDD249riYEKPfTZrg9vTdwzmcF3ccLdgHQCp2vx4tuHFV4z6aq42xDZVG1EA3qQwNJkPRaZe6tb3jds65qMRYFgevbd6PXdUPutLbB5JQjft";
+
+  fn keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+  }
+
+  fn address0(descriptor: &str) -> String {
+    address::generate(WalletConfig::new_offline(descriptor, None).unwrap(), 0)
+      .unwrap()
+      .address
+  }
+
+  #[test]
+  fn test_sortedmulti() {
+    let abe = sortedmulti(&keys(&[A_XPUB, B_XPUB, E_XPUB]), 2, ScriptType::WSH).unwrap();
+    assert!(abe.descriptor.starts_with("wsh(sortedmulti(2,[c2cb6b81/84h/1h/0h]"));
+    assert_eq!(abe.fingerprints, vec!["c2cb6b81", "9eee95d3", "958b4ad7"]);
+    assert_eq!(abe.network, "test");
+    assert_eq!(abe.script_type, "wsh");
+
+    let eba = sortedmulti(&keys(&[E_XPUB, B_XPUB, A_XPUB]), 2, ScriptType::WSH).unwrap();
+    assert_ne!(abe.descriptor, eba.descriptor);
+    assert_eq!(address0(&abe.descriptor), address0(&eba.descriptor));
+    let a_private = sortedmulti(&keys(&[B_XPUB, A_XPRV, E_XPUB]), 2, ScriptType::WSH).unwrap();
+    assert_eq!(address0(&a_private.descriptor), address0(&abe.descriptor));
+    let (e_origin, e_tpub) = E_XPUB.split_once(']').unwrap();
+    let e_vpub = format!("{}]{}", e_origin, slip132::from_xpub(e_tpub, "wsh").unwrap());
+    let e_slip132 = sortedmulti(&keys(&[A_XPUB, B_XPUB, &e_vpub]), 2, ScriptType::WSH).unwrap();
+    assert_eq!(e_slip132.descriptor, abe.descriptor);
+
+    let nested = sortedmulti(&keys(&[A_XPUB, B_XPUB, E_XPUB]), 2, ScriptType::SHWSH).unwrap();
+    assert!(nested.descriptor.starts_with("sh(wsh(sortedmulti(2,"));
+    assert!(address0(&nested.descriptor).starts_with('2'));
+  }
+
+  #[test]
+  fn test_sortedmulti_errors() {
+    let mainnet = "[3442193e/48h/0h/0h/2h]xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+    let error = |keys: Vec<String>, threshold: usize, script_type: ScriptType| {
+      sortedmulti(&keys, threshold, script_type).err().unwrap().message
+    };
+    assert_eq!(error(keys(&[A_XPUB]), 1, ScriptType::WSH), "Multisig Requires At Least 2 Keys");
+    assert_eq!(error(keys(&[A_XPUB, B_XPUB]), 3, ScriptType::WSH), "Invalid Threshold");
+    assert_eq!(error(keys(&[A_XPUB, B_XPUB]), 0, ScriptType::WSH), "Invalid Threshold");
+    assert_eq!(error(keys(&[A_XPUB, mainnet]), 2, ScriptType::WSH), "Key Network Mismatch");
+    assert_eq!(error(keys(&[A_XPUB, A_XPRV]), 2, ScriptType::WSH), "Duplicate Fingerprint");
+    assert_eq!(
+      error(keys(&[A_XPUB, B_XPUB]), 2, ScriptType::TR),
+      "Multisig Requires wsh, sh-wsh Or sh"
+    );
+    let no_origin = B_XPUB.split(']').nth(1).unwrap();
+    assert_eq!(error(keys(&[A_XPUB, no_origin]), 2, ScriptType::WSH), "Key Requires Origin");
+    assert_eq!(error(keys(&[A_XPUB, "notakey"]), 2, ScriptType::WSH), "Invalid Key");
+  }
+
+  #[test]
+  fn test_coldcard_config() {
+    let abe = sortedmulti(&keys(&[A_XPUB, B_XPUB, E_XPUB]), 2, ScriptType::WSH).unwrap();
+    let file = export(&abe.descriptor, "family vault", MultisigFormat::Coldcard).unwrap();
+    assert_eq!(file.format, "coldcard");
+    assert!(file.content.contains("Name: family vault\nPolicy: 2 of 3\nDerivation: m/84'/1'/0'\nFormat: P2WSH\n"));
+    assert!(file.content.contains("\nC2CB6B81: tpubDDVqM1Yix"));
+
+    let config = import(&file.content).unwrap();
+    assert_eq!(config.name, "family vault");
+    assert_eq!(config.threshold, 2);
+    assert_eq!(config.script_type, "wsh");
+    assert!(config.sorted);
+    assert_eq!(config.keys[2].fingerprint, "958b4ad7");
+    assert_eq!(address0(&config.descriptor), address0(&abe.descriptor));
+
+    let coldcard = format!(
+      "# Coldcard Multisig setup file (created on C2CB6B81)\n#\nName: cc\nPolicy: 2 / 3\nFormat: P2WSH-P2SH\n\n\
+       Derivation: m/84h/1h/0h\nC2CB6B81: {}\n\nDerivation: m/84'/1'/0'\n9EEE95D3: {}\n958B4AD7: {}\n",
+      A_XPUB.split(']').nth(1).unwrap(),
+      B_XPUB.split(']').nth(1).unwrap(),
+      E_XPUB.split(']').nth(1).unwrap()
+    );
+    let config = from_coldcard(&coldcard).unwrap();
+    assert_eq!(config.script_type, "sh-wsh");
+    let nested = sortedmulti(&keys(&[A_XPUB, B_XPUB, E_XPUB]), 2, ScriptType::SHWSH).unwrap();
+    assert_eq!(address0(&config.descriptor), address0(&nested.descriptor));
+
+    let missing_key = coldcard.replace("958B4AD7", "# 958B4AD7");
+    assert_eq!(
+      from_coldcard(&missing_key).err().unwrap().message,
+      "Policy Does Not Match Keys"
+    );
+  }
+
+  #[test]
+  fn test_specter_config() {
+    let abe = sortedmulti(&keys(&[A_XPUB, B_XPUB, E_XPUB]), 2, ScriptType::WSH).unwrap();
+    let file = export(&abe.descriptor, "vault", MultisigFormat::from_str("sparrow")).unwrap();
+    assert_eq!(file.format, "specter");
+    assert!(file.content.contains("\"descriptor\":\"wsh(sortedmulti(2,[c2cb6b81/84'/1'/0']"));
+    assert!(file.content.contains("/0/*"));
+    let config = import(&file.content).unwrap();
+    assert_eq!(config.name, "vault");
+    assert_eq!(address0(&config.descriptor), address0(&abe.descriptor));
+
+    let specter = format!(
+      "{{\"label\": \"Specter Vault\", \"blockheight\": 2100000, \"descriptor\": \"wsh(sortedmulti(2,{}/0/*,{}/0/*,{}/0/*))\", \"devices\": [{{\"type\": \"coldcard\", \"label\": \"a\"}}]}}",
+      A_XPUB, B_XPUB, E_XPUB
+    );
+    let config = import(&specter).unwrap();
+    assert_eq!(config.name, "Specter Vault");
+    assert_eq!(config.keys[0].derivation, "m/84'/1'/0'");
+    assert_eq!(address0(&config.descriptor), address0(&abe.descriptor));
+
+    let escrow = format!("wsh(multi(2,{}/*,{}/*,{}/*))", A_XPUB, B_XPUB, E_XPUB);
+    let unsorted = describe(&escrow, "escrow").unwrap();
+    assert!(!unsorted.sorted);
+    assert!(to_specter(&unsorted).unwrap().contains("wsh(multi(2,"));
+    assert_eq!(
+      to_coldcard(&unsorted).err().unwrap().message,
+      "Coldcard Requires sortedmulti"
+    );
+    assert_eq!(
+      describe(&format!("wpkh({}/*)", A_XPUB), "").err().unwrap().message,
+      "Not A Multisig Descriptor"
+    );
+  }
+}
@@ -1,19 +1,32 @@
 use crate::config::WalletConfig;
 use crate::e::{ErrorKind, S5Error};
+use crate::key::slip132;
+use crate::network::height;
+use crate::wallet::descriptor;
 use bdk::database::MemoryDatabase;
-// use bdk::descriptor::policy::{Policy, Satisfaction, SatisfiableItem};
-use bdk::descriptor::{Descriptor, Legacy, Miniscript, Segwitv0};
+use bdk::descriptor::policy::{BuildSatisfaction, Policy, Satisfaction, SatisfiableItem};
+use bdk::descriptor::{Descriptor, ExtractPolicy, Legacy, Miniscript, Segwitv0};
+use bdk::miniscript::descriptor::DescriptorPublicKey;
+use bdk::miniscript::descriptor::TapTree;
 use bdk::miniscript::policy::Concrete;
+use bdk::miniscript::{DescriptorTrait, ForEach, ForEachKey, Tap};
+use bdk::wallet::signer::SignersContainer;
+use bdk::SyncOptions;
 use bdk::KeychainKind;
 use bdk::Wallet;
+use bitcoin::secp256k1::{All, Secp256k1};
+use bitcoin::util::bip32::ChildNumber;
+use bitcoin::{PublicKey, XOnlyPublicKey};
+use bitcoin::util::psbt::{Input, PartiallySignedTransaction};
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
-// use std::collections::BTreeMap;
 use std::ffi::CString;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::os::raw::c_char;
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// FFI Output
 #[derive(Serialize, Deserialize, Debug)]
@@ -36,6 +49,108 @@ impl WalletPolicy {
     }
 }
 
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScriptTypeFit {
+    pub script_type: String,
+    pub weight: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScriptTypeRecommendation {
+    pub script_type: String,
+    pub options: Vec<ScriptTypeFit>,
+}
+impl ScriptTypeRecommendation {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// A keychain's policy tree and whether a policy path must be chosen to spend from it.
+#[derive(Serialize, Debug, Clone)]
+pub struct KeychainPolicy {
+    pub id: String,
+    pub requires_path: bool,
+    pub policy: Policy,
+}
+
+/// FFI Output
+#[derive(Serialize, Debug, Clone)]
+pub struct PolicyTree {
+    pub external: Option<KeychainPolicy>,
+    pub internal: Option<KeychainPolicy>,
+}
+impl PolicyTree {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimelockStatus {
+    pub kind: String,
+    pub value: u32,
+    pub is_ready: bool,
+    pub blocks_remaining: Option<u32>,
+}
+
+/// FFI Output
+#[derive(Serialize, Debug, Clone)]
+pub struct InputSatisfaction {
+    pub outpoint: String,
+    pub signed: Vec<String>,
+    pub unsigned: Vec<String>,
+    pub timelocks: Vec<TimelockStatus>,
+    pub is_satisfied: bool,
+    pub is_finalized: bool,
+    pub policy: Option<Policy>,
+}
+
+/// FFI Output
+#[derive(Serialize, Debug, Clone)]
+pub struct PsbtSatisfaction {
+    pub height: u32,
+    pub inputs: Vec<InputSatisfaction>,
+    pub is_satisfied: bool,
+}
+impl PsbtSatisfaction {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ScriptType {
     WPKH,
     WSH,
@@ -66,137 +181,476 @@ impl ScriptType {
             _ => ScriptType::WPKH,
         }
     }
+
+    /// Size in bytes of the script pubkey paid to by this script type.
+    fn script_pubkey_len(&self) -> usize {
+        match self {
+            ScriptType::WPKH => 22,
+            ScriptType::WSH | ScriptType::TR => 34,
+            ScriptType::SHWSH | ScriptType::SH => 23,
+        }
+    }
 }
-pub fn compile(policy: &str, script_type: ScriptType) -> Result<String, S5Error> {
-    let x_policy = match Concrete::<String>::from_str(policy) {
-        Ok(result) => result,
-        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Policy")),
-    };
-    let legacy_policy: Miniscript<String, Legacy> = match x_policy.compile() {
-        Ok(result) => result,
-        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
-    };
-    let segwit_policy: Miniscript<String, Segwitv0> = match x_policy.compile() {
-        Ok(result) => result,
-        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+
+fn unfit(script_type: &ScriptType, reason: &str) -> S5Error {
+    S5Error::new(
+        ErrorKind::Input,
+        &format!("Policy Does Not Fit {}: {}", script_type, reason),
+    )
+}
+
+/// BIP341 NUMS point used as an unspendable internal key when no single key branch exists.
+pub const TR_UNSPENDABLE_KEY: &str =
+    "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+/// Splits a policy into its alternative spending branches, weighted by their probability.
+fn tr_branches(policy: Concrete<String>, weight: f64, branches: &mut Vec<(f64, Concrete<String>)>) {
+    match policy {
+        Concrete::Or(subs) => {
+            let total: usize = subs.iter().map(|(odds, _)| odds).sum();
+            for (odds, sub) in subs {
+                tr_branches(sub, weight * odds as f64 / total as f64, branches);
+            }
+        }
+        Concrete::Threshold(1, subs) => {
+            let count = subs.len() as f64;
+            for sub in subs {
+                tr_branches(sub, weight / count, branches);
+            }
+        }
+        other => branches.push((weight, other)),
+    }
+}
+
+/// Compiles a policy into a taproot descriptor.
+/// The most likely single key branch becomes the key path internal key,
+/// or an unspendable key is used if there is none.
+/// Every other branch is compiled into a tapscript leaf, with more likely leaves placed closer to the root.
+fn compile_tr(policy: Concrete<String>) -> Result<Descriptor<String>, S5Error> {
+    let mut branches = vec![];
+    tr_branches(policy, 1.0, &mut branches);
+
+    let key_branch = branches
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, branch))| matches!(branch, Concrete::Key(_)))
+        .fold(None, |best: Option<(usize, f64)>, (index, (weight, _))| match best {
+            Some((_, best_weight)) if best_weight >= *weight => best,
+            _ => Some((index, *weight)),
+        });
+    let internal_key = match key_branch {
+        Some((index, _)) => match branches.remove(index).1 {
+            Concrete::Key(key) => key,
+            _ => return Err(S5Error::new(ErrorKind::Internal, "Taproot Internal Key")),
+        },
+        None => TR_UNSPENDABLE_KEY.to_string(),
     };
+
+    let mut leaves: Vec<(f64, TapTree<String>)> = vec![];
+    for (weight, branch) in branches {
+        let leaf: Miniscript<String, Tap> = match branch.compile() {
+            Ok(result) => result,
+            Err(e) => return Err(unfit(&ScriptType::TR, &e.to_string())),
+        };
+        leaves.push((weight, TapTree::Leaf(Arc::new(leaf))));
+    }
+    while leaves.len() > 1 {
+        leaves.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        let (right_weight, right) = leaves.pop().unwrap();
+        let (left_weight, left) = leaves.pop().unwrap();
+        leaves.push((
+            left_weight + right_weight,
+            TapTree::Tree(Arc::new(left), Arc::new(right)),
+        ));
+    }
+
+    match Descriptor::new_tr(internal_key, leaves.pop().map(|(_, tree)| tree)) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(unfit(&ScriptType::TR, &e.to_string())),
+    }
+}
+
+/// Compiles a policy into a descriptor of the given script type.
+/// Only the miniscript context required by the script type is compiled.
+fn compile_descriptor(
+    x_policy: Concrete<String>,
+    script_type: ScriptType,
+) -> Result<Descriptor<String>, S5Error> {
     let descriptor = match script_type {
-        ScriptType::WPKH => policy.replace("pk(", "wpkh("),
-        ScriptType::SH => Descriptor::new_sh(legacy_policy).unwrap().to_string(),
-        ScriptType::WSH => Descriptor::new_wsh(segwit_policy).unwrap().to_string(),
-        ScriptType::SHWSH => Descriptor::new_sh_wsh(segwit_policy).unwrap().to_string(),
-        ScriptType::TR => policy.replace("pk(", "tr("),
+        ScriptType::WPKH => match x_policy {
+            Concrete::Key(key) => Descriptor::new_wpkh(key),
+            _ => return Err(unfit(&script_type, "Requires A Single Key")),
+        },
+        ScriptType::SH => {
+            let legacy_policy: Miniscript<String, Legacy> = match x_policy.compile() {
+                Ok(result) => result,
+                Err(e) => return Err(unfit(&script_type, &e.to_string())),
+            };
+            Descriptor::new_sh(legacy_policy)
+        }
+        ScriptType::WSH | ScriptType::SHWSH => {
+            let segwit_policy: Miniscript<String, Segwitv0> = match x_policy.compile() {
+                Ok(result) => result,
+                Err(e) => return Err(unfit(&script_type, &e.to_string())),
+            };
+            if script_type == ScriptType::WSH {
+                Descriptor::new_wsh(segwit_policy)
+            } else {
+                Descriptor::new_sh_wsh(segwit_policy)
+            }
+        }
+        ScriptType::TR => return compile_tr(x_policy),
     };
-    Ok(descriptor.split('#').collect::<Vec<&str>>()[0].to_string())
-}
-
-// pub fn _decode(config: WalletConfig) -> Result<Policy, S5Error> {
-//   let wallet = match Wallet::new_offline(
-//     &config.deposit_desc,
-//     Some(&config.change_desc),
-//     config.network,
-//     MemoryDatabase::default(),
-//   ) {
-//     Ok(result) => result,
-//     Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
-//   };
-
-//   let external_policies = wallet.policies(KeychainKind::External).unwrap().unwrap();
-//   println!("{:#?}", external_policies);
-//   println!(
-//     "The policy with id {} requires the following conditions to be satisfied.",
-//     external_policies.id
-//   );
-
-//   match external_policies.clone().satisfaction {
-//     Satisfaction::Partial {
-//       n,
-//       m,
-//       items,
-//       sorted,
-//       conditions,
-//     } => {
-//       println!("{}/{} conditions need to be satisfied.", m, n);
-//     }
-//     Satisfaction::PartialComplete {
-//       n,
-//       m,
-//       items,
-//       sorted,
-//       conditions,
-//     } => {
-//       println!("{}/{} conditions need to be satisfied.", m, n);
-//     }
-//     Satisfaction::Complete { condition } => {
-//       println!("{:#?} conditions need to be satisfied.", condition);
-//     }
-//     _ => {
-//       println!("No conditions need to be satisfied :o Free coinsh??");
-//     }
-//   };
-
-//   let mut path = BTreeMap::new();
-//   path.insert(external_policies.item.id(), vec![0]);
-//   let conditions = external_policies.get_condition(&path);
-//   println!("is_leaf: {:#?}", external_policies.item.is_leaf());
-
-//   match &external_policies.item {
-
-//     SatisfiableItem::Thresh { items, threshold } => {
-//       for item in items {
-//         match &item.item {
-//           SatisfiableItem::Signature(pkorf) => {
-//             println!("is_leaf: {:#?}", item.item.is_leaf());
-//             println!("{:#?}, id: {:#?}", format!("{:?}", pkorf), item.item.id());
-//           }
-//           SatisfiableItem::Thresh { items, threshold } => {
-//             for item in items {
-//               match &item.item {
-//                 SatisfiableItem::Signature(pkorf) => {
-//                   println!("is_leaf: {:#?}", item.item.is_leaf());
-//                   println!("{:#?}, id: {:#?}", format!("{:?}", pkorf), item.item.id());
-//                 }
-//                 _ => {
-//                   println!("NOT A SIGNATURE POLICY: {:#?}", item.item.id());
-//                 }
-//               }
-//             }
-//           }
-//           _ => {
-//             println!("NOT A SIGNATURE POLICY: {:#?}", item.item.id());
-//           }
-//         }
-//       }
-//     }
-//     SatisfiableItem::Multisig { keys, threshold } => {
-//     }
-//     SatisfiableItem::AbsoluteTimelock { value } => {}
-//     SatisfiableItem::RelativeTimelock { value } => {}
-//     _ => {}
-//   };
-//   Ok(external_policies)
-// }
+    match descriptor {
+        Ok(result) => Ok(result),
+        Err(e) => Err(unfit(&script_type, &e.to_string())),
+    }
+}
 
-/// Checks wether a wallet needs to specify policy path and returns the root policy node id.
-pub fn id(config: WalletConfig) -> Result<(bool, String), S5Error> {
-    let wallet = match Wallet::new(
+fn parse_policy(policy: &str) -> Result<Concrete<String>, S5Error> {
+    match Concrete::<String>::from_str(&slip132::normalize(policy)) {
+        Ok(result) => Ok(result),
+        Err(_) => Err(S5Error::new(ErrorKind::Input, "Invalid Policy")),
+    }
+}
+
+/// Compiles a policy into a descriptor with checksum.
+/// Errors if the policy cannot be expressed with the given script type, i.e. wpkh only fits a single key.
+pub fn compile(policy: &str, script_type: ScriptType) -> Result<String, S5Error> {
+    let descriptor = compile_descriptor(parse_policy(policy)?, script_type)?;
+    Ok(descriptor::with_checksum(&descriptor.to_string())?.descriptor)
+}
+
+/// Compiles a policy into every script type and recommends the one that fits with the lowest weight.
+/// The weight of an option is its max satisfaction weight plus the weight of its script pubkey,
+/// i.e. the cost of receiving to and then spending from it.
+pub fn recommend(policy: &str) -> Result<ScriptTypeRecommendation, S5Error> {
+    let x_policy = parse_policy(policy)?;
+    let script_types = [
+        ScriptType::WPKH,
+        ScriptType::TR,
+        ScriptType::WSH,
+        ScriptType::SHWSH,
+        ScriptType::SH,
+    ];
+    let mut options: Vec<ScriptTypeFit> = vec![];
+    let mut best: Option<(usize, ScriptType)> = None;
+    for script_type in script_types {
+        let weight = match compile_descriptor(x_policy.clone(), script_type) {
+            Ok(descriptor) => match descriptor.max_satisfaction_weight() {
+                Ok(result) => Ok(result + script_type.script_pubkey_len() * 4),
+                Err(e) => Err(unfit(&script_type, &e.to_string())),
+            },
+            Err(e) => Err(e),
+        };
+        match weight {
+            Ok(weight) => {
+                if best.is_none_or(|(best_weight, _)| weight < best_weight) {
+                    best = Some((weight, script_type));
+                }
+                options.push(ScriptTypeFit {
+                    script_type: script_type.to_string(),
+                    weight: Some(weight),
+                    error: None,
+                });
+            }
+            Err(e) => options.push(ScriptTypeFit {
+                script_type: script_type.to_string(),
+                weight: None,
+                error: Some(e.message),
+            }),
+        }
+    }
+    match best {
+        Some((_, script_type)) => Ok(ScriptTypeRecommendation {
+            script_type: script_type.to_string(),
+            options,
+        }),
+        None => Err(S5Error::new(
+            ErrorKind::Input,
+            "Policy Does Not Fit Any Script Type",
+        )),
+    }
+}
+
+/// Loads a wallet with its signers so that policies report which keys can contribute.
+fn policy_wallet(config: &WalletConfig) -> Result<Wallet<MemoryDatabase>, S5Error> {
+    match Wallet::new(
         &config.deposit_desc,
         Some(&config.change_desc),
         config.network,
         MemoryDatabase::default(),
     ) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    }
+}
+
+fn keychain_policy(
+    wallet: &Wallet<MemoryDatabase>,
+    keychain: KeychainKind,
+) -> Result<Option<KeychainPolicy>, S5Error> {
+    match wallet.policies(keychain) {
+        Ok(Some(policy)) => Ok(Some(KeychainPolicy {
+            id: policy.id.clone(),
+            requires_path: policy.requires_path(),
+            policy,
+        })),
+        Ok(None) => Ok(None),
+        Err(e) => Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    }
+}
+
+/// Gets the policy tree of both keychains.
+/// Each node has an id, its satisfiable item (signature, thresh, multisig, timelock...),
+/// the satisfaction of the tree by signatures and the contribution of the keys held by this wallet.
+/// Node ids and child indexes are used to build a policy path.
+pub fn tree(config: WalletConfig) -> Result<PolicyTree, S5Error> {
+    let wallet = policy_wallet(&config)?;
+    Ok(PolicyTree {
+        external: keychain_policy(&wallet, KeychainKind::External)?,
+        internal: keychain_policy(&wallet, KeychainKind::Internal)?,
+    })
+}
+
+/// Maps the keys of a descriptor, derived at the indexes found in the key origins of a psbt input, to their fingerprints.
+/// Keys of a ranged descriptor cannot be derived without an index, so an input without any key origin only maps single keys.
+fn input_keys(
+    parsed: &Descriptor<DescriptorPublicKey>,
+    input: &Input,
+    secp: &Secp256k1<All>,
+) -> BTreeMap<PublicKey, String> {
+    let mut indexes: Vec<u32> = input
+        .bip32_derivation
+        .values()
+        .map(|(_, path)| path)
+        .chain(input.tap_key_origins.values().map(|(_, (_, path))| path))
+        .filter_map(|path| match path.as_ref().last() {
+            Some(ChildNumber::Normal { index }) => Some(*index),
+            _ => None,
+        })
+        .collect();
+    if indexes.is_empty() {
+        indexes.push(0);
+    }
+    indexes.sort_unstable();
+    indexes.dedup();
+    let mut keys = BTreeMap::new();
+    for index in indexes {
+        parsed.derive(index).for_each_key(|key: ForEach<DescriptorPublicKey>| {
+            if let Ok(public_key) = key.as_key().derive_public_key(secp) {
+                keys.insert(public_key, key.as_key().master_fingerprint().to_string());
+            }
+            true
+        });
+    }
+    keys
+}
+
+/// Gets the fingerprints of the keys that have signed a psbt input.
+/// Signatures by keys without an origin in the psbt are attributed through the descriptor keys, or skipped.
+fn signed_fingerprints(input: &Input, descriptor_keys: &BTreeMap<PublicKey, String>) -> Vec<String> {
+    let mut signed: Vec<String> = vec![];
+    for pubkey in input.partial_sigs.keys() {
+        match input.bip32_derivation.get(&pubkey.inner) {
+            Some((fingerprint, _)) => signed.push(fingerprint.to_string()),
+            None => signed.extend(descriptor_keys.get(pubkey).cloned()),
+        }
+    }
+    let mut tap_signers: Vec<_> = input.tap_script_sigs.keys().map(|(key, _)| *key).collect();
+    if input.tap_key_sig.is_some() {
+        tap_signers.extend(input.tap_internal_key);
+    }
+    for key in tap_signers {
+        match input.tap_key_origins.get(&key) {
+            Some((_, (fingerprint, _))) => signed.push(fingerprint.to_string()),
+            None => signed.extend(
+                descriptor_keys
+                    .iter()
+                    .find(|(public_key, _)| XOnlyPublicKey::from(public_key.inner) == key)
+                    .map(|(_, fingerprint)| fingerprint.clone()),
+            ),
+        }
+    }
+    signed.sort();
+    signed.dedup();
+    signed
+}
+
+/// Collects the timelocks of a policy tree with their readiness at the current height.
+/// Relative timelocks count from the input confirmation height (current height if unconfirmed).
+/// Time based timelocks cannot be checked against a height and are never reported ready.
+fn timelock_status(
+    policy: &Policy,
+    current_height: u32,
+    input_height: u32,
+    timelocks: &mut Vec<TimelockStatus>,
+) {
+    let status = |kind: &str, value: u32, target: Option<u32>| TimelockStatus {
+        kind: kind.to_string(),
+        value,
+        is_ready: target.is_some_and(|target| current_height >= target),
+        blocks_remaining: target.map(|target| target.saturating_sub(current_height)),
+    };
+    match &policy.item {
+        SatisfiableItem::AbsoluteTimelock { value } => {
+            let target = if *value < 500_000_000 { Some(*value) } else { None };
+            timelocks.push(status("after", *value, target));
+        }
+        SatisfiableItem::RelativeTimelock { value } => {
+            let target = if value & (1 << 22) == 0 {
+                Some(input_height + (value & 0xffff))
+            } else {
+                None
+            };
+            timelocks.push(status("older", *value, target));
+        }
+        SatisfiableItem::Thresh { items, .. } => {
+            for item in items {
+                timelock_status(item, current_height, input_height, timelocks);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Checks how far each input of a psbt is from satisfying the descriptor policy.
+/// input_heights are the confirmation heights of the spent outputs, unconfirmed if missing.
+pub fn satisfaction(
+    descriptor: &str,
+    psbt: &str,
+    current_height: u32,
+    input_heights: &BTreeMap<String, u32>,
+) -> Result<PsbtSatisfaction, S5Error> {
+    let secp = Secp256k1::new();
+    let (parsed, keymap) = match Descriptor::parse_descriptor(&secp, descriptor) {
         Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    };
+    let psbt = match PartiallySignedTransaction::from_str(psbt) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid PSBT")),
+    };
+    let mut fingerprints: Vec<String> = vec![];
+    parsed.for_each_key(|key: ForEach<DescriptorPublicKey>| {
+        fingerprints.push(key.as_key().master_fingerprint().to_string());
+        true
+    });
+    fingerprints.sort();
+    fingerprints.dedup();
+    let signers = SignersContainer::build(keymap, &parsed, &secp);
+
+    let mut inputs: Vec<InputSatisfaction> = vec![];
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        let outpoint = psbt.unsigned_tx.input[index].previous_output.to_string();
+        let input_height = match input_heights.get(&outpoint) {
+            Some(height) => *height,
+            None => current_height,
+        };
+        let mut single_input = psbt.clone();
+        single_input.unsigned_tx.input = vec![psbt.unsigned_tx.input[index].clone()];
+        single_input.inputs = vec![input.clone()];
+        let policy = match parsed.extract_policy(
+            &signers,
+            BuildSatisfaction::PsbtTimelocks {
+                psbt: &single_input,
+                current_height,
+                input_max_height: input_height,
+            },
+            &secp,
+        ) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+        };
+
+        let is_finalized = input.final_script_witness.is_some() || input.final_script_sig.is_some();
+        let signed = signed_fingerprints(input, &input_keys(&parsed, input, &secp));
+        let mut timelocks = vec![];
+        if let Some(policy) = &policy {
+            timelock_status(policy, current_height, input_height, &mut timelocks);
+        }
+        let is_satisfied = is_finalized
+            || policy.as_ref().is_some_and(|policy| {
+                matches!(
+                    policy.satisfaction,
+                    Satisfaction::Complete { .. } | Satisfaction::PartialComplete { .. }
+                )
+            });
+        // once the policy is satisfied, no more signatures are needed
+        let unsigned = if is_satisfied {
+            vec![]
+        } else {
+            fingerprints
+                .iter()
+                .filter(|fingerprint| !signed.contains(fingerprint))
+                .cloned()
+                .collect()
+        };
+        inputs.push(InputSatisfaction {
+            outpoint,
+            signed,
+            unsigned,
+            timelocks,
+            is_satisfied,
+            is_finalized,
+            policy,
+        });
+    }
+
+    Ok(PsbtSatisfaction {
+        height: current_height,
+        is_satisfied: inputs.iter().all(|input| input.is_satisfied),
+        inputs,
+    })
+}
+
+/// Syncs the wallet to find input confirmation heights and checks the psbt satisfaction at the current height.
+pub fn sync_satisfaction(config: WalletConfig, psbt: &str) -> Result<PsbtSatisfaction, S5Error> {
+    let client = match config.client {
+        Some(ref client) => client,
+        None => return Err(S5Error::new(ErrorKind::Input, "Satisfaction Requires A Node")),
+    };
+    let wallet = policy_wallet(&config)?;
+    match wallet.sync(client, SyncOptions::default()) {
+        Ok(_) => (),
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Sync")),
+    };
+    let mut input_heights = BTreeMap::new();
+    match wallet.list_unspent() {
+        Ok(utxos) => {
+            for utxo in utxos {
+                if let Ok(Some(tx)) = wallet.get_tx(&utxo.outpoint.txid, false) {
+                    if let Some(confirmation) = tx.confirmation_time {
+                        input_heights.insert(utxo.outpoint.to_string(), confirmation.height);
+                    }
+                }
+            }
+        }
         Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
     };
+    let descriptor = config.deposit_desc.clone();
+    let current_height = height::get_height(config)?.height;
+    satisfaction(&descriptor, psbt, current_height, &input_heights)
+}
 
-    let external_policies = wallet.policies(KeychainKind::External).unwrap().unwrap();
-    Ok((external_policies.requires_path(), external_policies.id))
+/// Checks wether a wallet needs to specify policy path and returns the root policy node id.
+pub fn id(config: WalletConfig) -> Result<(bool, String), S5Error> {
+    let wallet = policy_wallet(&config)?;
+    match keychain_policy(&wallet, KeychainKind::External)? {
+        Some(external) => Ok((external.requires_path, external.id)),
+        None => Err(S5Error::new(ErrorKind::Wallet, "Descriptor Has No Policy")),
+    }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::{WalletConfig, DEFAULT_TESTNET_NODE};
     use crate::wallet::address::generate;
+    use crate::wallet::fixtures::funded_wallet;
+    use bdk::wallet::AddressIndex;
+    use bdk::SignOptions;
+    use bitcoin::util::bip32::ExtendedPrivKey;
+    use bitcoin::Network;
     // use bdk::descriptor::policy::BuildSatisfaction;
     // use bdk::descriptor::ExtractPolicy;
     // use bitcoin::secp256k1::Secp256k1;
@@ -219,12 +673,12 @@ mod tests {
         );
         //  DESCRIPTORS
         let raft_result_bech32 = compile(&raft_policy, ScriptType::WSH).unwrap();
-        let expected_raft_wsh = "wsh(or_d(pk([db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*),and_v(v:pk([66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*),after(595600))))";
+        let expected_raft_wsh = "wsh(or_d(pk([db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*),and_v(v:pk([66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*),after(595600))))#gweqcnwj";
         let single_result_bech32 = compile(&single_policy, ScriptType::WPKH).unwrap();
-        let expected_single_wpkh = "wpkh([db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*)";
+        let expected_single_wpkh = "wpkh([db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*)#pr5ru6cj";
 
         let escrow_result = compile(&escrow_policy, ScriptType::WSH).unwrap();
-        let expected_escrow_wsh = "wsh(multi(2,[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*,[a90a3a81/84'/0'/0']tprv8g3FKkLE9gRHDYeedikuNRXMhZyQ6bsgnMxYk8dRPKg15BCsimrbw2zjA97gwu4Brw9XtVVdgyuUSSZd7ckjSbbwpGjAyVjonCXGKg2gE2D/*,[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*))";
+        let expected_escrow_wsh = "wsh(multi(2,[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*,[a90a3a81/84'/0'/0']tprv8g3FKkLE9gRHDYeedikuNRXMhZyQ6bsgnMxYk8dRPKg15BCsimrbw2zjA97gwu4Brw9XtVVdgyuUSSZd7ckjSbbwpGjAyVjonCXGKg2gE2D/*,[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*))#lt4dlc9y";
         assert_eq!(&raft_result_bech32, expected_raft_wsh);
         assert_eq!(&single_result_bech32, expected_single_wpkh);
         assert_eq!(&escrow_result, expected_escrow_wsh);
@@ -244,12 +698,176 @@ mod tests {
         assert!(!escrow_id.0);
     }
 
+    #[test]
+    fn test_policy_fit() {
+        let alice_xpub = "[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*";
+        let bob_xpub = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe/*";
+        let multi_policy = format!("thresh(2,pk({}),pk({}))", alice_xpub, bob_xpub);
+
+        assert_eq!(
+            compile(&multi_policy, ScriptType::WPKH).err().unwrap().message,
+            "Policy Does Not Fit wpkh: Requires A Single Key"
+        );
+        assert_eq!(
+            compile("pk(alice", ScriptType::WSH).err().unwrap().message,
+            "Invalid Policy"
+        );
+        assert!(compile(&multi_policy, ScriptType::SH).is_ok());
+
+        // 21 keys exceed the legacy multisig limit but still fit segwit
+        let keys: Vec<String> = (0..21).map(|i| format!("pk(K{})", i)).collect();
+        let large_policy = format!("thresh(21,{})", keys.join(","));
+        assert!(compile(&large_policy, ScriptType::WSH).is_ok());
+        assert!(compile(&large_policy, ScriptType::SH)
+            .err()
+            .unwrap()
+            .message
+            .starts_with("Policy Does Not Fit sh: "));
+
+        let single = recommend(&format!("pk({})", alice_xpub)).unwrap();
+        assert_eq!(single.script_type, "wpkh");
+        assert_eq!(single.options.len(), 5);
+        assert!(single.options.iter().all(|option| option.weight.is_some()));
+
+        let multi = recommend(&multi_policy).unwrap();
+        assert_eq!(multi.options[0].script_type, "wpkh");
+        assert!(multi.options[0].weight.is_none());
+        assert_eq!(multi.script_type, "wsh");
+
+        assert_eq!(recommend(&large_policy).unwrap().options[4].weight, None);
+    }
+
+    #[test]
+    fn test_policy_tree() {
+        let alice_xprv = "[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*";
+        let escrow_xpub = "[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*";
+        let raft_policy = format!(
+            "or(pk({}),and(pk({}),after({})))",
+            alice_xprv, escrow_xpub, 595_600
+        );
+        let raft_descriptor = compile(&raft_policy, ScriptType::WSH).unwrap();
+        let config = WalletConfig::new_offline(&raft_descriptor, None).unwrap();
+        let tree = tree(config).unwrap();
+
+        let external = tree.external.unwrap();
+        assert_eq!(external.id, "hgl9rs6e");
+        assert!(external.requires_path);
+        let json = serde_json::to_value(&external.policy).unwrap();
+        assert_eq!(json["type"], "THRESH");
+        assert_eq!(json["threshold"], 1);
+        assert_eq!(json["items"][1]["items"][1]["type"], "ABSOLUTETIMELOCK");
+        assert_eq!(json["items"][1]["items"][1]["value"], 595_600);
+        assert!(tree.internal.is_some());
+    }
+
+    #[test]
+    fn test_psbt_satisfaction() {
+        let secp = Secp256k1::new();
+        let alice_xprv = "[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*";
+        let alice_xpub = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe/*";
+        let bob_tprv = "tprv8g3FKkLE9gRHDYeedikuNRXMhZyQ6bsgnMxYk8dRPKg15BCsimrbw2zjA97gwu4Brw9XtVVdgyuUSSZd7ckjSbbwpGjAyVjonCXGKg2gE2D";
+        let bob_tpub = ExtendedPubKey::from_priv(&secp, &ExtendedPrivKey::from_str(bob_tprv).unwrap());
+        let bob_xprv = format!("[a90a3a81/84'/0'/0']{}/*", bob_tprv);
+        let bob_xpub = format!("[a90a3a81/84'/0'/0']{}/*", bob_tpub);
+        let escrow_xpub = "[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*";
+        let policy = |alice: &str, bob: &str| {
+            format!(
+                "or(thresh(2,pk({}),pk({})),and(pk({}),after(200)))",
+                alice, bob, escrow_xpub
+            )
+        };
+        let public = compile(&policy(alice_xpub, &bob_xpub), ScriptType::WSH).unwrap();
+        let alice = compile(&policy(alice_xprv, &bob_xpub), ScriptType::WSH).unwrap();
+        let bob = compile(&policy(alice_xpub, &bob_xprv), ScriptType::WSH).unwrap();
+
+        let wallet = funded_wallet(&public, None);
+        let address = wallet.get_address(AddressIndex::Peek(0)).unwrap();
+        let root = tree(WalletConfig::new_offline(&public, None).unwrap())
+            .unwrap()
+            .external
+            .unwrap()
+            .id;
+        let mut path = BTreeMap::new();
+        path.insert(root, vec![0]);
+        let (mut psbt, _) = {
+            let mut builder = wallet.build_tx();
+            builder
+                .drain_wallet()
+                .drain_to(address.script_pubkey())
+                .policy_path(path, KeychainKind::External);
+            builder.finish().unwrap()
+        };
+        let outpoint = psbt.unsigned_tx.input[0].previous_output.to_string();
+        let mut input_heights = BTreeMap::new();
+        input_heights.insert(outpoint.clone(), 100);
+
+        let status = satisfaction(&public, &psbt.to_string(), 150, &input_heights).unwrap();
+        assert_eq!(status.height, 150);
+        assert!(!status.is_satisfied);
+        let input = &status.inputs[0];
+        assert_eq!(input.outpoint, outpoint);
+        assert!(input.signed.is_empty());
+        assert_eq!(input.unsigned, vec!["66a0c105", "a90a3a81", "db7d25b5"]);
+        assert_eq!(
+            input.timelocks,
+            vec![TimelockStatus {
+                kind: "after".to_string(),
+                value: 200,
+                is_ready: false,
+                blocks_remaining: Some(50),
+            }]
+        );
+        let status = satisfaction(&public, &psbt.to_string(), 250, &input_heights).unwrap();
+        assert!(status.inputs[0].timelocks[0].is_ready);
+        assert_eq!(status.inputs[0].timelocks[0].blocks_remaining, Some(0));
+
+        let alice_wallet =
+            Wallet::new(&alice, None, Network::Testnet, MemoryDatabase::default()).unwrap();
+        alice_wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+        let status = satisfaction(&public, &psbt.to_string(), 150, &input_heights).unwrap();
+        assert!(!status.is_satisfied);
+        assert_eq!(status.inputs[0].signed, vec!["db7d25b5"]);
+        assert_eq!(status.inputs[0].unsigned, vec!["66a0c105", "a90a3a81"]);
+
+        let bob_wallet = Wallet::new(&bob, None, Network::Testnet, MemoryDatabase::default()).unwrap();
+        let sign_options = SignOptions {
+            try_finalize: false,
+            ..Default::default()
+        };
+        bob_wallet.sign(&mut psbt, sign_options).unwrap();
+        let status = satisfaction(&public, &psbt.to_string(), 150, &input_heights).unwrap();
+        assert!(status.is_satisfied);
+        assert!(!status.inputs[0].is_finalized);
+        assert_eq!(status.inputs[0].signed, vec!["a90a3a81", "db7d25b5"]);
+        assert!(status.inputs[0].unsigned.is_empty());
+
+        // a signer that leaves out its key origin is attributed through the descriptor keys
+        let mut stripped = psbt.clone();
+        let bob_key = *stripped.inputs[0]
+            .bip32_derivation
+            .iter()
+            .find(|(_, (fingerprint, _))| fingerprint.to_string() == "a90a3a81")
+            .unwrap()
+            .0;
+        stripped.inputs[0].bip32_derivation.remove(&bob_key);
+        let status = satisfaction(&public, &stripped.to_string(), 150, &input_heights).unwrap();
+        assert_eq!(status.inputs[0].signed, vec!["a90a3a81", "db7d25b5"]);
+
+        assert_eq!(
+            satisfaction(&public, "notapsbt", 150, &input_heights)
+                .err()
+                .unwrap()
+                .message,
+            "Invalid PSBT"
+        );
+    }
+
     #[test]
     fn test_taproot_policy() {
         let alice_xprv = "[db7d25b5/86'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*";
         let single_policy = format!("pk({})", alice_xprv);
         let single_result_taproot = compile(&single_policy, ScriptType::TR).unwrap();
-        let expected_single_tr = "tr([db7d25b5/86'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*)";
+        let expected_single_tr = "tr([db7d25b5/86'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*)#fk2xq5wu";
         assert_eq!(&single_result_taproot, expected_single_tr);
         let taproot_config: WalletConfig =
             WalletConfig::new(&single_result_taproot, DEFAULT_TESTNET_NODE, None,None).unwrap();
@@ -259,6 +877,53 @@ mod tests {
             "tb1pyky6jtr8amxr726he4qejpcdrq9yh86kq3vqjvmfguw8ty6hwf8s5y0zdj"
         );
     }
+
+    #[test]
+    fn test_taproot_tree_policy() {
+        let alice_xpub = "[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*";
+        let bob_xpub = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe/*";
+        let bob_xprv = "[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*";
+
+        let timelocked = compile(
+            &format!("or(pk({}),and(pk({}),older(10)))", alice_xpub, bob_xpub),
+            ScriptType::TR,
+        )
+        .unwrap();
+        assert!(timelocked.starts_with(&format!(
+            "tr({},and_v(v:pk({}),older(10)))#",
+            alice_xpub, bob_xpub
+        )));
+
+        let no_key_path = compile(
+            &format!("and(pk({}),pk({}))", alice_xpub, bob_xpub),
+            ScriptType::TR,
+        )
+        .unwrap();
+        assert!(no_key_path.starts_with(&format!("tr({},", TR_UNSPENDABLE_KEY)));
+        let config = WalletConfig::new_offline(&no_key_path, None).unwrap();
+        assert!(generate(config, 0).unwrap().address.starts_with("tb1p"));
+
+        // bob can only spend through the script path
+        let descriptor = compile(
+            &format!("or(pk({}),pk({}))", alice_xpub, bob_xprv),
+            ScriptType::TR,
+        )
+        .unwrap();
+        assert!(descriptor.starts_with(&format!("tr({},pk({}))#", alice_xpub, bob_xprv)));
+
+        let wallet = funded_wallet(&descriptor, None);
+        let address = wallet.get_address(AddressIndex::Peek(0)).unwrap();
+
+        let (mut psbt, _) = {
+            let mut builder = wallet.build_tx();
+            builder.drain_wallet().drain_to(address.script_pubkey());
+            builder.finish().unwrap()
+        };
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        assert_eq!(psbt.inputs[0].tap_script_sigs.len(), 1);
+        assert!(psbt.inputs[0].tap_key_sig.is_none());
+    }
+
     use bdk::descriptor;
     use bdk::keys::DerivableKey;
     use bdk::keys::{DescriptorKey, ExtendedKey};
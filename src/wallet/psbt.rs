@@ -3,20 +3,22 @@ use std::collections::BTreeMap;
 use std::ffi::CString;
 use std::os::raw::c_char;
 use std::str::FromStr;
-use bdk::database::{MemoryDatabase, SqliteDatabase};
+use bdk::database::{BatchDatabase, MemoryDatabase, SqliteDatabase};
 use bdk::descriptor::Descriptor;
 use bdk::miniscript::DescriptorTrait;
 use bdk::blockchain::Blockchain;
 use bdk::Error;
 use bdk::{KeychainKind, SignOptions,SyncOptions, Wallet};
 use bitcoin::base64;
-use bitcoin::blockdata::transaction::Transaction;
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, Transaction};
 use bitcoin::consensus::deserialize;
 use bitcoin::network::constants::Network;
 use bitcoin::util::address::Address;
 use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
-use crate::config::WalletConfig;
+use crate::config::{split_descriptor, WalletConfig};
 use crate::e::{ErrorKind, S5Error};
+use crate::wallet::amount;
 use bdk::bitcoin::Txid;
 /// FFI Output
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -42,12 +44,16 @@ impl WalletPSBT {
 pub struct TxOutput {
   pub address: String,
   pub amount: Option<u64>,
+  #[serde(default)]
+  pub percent: Option<f64>,
 }
 
 pub type TxOutputs = Vec<TxOutput>;
 
 impl TxOutput {
   /// outputs as a str is address:amount,address:amount,address:amount
+  /// amount is in sats unless followed by a unit i.e. 0.001btc, 1000bits, 100000sats
+  /// when sweeping, amount can be a percentage of the swept funds i.e. address:40%
   pub fn vec_from_str(str: &str) -> Result<TxOutputs, S5Error> {
     let mut outputs: Vec<TxOutput> = Vec::new();
     for output in str.split(",") {
@@ -57,20 +63,38 @@ impl TxOutput {
         None => return Err(S5Error::new(ErrorKind::Input, "Invalid tx outputs string")),
       };
       let amount = match output_split.next() {
-        Some(amount) => match amount.parse::<u64>() {
-          Ok(amount) => amount,
-          Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid tx amount")),
-        },
+        Some(amount) => amount.trim(),
         None => return Err(S5Error::new(ErrorKind::Input, "Invalid tx outputs string")),
       };
+      if let Some(percent) = amount.strip_suffix('%') {
+        let percent = match percent.trim().parse::<f64>() {
+          Ok(percent) => percent,
+          Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid tx amount")),
+        };
+        outputs.push(TxOutput {
+          address: address.to_string(),
+          amount: None,
+          percent: Some(percent),
+        });
+        continue;
+      }
+      let amount = match amount::parse(amount) {
+        Ok(amount) => amount,
+        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid tx amount")),
+      };
 
       outputs.push(TxOutput {
         address: address.to_string(),
         amount: Some(amount),
+        percent: None,
       });
     }
     Ok(outputs)
   }
+  /// Outputs without an amount or percentage receive the remainder of a sweep.
+  pub fn is_drain(&self) -> bool {
+    self.percent.is_none() && self.amount.unwrap_or(0) == 0
+  }
   pub fn _vec_from_json_str(str: &str) -> Result<TxOutputs, S5Error> {
     let outputs: TxOutputs = match serde_json::from_str(str) {
       Ok(result) => result,
@@ -80,6 +104,18 @@ impl TxOutput {
   }
 }
 
+/// utxos as a str is txid:vout,txid:vout
+pub fn outpoints_from_str(str: &str) -> Result<Vec<OutPoint>, S5Error> {
+  str
+    .split(",")
+    .filter(|outpoint| !outpoint.trim().is_empty())
+    .map(|outpoint| match OutPoint::from_str(outpoint.trim()) {
+      Ok(result) => Ok(result),
+      Err(_) => Err(S5Error::new(ErrorKind::Input, "Invalid Utxo")),
+    })
+    .collect()
+}
+
 #[derive(Deserialize,Clone)]
 pub struct PolicyPath{
   pub id: String,
@@ -92,12 +128,51 @@ impl PolicyPath{
     map.insert(self.id.clone(), self.path.clone());
     map
   }
-  pub fn from_json_str(str: &str) -> Result<PolicyPath, S5Error> {
-    let policy_path: PolicyPath = match serde_json::from_str(str) {
+}
+
+/// Policy paths to spend with, per keychain.
+/// Each maps policy node ids to the indexes of the items to satisfy at that node.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct KeychainPolicyPath {
+  #[serde(default)]
+  pub external: BTreeMap<String, Vec<usize>>,
+  #[serde(default)]
+  pub internal: BTreeMap<String, Vec<usize>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PolicyPathInput {
+  Single(PolicyPath),
+  Keychains {
+    external: BTreeMap<String, Vec<usize>>,
+    internal: Option<BTreeMap<String, Vec<usize>>>,
+  },
+  Nodes(BTreeMap<String, Vec<usize>>),
+}
+
+impl KeychainPolicyPath {
+  /// Uses the same node paths for both keychains.
+  pub fn both(path: BTreeMap<String, Vec<usize>>) -> KeychainPolicyPath {
+    KeychainPolicyPath {
+      external: path.clone(),
+      internal: path,
+    }
+  }
+  /// Parses either a single node {"id":..,"path":[..]}, a map of node ids {"id":[..],"id":[..]}
+  /// applied to both keychains, or {"external":{"id":[..]},"internal":{"id":[..]}}.
+  /// If internal is not given, the external paths are used for both keychains.
+  pub fn from_json_str(str: &str) -> Result<KeychainPolicyPath, S5Error> {
+    let input: PolicyPathInput = match serde_json::from_str(str) {
       Ok(result) => result,
       Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid policy path.")),
     };
-    Ok(policy_path)
+    Ok(match input {
+      PolicyPathInput::Single(path) => KeychainPolicyPath::both(path.to_btreemap()),
+      PolicyPathInput::Keychains { external, internal: Some(internal) } => KeychainPolicyPath { external, internal },
+      PolicyPathInput::Keychains { external, internal: None } => KeychainPolicyPath::both(external),
+      PolicyPathInput::Nodes(path) => KeychainPolicyPath::both(path),
+    })
   }
 }
 
@@ -105,8 +180,9 @@ pub fn build(
   config: WalletConfig,
   outputs: Vec<TxOutput>,
   fee_absolute: u64,
-  policy_path: Option<BTreeMap<String, Vec<usize>>>,
+  policy_path: Option<KeychainPolicyPath>,
   sweep: bool,
+  utxos: Option<Vec<OutPoint>>,
 ) -> Result<WalletPSBT, S5Error> {
   
   let wallet = match Wallet::new(
@@ -122,71 +198,21 @@ pub fn build(
     Ok(_) => (),
     Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Sync")),
   };
+  let balance = selected_balance(&wallet, &utxos)?;
+  if let Some(e) = amount::validate(config.network, &outputs, fee_absolute, balance, sweep).to_error() {
+    return Err(e);
+  }
 
-  let outputs = match outputs
-    .iter()
-    .map(|output| {
-      let address = match Address::from_str(&output.address) {
-        Ok(result) => result,
-        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Address")),
-      };
-      let amount = match output.amount {
-        Some(result) => result,
-        None => return Err(S5Error::new(ErrorKind::Input, "Invalid Amount")),
-      };
-      Ok((address, amount))
-    })
-    .collect::<Result<Vec<(Address, u64)>, S5Error>>()
-  {
-    Ok(result) => result,
-    Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Output Set")),
-  };
-
-  let (psbt, _) = {
-    let mut builder = wallet.build_tx();
-    
-    if sweep {
-      builder
-        .drain_wallet()
-        .drain_to(outputs[0].0.script_pubkey());
-    } else {
-      outputs.iter().for_each(|(address, amount)| {
-        builder.add_recipient(address.script_pubkey(), *amount);
-      });
-    }
-    if policy_path.is_some() {
-      builder.policy_path(policy_path.clone().unwrap(), KeychainKind::External);
-      builder.policy_path(policy_path.unwrap(), KeychainKind::Internal);
-    }
-
-    builder.enable_rbf();
-    builder.fee_absolute(fee_absolute);
-    match builder.finish() {
-      Ok(result) => result,
-      Err(e) => {
-        println!("{:?}", e);
-        return match e {
-          Error::SpendingPolicyRequired(_) => {
-            Err(S5Error::new(ErrorKind::Input, "Spending Policy Required"))
-          }
-          e=> Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
-        };
-      }
-    }
-  };
-
-  Ok(WalletPSBT {
-    psbt: psbt.to_string(),
-    is_finalized: false,
-  })
+  create_psbt(&wallet, &outputs, fee_absolute, balance, policy_path, sweep, utxos)
 }
 
 pub fn sqlite_build(
   config: WalletConfig,
   outputs: Vec<TxOutput>,
   fee_absolute: u64,
-  policy_path: Option<BTreeMap<String, Vec<usize>>>,
+  policy_path: Option<KeychainPolicyPath>,
   sweep: bool,
+  utxos: Option<Vec<OutPoint>>,
 ) -> Result<WalletPSBT, S5Error> {
   if config.db_path.is_none(){
     return Err(S5Error::new(ErrorKind::Input, "SQLite Requires a Db Path."));
@@ -200,41 +226,154 @@ pub fn sqlite_build(
     Ok(result) => result,
     Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Initialization")),
   };
+  let balance = selected_balance(&wallet, &utxos)?;
+  if let Some(e) = amount::validate(config.network, &outputs, fee_absolute, balance, sweep).to_error() {
+    return Err(e);
+  }
 
-  let outputs = match outputs
-    .iter()
-    .map(|output| {
-      let address = match Address::from_str(&output.address) {
-        Ok(result) => result,
-        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Address")),
-      };
-      let amount = match output.amount {
-        Some(result) => result,
-        None => return Err(S5Error::new(ErrorKind::Input, "Invalid Amount")),
-      };
-      Ok((address, amount))
-    })
-    .collect::<Result<Vec<(Address, u64)>, S5Error>>()
-  {
+  create_psbt(&wallet, &outputs, fee_absolute, balance, policy_path, sweep, utxos)
+}
+
+/// Gets the total value of the selected utxos or the wallet balance if none are selected.
+fn selected_balance<D: BatchDatabase>(
+  wallet: &Wallet<D>,
+  utxos: &Option<Vec<OutPoint>>,
+) -> Result<u64, S5Error> {
+  let unspent = match wallet.list_unspent() {
     Ok(result) => result,
-    Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Output Set")),
+    Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
   };
+  match utxos {
+    Some(outpoints) => outpoints.iter().try_fold(0, |total: u64, outpoint| {
+      match unspent.iter().find(|utxo| utxo.outpoint == *outpoint) {
+        Some(utxo) => Ok(total + utxo.txout.value),
+        None => Err(S5Error::new(ErrorKind::Input, "Unknown Utxo")),
+      }
+    }),
+    None => Ok(unspent.iter().map(|utxo| utxo.txout.value).sum()),
+  }
+}
+
+/// Fixed amount recipients and an optional script to drain the remainder to.
+type OutputPlan = (Vec<(Script, u64)>, Option<Script>);
+
+/// Resolves outputs into fixed recipients and an optional drain script.
+/// A sweep either drains to its single output, sends fixed amounts and drains the remainder
+/// to the one output without an amount, or splits the funds across percentage outputs.
+/// The last percentage output receives the remainder so nothing is lost to rounding.
+/// Every share must be above the dust limit of its output.
+fn plan_outputs(
+  outputs: &[TxOutput],
+  fee_absolute: u64,
+  selected: u64,
+  sweep: bool,
+) -> Result<OutputPlan, S5Error> {
+  let script = |output: &TxOutput| match Address::from_str(&output.address) {
+    Ok(address) => Ok(address.script_pubkey()),
+    Err(_) => Err(S5Error::new(ErrorKind::Input, "Invalid Address")),
+  };
+  if !sweep {
+    let recipients = outputs
+      .iter()
+      .map(|output| match output.amount {
+        Some(amount) => Ok((script(output)?, amount)),
+        None => Err(S5Error::new(ErrorKind::Input, "Invalid Amount")),
+      })
+      .collect::<Result<Vec<(Script, u64)>, S5Error>>()?;
+    return Ok((recipients, None));
+  }
+  if outputs.len() == 1 && outputs[0].percent.is_none() {
+    return Ok((vec![], Some(script(&outputs[0])?)));
+  }
+
+  let mut recipients: Vec<(Script, u64)> = vec![];
+  let mut drains: Vec<Script> = vec![];
+  let mut shares: Vec<(Script, f64)> = vec![];
+  for output in outputs {
+    match output.percent {
+      Some(percent) if percent.is_finite() && percent > 0.0 && percent <= 100.0 => {
+        shares.push((script(output)?, percent))
+      }
+      Some(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid Sweep Percentage")),
+      None if output.is_drain() => drains.push(script(output)?),
+      None => recipients.push((script(output)?, output.amount.unwrap_or(0))),
+    }
+  }
+
+  if shares.is_empty() {
+    return match drains.len() {
+      1 => Ok((recipients, drains.pop())),
+      0 => Err(S5Error::new(ErrorKind::Input, "Sweep Requires A Drain Output")),
+      _ => Err(S5Error::new(ErrorKind::Input, "Sweep Allows A Single Drain Output")),
+    };
+  }
+  if !drains.is_empty() {
+    return Err(S5Error::new(
+      ErrorKind::Input,
+      "Sweep Cannot Mix Drain And Percent Outputs",
+    ));
+  }
+  let total_percent: f64 = shares.iter().map(|(_, percent)| percent).sum();
+  if (total_percent - 100.0).abs() > 1e-6 {
+    return Err(S5Error::new(ErrorKind::Input, "Sweep Percentages Must Total 100"));
+  }
+  let fixed: u64 = recipients.iter().map(|(_, amount)| amount).sum();
+  let remainder = match selected.checked_sub(fixed + fee_absolute) {
+    Some(result) => result,
+    None => return Err(S5Error::new(ErrorKind::Input, "Insufficient Funds")),
+  };
+  let (last, _) = shares.pop().unwrap();
+  let mut shared = 0;
+  for (script_pubkey, percent) in shares {
+    let share = (remainder as f64 * percent / 100.0).floor() as u64;
+    if share < amount::dust_limit(&script_pubkey) {
+      return Err(S5Error::new(ErrorKind::Input, "Sweep Share Below Dust Limit"));
+    }
+    shared += share;
+    recipients.push((script_pubkey, share));
+  }
+  if remainder - shared < amount::dust_limit(&last) {
+    return Err(S5Error::new(ErrorKind::Input, "Sweep Share Below Dust Limit"));
+  }
+  Ok((recipients, Some(last)))
+}
+
+fn create_psbt<D: BatchDatabase>(
+  wallet: &Wallet<D>,
+  outputs: &[TxOutput],
+  fee_absolute: u64,
+  selected: u64,
+  policy_path: Option<KeychainPolicyPath>,
+  sweep: bool,
+  utxos: Option<Vec<OutPoint>>,
+) -> Result<WalletPSBT, S5Error> {
+  let (recipients, drain_to) = plan_outputs(outputs, fee_absolute, selected, sweep)?;
 
   let (psbt, _) = {
     let mut builder = wallet.build_tx();
-    
-    if sweep {
-      builder
-        .drain_wallet()
-        .drain_to(outputs[0].0.script_pubkey());
-    } else {
-      outputs.iter().for_each(|(address, amount)| {
-        builder.add_recipient(address.script_pubkey(), *amount);
-      });
+
+    if let Some(outpoints) = utxos {
+      match builder.add_utxos(&outpoints) {
+        Ok(_) => (),
+        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+      };
+      builder.manually_selected_only();
+    } else if sweep {
+      builder.drain_wallet();
+    }
+    recipients.iter().for_each(|(script_pubkey, amount)| {
+      builder.add_recipient(script_pubkey.clone(), *amount);
+    });
+    if let Some(script_pubkey) = drain_to {
+      builder.drain_to(script_pubkey);
     }
-    if policy_path.is_some() {
-      builder.policy_path(policy_path.clone().unwrap(), KeychainKind::External);
-      builder.policy_path(policy_path.unwrap(), KeychainKind::Internal);
+    if let Some(policy_path) = policy_path {
+      if !policy_path.external.is_empty() {
+        builder.policy_path(policy_path.external, KeychainKind::External);
+      }
+      if !policy_path.internal.is_empty() {
+        builder.policy_path(policy_path.internal, KeychainKind::Internal);
+      }
     }
 
     builder.enable_rbf();
@@ -460,8 +599,15 @@ pub fn get_weight(deposit_desc: &str, psbt: &str) -> Result<TransactionWeight, S
   };
 
   let transaction: Transaction = psbt_struct.extract_tx();
-  let desc = Descriptor::<String>::from_str(deposit_desc).unwrap();
-  let satisfaction_weight = desc.max_satisfaction_weight().unwrap();
+  let (deposit_desc, _) = split_descriptor(deposit_desc)?;
+  let desc = match Descriptor::<String>::from_str(&deposit_desc) {
+    Ok(result) => result,
+    Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+  };
+  let satisfaction_weight = match desc.max_satisfaction_weight() {
+    Ok(result) => result,
+    Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+  };
 
   Ok(TransactionWeight {
     weight: transaction.weight() + satisfaction_weight,
@@ -568,6 +714,159 @@ mod tests {
     assert!(structured_from_json[1].address=="gaffs");
     assert!(structured_from_json[0].amount.is_some())
   }
+  #[test]
+  fn test_plan_sweep_outputs(){
+    let first = Address::from_str("tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3").unwrap().script_pubkey();
+    let second = Address::from_str("mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt").unwrap().script_pubkey();
+    let third = Address::from_str("tb1qzdwqxt8l2s47vl4fp4ft6w67fcxel4qf5j96ld").unwrap().script_pubkey();
+
+    let outputs = TxOutput::vec_from_str("tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:5000").unwrap();
+    let (recipients, drain_to) = plan_outputs(&outputs, 300, 100_000, true).unwrap();
+    assert!(recipients.is_empty());
+    assert_eq!(drain_to.unwrap(), first);
+
+    let outputs = TxOutput::vec_from_str(
+      "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:5000,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:0"
+    ).unwrap();
+    let (recipients, drain_to) = plan_outputs(&outputs, 300, 100_000, true).unwrap();
+    assert_eq!(recipients, vec![(first.clone(), 5_000)]);
+    assert_eq!(drain_to.unwrap(), second);
+
+    let outputs = TxOutput::vec_from_str(
+      "tb1qzdwqxt8l2s47vl4fp4ft6w67fcxel4qf5j96ld:700,tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:25%,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:75%"
+    ).unwrap();
+    let (recipients, drain_to) = plan_outputs(&outputs, 300, 101_000, true).unwrap();
+    assert_eq!(recipients, vec![(third, 700), (first, 25_000)]);
+    assert_eq!(drain_to.unwrap(), second);
+
+    let outputs = TxOutput::vec_from_str(
+      "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:25%,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:70%"
+    ).unwrap();
+    assert_eq!(
+      plan_outputs(&outputs, 300, 100_000, true).err().unwrap().message,
+      "Sweep Percentages Must Total 100"
+    );
+    for invalid in ["NaN%", "inf%", "-inf%", "0%", "-10%", "100.5%"] {
+      let outputs = TxOutput::vec_from_str(&format!(
+        "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:{}",
+        invalid
+      )).unwrap();
+      assert_eq!(
+        plan_outputs(&outputs, 300, 100_000, true).err().unwrap().message,
+        "Invalid Sweep Percentage"
+      );
+    }
+    let outputs = TxOutput::vec_from_str(
+      "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:110%,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:-10%"
+    ).unwrap();
+    assert_eq!(
+      plan_outputs(&outputs, 300, 100_000, true).err().unwrap().message,
+      "Invalid Sweep Percentage"
+    );
+    let outputs = TxOutput::vec_from_str("mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:100%").unwrap();
+    let (recipients, drain_to) = plan_outputs(&outputs, 300, 100_000, true).unwrap();
+    assert!(recipients.is_empty());
+    assert_eq!(drain_to.unwrap(), second);
+    let outputs = TxOutput::vec_from_str(
+      "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:0.5%,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:99.5%"
+    ).unwrap();
+    assert_eq!(
+      plan_outputs(&outputs, 300, 20_000, true).err().unwrap().message,
+      "Sweep Share Below Dust Limit"
+    );
+    let outputs = TxOutput::vec_from_str(
+      "mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:99.5%,tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:0.5%"
+    ).unwrap();
+    assert_eq!(
+      plan_outputs(&outputs, 300, 20_000, true).err().unwrap().message,
+      "Sweep Share Below Dust Limit"
+    );
+    let outputs = TxOutput::vec_from_str(
+      "tb1q093gl5yxww0hlvlkajdmf8wh3a6rlvsdk9e6d3:5000,mkHS9ne12qx9pS9VojpwU5xtRd4T7X7ZUt:6000"
+    ).unwrap();
+    assert_eq!(
+      plan_outputs(&outputs, 300, 100_000, true).err().unwrap().message,
+      "Sweep Requires A Drain Output"
+    );
+    let (recipients, drain_to) = plan_outputs(&outputs, 300, 100_000, false).unwrap();
+    assert_eq!(recipients.len(), 2);
+    assert!(drain_to.is_none());
+  }
+  #[test]
+  fn test_outpoints_from_str(){
+    let utxos = "cb8d0d6a3a4e8a0f6d3b7a1ae9c0f3f4d7e1c0a1b2c3d4e5f60718293a4b5c6d:0,cb8d0d6a3a4e8a0f6d3b7a1ae9c0f3f4d7e1c0a1b2c3d4e5f60718293a4b5c6d:1";
+    let outpoints = outpoints_from_str(utxos).unwrap();
+    assert_eq!(outpoints.len(), 2);
+    assert_eq!(outpoints[1].vout, 1);
+    assert!(outpoints_from_str("").unwrap().is_empty());
+    assert_eq!(outpoints_from_str("notatxid:0").err().unwrap().message, "Invalid Utxo");
+  }
+  #[test]
+  fn test_keychain_policy_path(){
+    let single = KeychainPolicyPath::from_json_str("{\"id\":\"hgl9rs6e\",\"path\":[1]}").unwrap();
+    assert_eq!(single.external.get("hgl9rs6e"), Some(&vec![1]));
+    assert_eq!(single.internal, single.external);
+
+    let nodes = KeychainPolicyPath::from_json_str("{\"hgl9rs6e\":[1],\"9slxjlp2\":[0,1]}").unwrap();
+    assert_eq!(nodes.external.len(), 2);
+    assert_eq!(nodes.internal, nodes.external);
+
+    let keychains = KeychainPolicyPath::from_json_str(
+      "{\"external\":{\"hgl9rs6e\":[1]},\"internal\":{\"xyz\":[0]}}"
+    ).unwrap();
+    assert_eq!(keychains.external.get("hgl9rs6e"), Some(&vec![1]));
+    assert_eq!(keychains.internal.get("xyz"), Some(&vec![0]));
+
+    let external_only = KeychainPolicyPath::from_json_str("{\"external\":{\"hgl9rs6e\":[0]}}").unwrap();
+    assert_eq!(external_only.internal, external_only.external);
+
+    assert!(KeychainPolicyPath::from_json_str("").is_err());
+    assert!(KeychainPolicyPath::from_json_str("{\"id\":1}").is_err());
+  }
+
+  #[test]
+  fn test_nested_policy_path(){
+    use bdk::wallet::AddressIndex;
+    use crate::wallet::fixtures::{funded_wallet, FUNDED_AMOUNT};
+    use crate::wallet::policy::{self, ScriptType};
+
+    let alice_xprv = "[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49/*";
+    let escrow_xpub = "[66a0c105/84'/1'/5']tpubDCKvnVh6U56wTSUEJGamQzdb3ByAc6gTPbjxXQqts5Bf1dBMopknipUUSmAV3UuihKPTddruSZCiqhyiYyhFWhz62SAGuC3PYmtAafUuG6R/*";
+    let bob_xprv = "[a90a3a81/84'/0'/0']tprv8g3FKkLE9gRHDYeedikuNRXMhZyQ6bsgnMxYk8dRPKg15BCsimrbw2zjA97gwu4Brw9XtVVdgyuUSSZd7ckjSbbwpGjAyVjonCXGKg2gE2D/*";
+    // recovery by alice and either bob or a timelock, nested inside an or with the escrow
+    let descriptor = policy::compile(
+      &format!("or(pk({}),thresh(2,pk({}),pk({}),older(10)))", escrow_xpub, alice_xprv, bob_xprv),
+      ScriptType::WSH,
+    ).unwrap();
+    let tree = policy::tree(WalletConfig::new_offline(&descriptor, None).unwrap()).unwrap();
+    let config = WalletConfig::new_offline(&descriptor, None).unwrap();
+    let root = tree.external.unwrap().policy;
+    let recovery = serde_json::to_value(&root).unwrap()["items"][1]["id"].as_str().unwrap().to_string();
+    let timelock = serde_json::to_value(&root).unwrap()["items"][1]["items"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .position(|item| item["type"] == "RELATIVETIMELOCK")
+      .unwrap();
+
+    let wallet = funded_wallet(&config.deposit_desc, Some(&config.change_desc));
+    let address = wallet.get_address(AddressIndex::Peek(0)).unwrap();
+    let outputs = vec![TxOutput { address: address.to_string(), amount: None, percent: None }];
+
+    let mut paths = BTreeMap::new();
+    paths.insert(root.id.clone(), vec![1]);
+    let root_only = create_psbt(&wallet, &outputs, 1_000, FUNDED_AMOUNT, Some(KeychainPolicyPath::both(paths.clone())), true, None);
+    assert!(root_only.is_err());
+
+    let alice_index = if timelock == 0 { 1 } else { 0 };
+    let mut alice_and_timelock = vec![alice_index, timelock];
+    alice_and_timelock.sort_unstable();
+    paths.insert(recovery, alice_and_timelock);
+    let psbt = create_psbt(&wallet, &outputs, 1_000, FUNDED_AMOUNT, Some(KeychainPolicyPath::both(paths)), true, None).unwrap();
+    let psbt = PartiallySignedTransaction::from_str(&psbt.psbt).unwrap();
+    assert_eq!(psbt.unsigned_tx.input[0].sequence, 10);
+  }
+
   #[test]
   fn test_send() {
     let xkey = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
@@ -586,8 +885,9 @@ mod tests {
     let output = TxOutput {
       address: to.to_string(),
       amount: Some(amount),
+      percent: None,
     };
-    let psbt_origin = build(config, vec![output], fee_absolute, None, false);
+    let psbt_origin = build(config, vec![output], fee_absolute, None, false, None);
     
     let decoded = decode(Network::Testnet, &psbt_origin.clone().unwrap().psbt);
     println!("Decoded: {:#?}", decoded.clone().unwrap());
@@ -623,8 +923,9 @@ mod tests {
     let output = TxOutput {
       address: to.to_string(),
       amount: Some(amount),
+      percent: None,
     };
-    let psbt_origin = sqlite_build(config, vec![output.clone()], fee_absolute, None, false);
+    let psbt_origin = sqlite_build(config, vec![output.clone()], fee_absolute, None, false, None);
     assert!(psbt_origin.is_err());
 
     // TEST SYNCED
@@ -635,7 +936,7 @@ mod tests {
         status.unwrap()
     );
     let config = WalletConfig::new(&descriptor, DEFAULT_TESTNET_NODE, None,Some(db_path.clone())).unwrap();
-    let psbt_origin = sqlite_build(config, vec![output], fee_absolute, None, false);
+    let psbt_origin = sqlite_build(config, vec![output], fee_absolute, None, false, None);
 
     let config = WalletConfig::new(&descriptor, DEFAULT_TESTNET_NODE, None,Some(db_path.clone())).unwrap();
 
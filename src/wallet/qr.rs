@@ -0,0 +1,373 @@
+use crate::e::{ErrorKind, S5Error};
+use crate::key::bbqr::{self, BbqrEncoding, BbqrJoiner, FileType};
+use crate::key::encoding::Encoding;
+use crate::key::ur::{self, Cbor, UrDecoder, UrEncoder};
+use crate::wallet::descriptor;
+use crate::wallet::hardware;
+use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::base64;
+use bitcoin::consensus::deserialize;
+use bitcoin::Transaction;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+/// Fragment length used when none is given, small enough for a QR code most phone cameras read at a glance.
+pub const DEFAULT_FRAGMENT_LENGTH: usize = 200;
+/// BBQr part length (header included) used when none is given.
+pub const DEFAULT_BBQR_PART_LENGTH: usize = 600;
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UrParts {
+    pub ur_type: String,
+    pub seq_len: usize,
+    pub parts: Vec<String>,
+}
+impl UrParts {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UrScan {
+    pub ur_type: Option<String>,
+    pub received: usize,
+    pub expected: usize,
+    pub is_complete: bool,
+    pub psbt: Option<String>,
+    pub descriptors: Vec<String>,
+}
+impl UrScan {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BbqrParts {
+    pub encoding: String,
+    pub file_type: String,
+    pub parts: Vec<String>,
+}
+impl BbqrParts {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BbqrScan {
+    pub file_type: Option<String>,
+    pub received: usize,
+    pub expected: usize,
+    pub is_complete: bool,
+    pub psbt: Option<String>,
+    pub tx: Option<String>,
+    pub json: Option<String>,
+}
+impl BbqrScan {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+fn psbt_bytes(psbt: &str) -> Result<Vec<u8>, S5Error> {
+    let bytes = match base64::decode(psbt.trim()) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid PSBT")),
+    };
+    match deserialize::<PartiallySignedTransaction>(&bytes) {
+        Ok(_) => Ok(bytes),
+        Err(_) => Err(S5Error::new(ErrorKind::Input, "Invalid PSBT")),
+    }
+}
+
+/// Encodes a base64 PSBT as ur:crypto-psbt or a public descriptor as ur:crypto-output.
+/// Returns part_count parts (default twice the number of fragments, at least enough to carry the whole payload),
+/// to be shown in a loop as an animated QR code.
+pub fn encode(content: &str, max_fragment_length: usize, part_count: Option<usize>) -> Result<UrParts, S5Error> {
+    let content = content.trim();
+    let (ur_type, payload) = if content.contains('(') {
+        ("crypto-output", ur::encode_output(content)?.encode())
+    } else {
+        ("crypto-psbt", Cbor::Bytes(psbt_bytes(content)?).encode())
+    };
+    let mut encoder = UrEncoder::new(ur_type, &payload, max_fragment_length)?;
+    let seq_len = encoder.seq_len();
+    let count = match (seq_len, part_count) {
+        (1, _) => 1,
+        (_, Some(count)) => count.max(seq_len),
+        (_, None) => seq_len * 2,
+    };
+    Ok(UrParts {
+        ur_type: ur_type.to_string(),
+        seq_len,
+        parts: (0..count).map(|_| encoder.next_part()).collect(),
+    })
+}
+
+/// Decodes the UR parts scanned so far, in any order and with repeats.
+/// Until the payload is complete, only the progress is returned; scan more parts and call again with all of them.
+pub fn decode(parts: &[String]) -> Result<UrScan, S5Error> {
+    let mut decoder = UrDecoder::new();
+    for part in parts.iter().filter(|part| !part.trim().is_empty()) {
+        decoder.receive(part)?;
+    }
+    scan(&decoder)
+}
+
+/// Reports the progress of a decoder that receives the parts one at a time as they are scanned.
+/// A crypto-psbt completes into a base64 PSBT, a crypto-output or crypto-account into descriptors.
+pub fn scan(decoder: &UrDecoder) -> Result<UrScan, S5Error> {
+    let (received, expected) = decoder.progress();
+    let mut scan = UrScan {
+        ur_type: decoder.ur_type().cloned(),
+        received,
+        expected,
+        is_complete: decoder.is_complete(),
+        ..UrScan::default()
+    };
+    let (ur_type, payload) = match (decoder.ur_type(), decoder.payload()) {
+        (Some(ur_type), Some(payload)) => (ur_type.as_str(), payload),
+        (None, _) => return Err(S5Error::new(ErrorKind::Input, "No UR Parts")),
+        _ => return Ok(scan),
+    };
+    match ur_type {
+        "crypto-psbt" | "psbt" => match Cbor::decode(payload)? {
+            Cbor::Bytes(bytes) => {
+                deserialize::<PartiallySignedTransaction>(&bytes)
+                    .map_err(|_| S5Error::new(ErrorKind::Input, "Invalid PSBT"))?;
+                scan.psbt = Some(base64::encode(&bytes));
+            }
+            _ => return Err(S5Error::new(ErrorKind::Input, "Invalid PSBT")),
+        },
+        "crypto-output" | "output-descriptor" => {
+            let output = ur::output_descriptor(&Cbor::decode(payload)?)?;
+            scan.descriptors = vec![descriptor::with_checksum(&output)?.descriptor];
+        }
+        "crypto-account" | "account-descriptor" => {
+            scan.descriptors = hardware::from_ur_payload(ur_type, payload)?
+                .into_iter()
+                .filter_map(|key| key.descriptor)
+                .collect();
+        }
+        _ => return Err(S5Error::new(ErrorKind::Input, "Unsupported UR Type")),
+    }
+    Ok(scan)
+}
+
+fn file_type_name(file_type: FileType) -> &'static str {
+    match file_type {
+        FileType::Psbt => "psbt",
+        FileType::Transaction => "tx",
+        FileType::Json => "json",
+        FileType::Cbor => "cbor",
+        FileType::Binary => "binary",
+        FileType::Unicode => "text",
+    }
+}
+
+/// Splits a base64 PSBT, a hex transaction or a JSON document (e.g. a multisig setup) into BBQr parts for a Coldcard Q.
+/// Z (compressed) is the default encoding; it falls back to 2 (base32) when compression does not help.
+pub fn bbqr_encode(content: &str, encoding: &str, max_part_length: usize) -> Result<BbqrParts, S5Error> {
+    let content = content.trim();
+    let is_json = serde_json::from_str::<serde_json::Value>(content)
+        .is_ok_and(|value| value.is_object() || value.is_array());
+    let (file_type, data) = if is_json {
+        (FileType::Json, content.as_bytes().to_vec())
+    } else {
+        match Encoding::Hex.decode(content) {
+            Ok(bytes) if deserialize::<Transaction>(&bytes).is_ok() => (FileType::Transaction, bytes),
+            _ => (FileType::Psbt, psbt_bytes(content)?),
+        }
+    };
+    let parts = bbqr::split(&data, file_type, BbqrEncoding::from_str(encoding), max_part_length)?;
+    Ok(BbqrParts {
+        encoding: parts[0][2..3].to_string(),
+        file_type: file_type_name(file_type).to_string(),
+        parts,
+    })
+}
+
+/// Joins the BBQr parts scanned so far, in any order and with repeats.
+/// Until all parts are received, only the progress is returned.
+/// A complete PSBT is returned as base64, a transaction as hex and JSON as text.
+pub fn bbqr_decode(parts: &[String]) -> Result<BbqrScan, S5Error> {
+    let mut joiner = BbqrJoiner::new();
+    for part in parts.iter().filter(|part| !part.trim().is_empty()) {
+        joiner.receive(part)?;
+    }
+    let (received, expected) = joiner.progress();
+    if expected == 0 {
+        return Err(S5Error::new(ErrorKind::Input, "No BBQr Parts"));
+    }
+    let mut scan = BbqrScan {
+        received,
+        expected,
+        is_complete: joiner.is_complete(),
+        ..BbqrScan::default()
+    };
+    if !scan.is_complete {
+        return Ok(scan);
+    }
+    let (file_type, data) = joiner.join()?;
+    scan.file_type = Some(file_type_name(file_type).to_string());
+    match file_type {
+        FileType::Psbt => {
+            deserialize::<PartiallySignedTransaction>(&data)
+                .map_err(|_| S5Error::new(ErrorKind::Input, "Invalid PSBT"))?;
+            scan.psbt = Some(base64::encode(&data));
+        }
+        FileType::Transaction => {
+            deserialize::<Transaction>(&data)
+                .map_err(|_| S5Error::new(ErrorKind::Input, "Invalid Transaction"))?;
+            scan.tx = Some(Encoding::Hex.encode(&data));
+        }
+        FileType::Json => match String::from_utf8(data) {
+            Ok(json) => scan.json = Some(json),
+            Err(_) => return Err(S5Error::new(ErrorKind::Input, "Invalid JSON")),
+        },
+        _ => return Err(S5Error::new(ErrorKind::Input, "Unsupported BBQr File Type")),
+    }
+    Ok(scan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PSBT: &str = "cHNidP8BAHQBAAAAAf3cLERUN9+6X5+1yk3x9XzSCq1417WtB+gB5qNyj+xpAAAAAAD9////AnRxAQAAAAAAFgAUVyorkNVSCsiE4/7OspP52IwquzqIEwAAAAAAABl2qRQ0Sg9IyhUOwrkDgXZgubaLE6ZwJoisAAAAAAABAN4CAAAAAAEByvn9X3PvFqemGsrTv8ivAO07IOeRhBz7J0huqXJLfVgBAAAAAP7///8CoIYBAAAAAAAWABQTXAMs/1Qr5n6pDVK9O15ODZ/UCVZWjQAAAAAAFgAUIixaISTPlO8fwyT3hCL+An5+Km4CRzBEAiBFsQJfBur3eQgO5Vw+EvEgr2CagcVGXw9oYw3FOaMSSgIgch0CV+W3oRCKNBwxqiqIK0C5b1TsGk32HvNM+4Z7IksBIQNP/rsBHKbA98977TzmriFrOuO8hQjNg4ON3goI9/Uwjp0BIAABAR+ghgEAAAAAABYAFBNcAyz/VCvmfqkNUr07Xk4Nn9QJIgYD9WhlKKSeNh6567KTmyKrlitDWZOz/+mms7emVsWjGTsY230ltVQAAIABAACABgAAgAAAAAABAAAAACICAgHPrE7CShQkK90ApPF8xdr+8o7T/sHggOlZNOHIUft/GNt9JbVUAACAAQAAgAYAAIABAAAAAQAAAAAA";
+    // keys of the BIP32 test vector 1 seed, whose origins match their depth and child number
+    const WSH: &str = "wsh(sortedmulti(2,[3442193e/84'/1'/0']tpubDDNRbZGvdA33cgpY5uy2mmphT7sK4uciRjcQScSd64S5KRyZDxHcPuzs24or84Hywugb2JbEEt2jWH8fduiN9cmZzkSj8sSSx6txXkhXyZs/0/*,[3442193e/49'/1'/0']tpubDCoQ5oeS57aZM35CpZuV5WagduPC7zzwiCm52q2oa1jHvuuwSUWxX1smc4G1zg1jB57HzqyehjSGdAZY9cAuProNxMfxqHFh6JWq5tvoxh9/0/*))";
+
+    #[test]
+    fn test_psbt_qr() {
+        let encoded = encode(PSBT, 60, None).unwrap();
+        assert_eq!(encoded.ur_type, "crypto-psbt");
+        assert!(encoded.seq_len > 1);
+        assert_eq!(encoded.parts.len(), encoded.seq_len * 2);
+
+        let scanned: Vec<String> = encoded.parts[1..encoded.seq_len].to_vec();
+        let partial = decode(&scanned).unwrap();
+        assert!(!partial.is_complete);
+        assert_eq!(partial.expected, encoded.seq_len);
+        assert_eq!(partial.psbt, None);
+
+        let mut scanned = scanned;
+        scanned.extend(encoded.parts[encoded.seq_len..].iter().cloned());
+        let complete = decode(&scanned).unwrap();
+        assert!(complete.is_complete);
+        assert_eq!(complete.psbt, Some(PSBT.to_string()));
+
+        let single = encode(PSBT, 2000, Some(10)).unwrap();
+        assert_eq!(single.parts.len(), 1);
+        assert!(single.parts[0].starts_with("ur:crypto-psbt/"));
+        assert_eq!(decode(&single.parts).unwrap().psbt, Some(PSBT.to_string()));
+
+        let mut decoder = UrDecoder::new();
+        for part in encoded.parts.iter().skip(1) {
+            decoder.receive(part).unwrap();
+            if decoder.is_complete() {
+                break;
+            }
+        }
+        assert_eq!(scan(&decoder).unwrap().psbt, Some(PSBT.to_string()));
+
+        assert_eq!(encode("notapsbt", 60, None).err().unwrap().message, "Invalid PSBT");
+        assert_eq!(decode(&[]).err().unwrap().message, "No UR Parts");
+    }
+
+    #[test]
+    fn test_descriptor_qr() {
+        let encoded = encode(WSH, DEFAULT_FRAGMENT_LENGTH, None).unwrap();
+        assert_eq!(encoded.ur_type, "crypto-output");
+        let scan = decode(&encoded.parts).unwrap();
+        assert_eq!(scan.descriptors, vec![descriptor::with_checksum(WSH).unwrap().descriptor]);
+
+        let single = "wpkh([3442193e/84'/1'/0']tpubDDNRbZGvdA33cgpY5uy2mmphT7sK4uciRjcQScSd64S5KRyZDxHcPuzs24or84Hywugb2JbEEt2jWH8fduiN9cmZzkSj8sSSx6txXkhXyZs/0/*)";
+        let encoded = encode(single, DEFAULT_FRAGMENT_LENGTH, None).unwrap();
+        assert_eq!(encoded.parts.len(), 1);
+        assert_eq!(
+            decode(&encoded.parts).unwrap().descriptors,
+            vec![descriptor::with_checksum(single).unwrap().descriptor]
+        );
+
+        assert_eq!(
+            encode("wsh(and_v(v:pk(02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c),older(144)))", 200, None)
+                .err()
+                .unwrap()
+                .message,
+            "Unsupported UR Output"
+        );
+    }
+
+    #[test]
+    fn test_bbqr() {
+        let encoded = bbqr_encode(PSBT, "Z", 100).unwrap();
+        assert_eq!(encoded.file_type, "psbt");
+        assert!(encoded.parts.len() > 1);
+        assert!(encoded.parts.iter().all(|part| part.starts_with(&format!("B${}P", encoded.encoding))));
+        let partial = bbqr_decode(&encoded.parts[1..]).unwrap();
+        assert!(!partial.is_complete);
+        assert_eq!((partial.received, partial.expected), (encoded.parts.len() - 1, encoded.parts.len()));
+        let mut scanned: Vec<String> = encoded.parts.iter().rev().cloned().collect();
+        scanned.push(encoded.parts[0].clone());
+        assert_eq!(bbqr_decode(&scanned).unwrap().psbt, Some(PSBT.to_string()));
+
+        let psbt: PartiallySignedTransaction = deserialize(&base64::decode(PSBT).unwrap()).unwrap();
+        let tx = Encoding::Hex.encode(&bitcoin::consensus::serialize(&psbt.inputs[0].non_witness_utxo.clone().unwrap()));
+        let encoded = bbqr_encode(&tx, "H", DEFAULT_BBQR_PART_LENGTH).unwrap();
+        assert_eq!((encoded.encoding.as_str(), encoded.file_type.as_str()), ("H", "tx"));
+        assert_eq!(encoded.parts, vec![format!("B$HT0100{}", tx.to_uppercase())]);
+        assert_eq!(bbqr_decode(&encoded.parts).unwrap().tx, Some(tx));
+
+        let json = r#"{"xfp": "3442193E", "p2wsh_deriv": "m/48'/1'/0'/2'", "p2wsh": "tpubDEJcADF9D7M6cTDQLLxdDiNHXXEoLZQ1kxpmbtRg6N3mBgLzqUoimB7PfFjWhGvYhX3E7nTvTBMVwFzcbVKtjX8aETFG73A3VxJdtu1ZSv2"}"#;
+        let encoded = bbqr_encode(json, "2", DEFAULT_BBQR_PART_LENGTH).unwrap();
+        assert_eq!((encoded.encoding.as_str(), encoded.file_type.as_str()), ("2", "json"));
+        assert_eq!(bbqr_decode(&encoded.parts).unwrap().json, Some(json.to_string()));
+
+        assert_eq!(bbqr_encode("notapsbt", "Z", 100).err().unwrap().message, "Invalid PSBT");
+        assert_eq!(bbqr_decode(&[]).err().unwrap().message, "No BBQr Parts");
+    }
+}
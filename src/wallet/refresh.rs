@@ -0,0 +1,430 @@
+use crate::config::WalletConfig;
+use crate::e::{ErrorKind, S5Error};
+use crate::wallet::custom::TimelockType;
+use crate::wallet::descriptor;
+use bdk::blockchain::GetHeight;
+use bdk::database::{BatchDatabase, MemoryDatabase};
+use bdk::descriptor::policy::{Policy, SatisfiableItem};
+use bdk::wallet::AddressIndex;
+use bdk::{FeeRate, KeychainKind, SignOptions, SyncOptions, Wallet};
+use bitcoin::{OutPoint, Script};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RefreshUtxo {
+    pub outpoint: String,
+    pub value: u64,
+    pub confirmation_height: Option<u32>,
+    pub blocks_remaining: u32,
+    pub is_due: bool,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelockRefresh {
+    pub timelock_type: String,
+    pub timelock: u32,
+    pub current_height: u32,
+    pub next_descriptor: Option<String>,
+    pub address: String,
+    pub utxos: Vec<RefreshUtxo>,
+    pub psbt: Option<String>,
+    pub is_finalized: bool,
+}
+impl TimelockRefresh {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Gets the single block based timelock of a descriptor.
+pub fn timelock(descriptor: &str) -> Result<(TimelockType, u32), S5Error> {
+    let analysis = descriptor::analyze(WalletConfig::new_offline(descriptor, None)?)?;
+    let mut timelocks = analysis.timelocks;
+    timelocks.dedup();
+    let timelock = match timelocks.len() {
+        1 => timelocks.remove(0),
+        0 => return Err(S5Error::new(ErrorKind::Input, "Descriptor Has No Timelock")),
+        _ => return Err(S5Error::new(ErrorKind::Input, "Descriptor Has Multiple Timelocks")),
+    };
+    match (timelock.kind.as_str(), timelock.unit.as_str()) {
+        ("absolute", "height") => Ok((TimelockType::Absolute, timelock.value)),
+        ("relative", "blocks") => Ok((TimelockType::Relative, timelock.value)),
+        _ => Err(S5Error::new(
+            ErrorKind::Input,
+            "Time Based Timelocks Cannot Be Refreshed",
+        )),
+    }
+}
+
+/// Replaces the after(height) of a descriptor (or a deposit and change pair) with the next epoch's height.
+/// Keys and the rest of the policy are kept, so the owner can keep spending with the same backups.
+pub fn next_epoch(descriptor: &str, next_height: u32) -> Result<String, S5Error> {
+    let parts = descriptor.split_whitespace().collect::<Vec<&str>>();
+    let (timelock_type, height) = timelock(parts.first().copied().unwrap_or(""))?;
+    if timelock_type != TimelockType::Absolute {
+        return Err(S5Error::new(
+            ErrorKind::Input,
+            "Only Absolute Timelocks Have An Epoch",
+        ));
+    }
+    if next_height <= height || next_height >= 500_000_000 {
+        return Err(S5Error::new(ErrorKind::Input, "Invalid Next Timelock"));
+    }
+
+    let mut next: Vec<String> = vec![];
+    for part in parts {
+        let body = descriptor::verify(part)?.replace(
+            &format!("after({})", height),
+            &format!("after({})", next_height),
+        );
+        next.push(descriptor::with_checksum(&body)?.descriptor);
+    }
+    Ok(next.join(" "))
+}
+
+/// Gets the blocks left until the heir can spend each utxo and marks the ones within margin_blocks as due.
+/// An absolute timelock matures for all utxos at once. A relative timelock counts from each confirmation
+/// and does not start before an utxo confirms.
+pub fn due_utxos(
+    timelock_type: TimelockType,
+    timelock: u32,
+    current_height: u32,
+    margin_blocks: u32,
+    utxos: &[(OutPoint, u64, Option<u32>)],
+) -> Vec<RefreshUtxo> {
+    utxos
+        .iter()
+        .map(|(outpoint, value, confirmation_height)| {
+            let blocks_remaining = match (timelock_type, confirmation_height) {
+                (TimelockType::Absolute, _) => timelock.saturating_sub(current_height),
+                (TimelockType::Relative, Some(height)) => {
+                    (height + timelock).saturating_sub(current_height)
+                }
+                (TimelockType::Relative, None) => timelock,
+            };
+            RefreshUtxo {
+                outpoint: outpoint.to_string(),
+                value: *value,
+                confirmation_height: *confirmation_height,
+                blocks_remaining,
+                is_due: blocks_remaining <= margin_blocks,
+            }
+        })
+        .collect()
+}
+
+fn has_timelock(policy: &Policy) -> bool {
+    match &policy.item {
+        SatisfiableItem::AbsoluteTimelock { .. } | SatisfiableItem::RelativeTimelock { .. } => true,
+        SatisfiableItem::Thresh { items, .. } => items.iter().any(has_timelock),
+        _ => false,
+    }
+}
+
+/// Selects the first branch of the root policy without a timelock, which is the owner's branch of an inheritance wallet.
+fn owner_path<D: BatchDatabase>(
+    wallet: &Wallet<D>,
+    keychain: KeychainKind,
+) -> Result<BTreeMap<String, Vec<usize>>, S5Error> {
+    let policy = match wallet.policies(keychain) {
+        Ok(Some(policy)) => policy,
+        Ok(None) => return Ok(BTreeMap::new()),
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    if !policy.requires_path() {
+        return Ok(BTreeMap::new());
+    }
+    let index = match &policy.item {
+        SatisfiableItem::Thresh { items, .. } => items.iter().position(|item| !has_timelock(item)),
+        _ => None,
+    };
+    match index {
+        Some(index) => Ok(BTreeMap::from([(policy.id, vec![index])])),
+        None => Err(S5Error::new(
+            ErrorKind::Wallet,
+            "Descriptor Has No Path Without A Timelock",
+        )),
+    }
+}
+
+/// Builds and signs a psbt moving the due utxos of the wallet to the given script through the owner's branch.
+/// Returns no psbt if nothing is due.
+fn build_refresh<D: BatchDatabase>(
+    wallet: &Wallet<D>,
+    utxos: &[RefreshUtxo],
+    to: &Script,
+    fee_rate: f32,
+) -> Result<Option<(String, bool)>, S5Error> {
+    let outpoints = utxos
+        .iter()
+        .filter(|utxo| utxo.is_due)
+        .map(|utxo| utxo.outpoint.parse::<OutPoint>())
+        .collect::<Result<Vec<OutPoint>, _>>()
+        .map_err(|_| S5Error::new(ErrorKind::Internal, "Invalid Outpoint"))?;
+    if outpoints.is_empty() {
+        return Ok(None);
+    }
+    let external = owner_path(wallet, KeychainKind::External)?;
+    let internal = owner_path(wallet, KeychainKind::Internal)?;
+
+    let (mut psbt, _) = {
+        let mut builder = wallet.build_tx();
+        match builder.add_utxos(&outpoints) {
+            Ok(_) => (),
+            Err(e) => return Err(S5Error::new(ErrorKind::Wallet, &e.to_string())),
+        };
+        builder
+            .manually_selected_only()
+            .drain_to(to.clone())
+            .fee_rate(FeeRate::from_sat_per_vb(fee_rate))
+            .enable_rbf();
+        if !external.is_empty() {
+            builder.policy_path(external, KeychainKind::External);
+        }
+        if !internal.is_empty() {
+            builder.policy_path(internal, KeychainKind::Internal);
+        }
+        match builder.finish() {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Wallet, &e.to_string())),
+        }
+    };
+    let finalized = match wallet.sign(&mut psbt, SignOptions::default()) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Sign-Error")),
+    };
+    Ok(Some((psbt.to_string(), finalized)))
+}
+
+/// Checks a timelocked wallet for utxos the heir can spend within margin_blocks and builds a psbt
+/// spending them with the owner's keys before that happens.
+/// With after(height), the due utxos move to the first unused address of the next epoch descriptor
+/// (same keys, after(next_height)), which the owner must back up and use from then on. With older(blocks), they move to a new address
+/// of the same wallet, restarting the delay.
+/// The psbt is signed if the config holds the owner's private key.
+pub fn refresh(
+    config: WalletConfig,
+    next_height: Option<u32>,
+    margin_blocks: u32,
+    fee_rate: f32,
+) -> Result<TimelockRefresh, S5Error> {
+    let (timelock_type, timelock) = timelock(&config.deposit_desc)?;
+    let next_descriptor = match (timelock_type, next_height) {
+        (TimelockType::Absolute, Some(next_height)) => Some(next_epoch(
+            &format!("{} {}", config.deposit_desc, config.change_desc),
+            next_height,
+        )?),
+        (TimelockType::Absolute, None) => {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                "Absolute Refresh Requires Next Height",
+            ))
+        }
+        (TimelockType::Relative, _) => None,
+    };
+    let client = match config.client {
+        Some(ref client) => client,
+        None => return Err(S5Error::new(ErrorKind::Input, "Refresh Requires A Node")),
+    };
+
+    let wallet = match Wallet::new(
+        &config.deposit_desc,
+        Some(&config.change_desc),
+        config.network,
+        MemoryDatabase::default(),
+    ) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Initialization")),
+    };
+    match wallet.sync(client, SyncOptions::default()) {
+        Ok(_) => (),
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Sync")),
+    };
+    let current_height = match client.get_height() {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+    };
+    let mut unspent = vec![];
+    match wallet.list_unspent() {
+        Ok(utxos) => {
+            for utxo in utxos {
+                let confirmation_height = match wallet.get_tx(&utxo.outpoint.txid, false) {
+                    Ok(Some(tx)) => tx.confirmation_time.map(|time| time.height),
+                    _ => None,
+                };
+                unspent.push((utxo.outpoint, utxo.txout.value, confirmation_height));
+            }
+        }
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    let utxos = due_utxos(timelock_type, timelock, current_height, margin_blocks, &unspent);
+
+    let address = match &next_descriptor {
+        Some(descriptor) => {
+            let next_config = WalletConfig::new_offline(descriptor, None)?;
+            match Wallet::new(
+                &next_config.deposit_desc,
+                Some(&next_config.change_desc),
+                next_config.network,
+                MemoryDatabase::default(),
+            ) {
+                Ok(next_wallet) => {
+                    match next_wallet.sync(client, SyncOptions::default()) {
+                        Ok(_) => (),
+                        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Sync")),
+                    };
+                    next_wallet.get_address(AddressIndex::LastUnused)
+                }
+                Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+            }
+        }
+        None => wallet.get_address(AddressIndex::LastUnused),
+    };
+    let address = match address {
+        Ok(result) => result.address,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+
+    let (psbt, is_finalized) =
+        match build_refresh(&wallet, &utxos, &address.script_pubkey(), fee_rate)? {
+            Some((psbt, is_finalized)) => (Some(psbt), is_finalized),
+            None => (None, false),
+        };
+    Ok(TimelockRefresh {
+        timelock_type: timelock_type.to_string(),
+        timelock,
+        current_height,
+        next_descriptor,
+        address: address.to_string(),
+        utxos,
+        psbt,
+        is_finalized,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::fixtures::{funded_wallet, FUNDED_AMOUNT};
+    use crate::wallet::policy::{self, ScriptType};
+    use bitcoin::network::constants::Network;
+
+    const A_XPUB: &str = "[c2cb6b81/84h/1h/0h]tpubDDVqM1YixTfp9yZvxxAt5m6ybA48yeeYWdd3KndqrtVRBiPD2PKYMELSTq1JA3qPo4LXimT2VvBCure4JTTvgR3grz8nBY655bgTSCncmSg/*";
+    const A_XPRV: &str = "[c2cb6b81/84h/1h/0h]tprv8gooCbWUp5z9GWY95JWHgMSs28YCpKTdwL2G3GbYSch2ME8SPzVxAjiaHgCDdyHBLGkUB7Nh5U66G5uLwykSAvECA78Bx6T8mS3wVgQMAGf/*";
+    const B_XPUB: &str = "[9eee95d3/84h/1h/0h]tpubDDmbx25X8ThKHXgMnAj4Tbka85SiisU2KapKp42foekYKYvJ7AiB7MWV5G9wZKpax4fbqaHhuL1MShri2ACA9UDeSmDXyajHQ5ohf8RiUW6/*";
+
+    fn inheritance_descriptor(owner: &str, timelock: &str) -> String {
+        policy::compile(
+            &format!("or(pk({}),and(pk({}),{}))", owner, B_XPUB, timelock),
+            ScriptType::WSH,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_next_epoch() {
+        let descriptor = inheritance_descriptor(A_XPUB, "after(600000)");
+        assert_eq!(timelock(&descriptor).unwrap(), (TimelockType::Absolute, 600_000));
+
+        let next = next_epoch(&descriptor, 650_000).unwrap();
+        assert!(next.contains("after(650000)"));
+        assert!(!next.contains("after(600000)"));
+        assert_eq!(timelock(&next).unwrap(), (TimelockType::Absolute, 650_000));
+        assert_eq!(next, inheritance_descriptor(A_XPUB, "after(650000)"));
+
+        let pair = format!(
+            "{} {}",
+            descriptor.split('#').next().unwrap().replace("/*", "/0/*"),
+            descriptor.split('#').next().unwrap().replace("/*", "/1/*")
+        );
+        let next_pair = next_epoch(&pair, 650_000).unwrap();
+        assert_eq!(next_pair.split_whitespace().count(), 2);
+        assert!(WalletConfig::new_offline(&next_pair, None).is_ok());
+
+        assert_eq!(
+            next_epoch(&descriptor, 600_000).err().unwrap().message,
+            "Invalid Next Timelock"
+        );
+        let relative = inheritance_descriptor(A_XPUB, "older(4320)");
+        assert_eq!(timelock(&relative).unwrap(), (TimelockType::Relative, 4320));
+        assert_eq!(
+            next_epoch(&relative, 650_000).err().unwrap().message,
+            "Only Absolute Timelocks Have An Epoch"
+        );
+        assert_eq!(
+            timelock(&format!("wpkh({})", A_XPUB)).err().unwrap().message,
+            "Descriptor Has No Timelock"
+        );
+    }
+
+    #[test]
+    fn test_due_utxos() {
+        let outpoint = |vout| OutPoint::new(Default::default(), vout);
+        let utxos = vec![
+            (outpoint(0), 10_000, Some(595_000)),
+            (outpoint(1), 20_000, None),
+        ];
+
+        let absolute = due_utxos(TimelockType::Absolute, 600_000, 597_000, 2_016, &utxos);
+        assert!(absolute.iter().all(|utxo| utxo.blocks_remaining == 3_000 && !utxo.is_due));
+        let absolute = due_utxos(TimelockType::Absolute, 600_000, 598_000, 2_016, &utxos);
+        assert!(absolute.iter().all(|utxo| utxo.is_due));
+        let matured = due_utxos(TimelockType::Absolute, 600_000, 600_100, 0, &utxos);
+        assert!(matured.iter().all(|utxo| utxo.blocks_remaining == 0 && utxo.is_due));
+
+        let relative = due_utxos(TimelockType::Relative, 4_320, 599_000, 500, &utxos);
+        assert_eq!(relative[0].blocks_remaining, 320);
+        assert!(relative[0].is_due);
+        assert_eq!(relative[1].blocks_remaining, 4_320);
+        assert!(!relative[1].is_due);
+        assert_eq!(relative[0].confirmation_height, Some(595_000));
+    }
+
+    #[test]
+    fn test_build_refresh() {
+        let descriptor = inheritance_descriptor(A_XPRV, "after(600000)");
+        let wallet = funded_wallet(&descriptor, None);
+        let funding = wallet.list_unspent().unwrap()[0].outpoint;
+
+        let next = next_epoch(&descriptor, 650_000).unwrap();
+        let next_address = Wallet::new(&next, None, Network::Testnet, MemoryDatabase::default())
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .unwrap();
+        let unspent = vec![(funding, FUNDED_AMOUNT, Some(100))];
+
+        let not_due = due_utxos(TimelockType::Absolute, 600_000, 590_000, 1_000, &unspent);
+        assert!(build_refresh(&wallet, &not_due, &next_address.script_pubkey(), 1.0)
+            .unwrap()
+            .is_none());
+
+        let due = due_utxos(TimelockType::Absolute, 600_000, 599_500, 1_000, &unspent);
+        let (psbt, is_finalized) = build_refresh(&wallet, &due, &next_address.script_pubkey(), 1.0)
+            .unwrap()
+            .unwrap();
+        assert!(is_finalized);
+        let psbt = psbt
+            .parse::<bitcoin::util::psbt::PartiallySignedTransaction>()
+            .unwrap();
+        assert_eq!(psbt.unsigned_tx.output.len(), 1);
+        assert_eq!(
+            psbt.unsigned_tx.output[0].script_pubkey,
+            next_address.script_pubkey()
+        );
+        assert!(psbt.unsigned_tx.lock_time < 600_000);
+    }
+}
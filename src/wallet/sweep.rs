@@ -0,0 +1,286 @@
+use crate::config::{split_descriptor, WalletConfig};
+use crate::e::{ErrorKind, S5Error};
+use bdk::blockchain::AnyBlockchain;
+use bdk::database::MemoryDatabase;
+use bdk::descriptor::Descriptor;
+use bdk::miniscript::descriptor::DescriptorSecretKey;
+use bdk::wallet::AddressIndex;
+use bdk::{FeeRate, SignOptions, SyncOptions, Wallet};
+use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::PrivateKey;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+/// A descriptor to scan for funds when sweeping an external key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepCandidate {
+    pub script_type: String,
+    pub descriptor: String,
+    pub network: Network,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SweepTx {
+    pub script_type: String,
+    pub balance: u64,
+    pub amount: u64,
+    pub fee: u64,
+    pub psbt: String,
+    pub is_finalized: bool,
+}
+
+/// FFI Output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeySweep {
+    pub address: String,
+    pub txs: Vec<SweepTx>,
+}
+impl KeySweep {
+    pub fn c_stringify(&self) -> *mut c_char {
+        let stringified = match serde_json::to_string(self) {
+            Ok(result) => result,
+            Err(_) => {
+                return CString::new("Error:JSON Stringify Failed. BAD NEWS! Contact Support.")
+                    .unwrap()
+                    .into_raw()
+            }
+        };
+
+        CString::new(stringified).unwrap().into_raw()
+    }
+}
+
+/// Gets the descriptors to scan for a WIF, xprv or a descriptor with private keys.
+/// A WIF is checked as pkh, sh-wpkh, wpkh and tr (uncompressed keys only as pkh).
+/// A master xprv is checked at the BIP44/49/84/86 account paths, any other xprv directly.
+pub fn candidates(key: &str) -> Result<Vec<SweepCandidate>, S5Error> {
+    let key = key.trim();
+    let candidate = |script_type: &str, descriptor: String, network: Network| SweepCandidate {
+        script_type: script_type.to_string(),
+        descriptor,
+        network,
+    };
+
+    if key.contains('(') {
+        let secp = Secp256k1::new();
+        let (_, keymap) = match Descriptor::parse_descriptor(&secp, key) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let network = match keymap.values().next() {
+            Some(DescriptorSecretKey::SinglePriv(single)) => single.key.network,
+            Some(DescriptorSecretKey::XPrv(xprv)) => xprv.xkey.network,
+            None => {
+                return Err(S5Error::new(
+                    ErrorKind::Key,
+                    "Descriptor Has No Private Keys",
+                ))
+            }
+        };
+        return Ok(vec![candidate("descriptor", key.to_string(), network)]);
+    }
+
+    if let Ok(private_key) = PrivateKey::from_wif(key) {
+        let network = private_key.network;
+        if !private_key.compressed {
+            return Ok(vec![candidate("pkh", format!("pkh({})", key), network)]);
+        }
+        return Ok(vec![
+            candidate("pkh", format!("pkh({})", key), network),
+            candidate("sh-wpkh", format!("sh(wpkh({}))", key), network),
+            candidate("wpkh", format!("wpkh({})", key), network),
+            candidate("tr", format!("tr({})", key), network),
+        ]);
+    }
+
+    let xprv = match ExtendedPrivKey::from_str(key) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Key, "Invalid Private Key")),
+    };
+    let network = xprv.network;
+    if xprv.depth == 0 {
+        let coin = match network {
+            Network::Bitcoin => 0,
+            _ => 1,
+        };
+        Ok(vec![
+            candidate("pkh", format!("pkh({}/44'/{}'/0'/*)", key, coin), network),
+            candidate("sh-wpkh", format!("sh(wpkh({}/49'/{}'/0'/*))", key, coin), network),
+            candidate("wpkh", format!("wpkh({}/84'/{}'/0'/*)", key, coin), network),
+            candidate("tr", format!("tr({}/86'/{}'/0'/*)", key, coin), network),
+        ])
+    } else {
+        Ok(vec![
+            candidate("pkh", format!("pkh({}/*)", key), network),
+            candidate("sh-wpkh", format!("sh(wpkh({}/*))", key), network),
+            candidate("wpkh", format!("wpkh({}/*)", key), network),
+            candidate("tr", format!("tr({}/*)", key), network),
+        ])
+    }
+}
+
+fn build_sweep(
+    client: &AnyBlockchain,
+    candidate: &SweepCandidate,
+    to: &bitcoin::Script,
+    fee_rate: f32,
+) -> Result<Option<SweepTx>, S5Error> {
+    let (deposit_desc, change_desc) = split_descriptor(&candidate.descriptor)?;
+    let change_desc = if change_desc == deposit_desc {
+        None
+    } else {
+        Some(change_desc)
+    };
+    let wallet = match Wallet::new(
+        &deposit_desc,
+        change_desc.as_ref(),
+        candidate.network,
+        MemoryDatabase::default(),
+    ) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    match wallet.sync(client, SyncOptions::default()) {
+        Ok(_) => (),
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Sync")),
+    };
+    let balance = match wallet.get_balance() {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+    if balance == 0 {
+        return Ok(None);
+    }
+
+    let (mut psbt, details) = {
+        let mut builder = wallet.build_tx();
+        builder
+            .drain_wallet()
+            .drain_to(to.clone())
+            .fee_rate(FeeRate::from_sat_per_vb(fee_rate))
+            .enable_rbf();
+        match builder.finish() {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Wallet, &e.to_string())),
+        }
+    };
+    let finalized = match wallet.sign(&mut psbt, SignOptions::default()) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Sign-Error")),
+    };
+
+    let fee = details.fee.unwrap_or(0);
+    Ok(Some(SweepTx {
+        script_type: candidate.script_type.clone(),
+        balance,
+        amount: balance.saturating_sub(fee),
+        fee,
+        psbt: psbt.to_string(),
+        is_finalized: finalized,
+    }))
+}
+
+/// Scans an external private key for funds and builds signed txs draining them
+/// into the next unused deposit address of the main wallet.
+/// One tx is built per funded script type. Broadcast each with psbt::broadcast.
+pub fn sweep(config: WalletConfig, key: &str, fee_rate: f32) -> Result<KeySweep, S5Error> {
+    let candidates = candidates(key)?;
+    if candidates.iter().any(|candidate| candidate.network != config.network) {
+        return Err(S5Error::new(ErrorKind::Input, "Key Network Mismatch"));
+    }
+    let client = match config.client {
+        Some(ref client) => client,
+        None => return Err(S5Error::new(ErrorKind::Input, "Sweep Requires A Node")),
+    };
+
+    let wallet = match Wallet::new(
+        &config.deposit_desc,
+        Some(&config.change_desc),
+        config.network,
+        MemoryDatabase::default(),
+    ) {
+        Ok(result) => result,
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Initialization")),
+    };
+    match wallet.sync(client, SyncOptions::default()) {
+        Ok(_) => (),
+        Err(_) => return Err(S5Error::new(ErrorKind::Internal, "Wallet-Sync")),
+    };
+    let address = match wallet.get_address(AddressIndex::LastUnused) {
+        Ok(result) => result.address,
+        Err(e) => return Err(S5Error::new(ErrorKind::Internal, &e.to_string())),
+    };
+
+    let mut txs: Vec<SweepTx> = vec![];
+    for candidate in candidates.iter() {
+        if let Some(tx) = build_sweep(client, candidate, &address.script_pubkey(), fee_rate)? {
+            txs.push(tx);
+        }
+    }
+    if txs.is_empty() {
+        return Err(S5Error::new(ErrorKind::Wallet, "No Funds Found"));
+    }
+
+    Ok(KeySweep {
+        address: address.to_string(),
+        txs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let secret = [1u8; 32];
+        let wif = PrivateKey::from_slice(&secret, Network::Testnet)
+            .unwrap()
+            .to_wif();
+        let found = candidates(&wif).unwrap();
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[1].descriptor, format!("sh(wpkh({}))", wif));
+        assert_eq!(found[3].script_type, "tr");
+        assert_eq!(found[0].network, Network::Testnet);
+        for candidate in found {
+            assert!(Wallet::new(
+                &candidate.descriptor,
+                None,
+                candidate.network,
+                MemoryDatabase::default()
+            )
+            .is_ok());
+        }
+
+        let mut uncompressed = PrivateKey::from_slice(&secret, Network::Testnet).unwrap();
+        uncompressed.compressed = false;
+        let found = candidates(&uncompressed.to_wif()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].script_type, "pkh");
+
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &secret).unwrap();
+        let found = candidates(&master.to_string()).unwrap();
+        assert_eq!(found[2].descriptor, format!("wpkh({}/84'/0'/0'/*)", master));
+        assert_eq!(found[2].network, Network::Bitcoin);
+
+        let xprv = "[db7d25b5/84'/1'/6']tprv8fWev2sCuSkVWYoNUUSEuqLkmmfiZaVtgxosS5jRE9fw5ejL2odsajv1QyiLrPri3ppgyta6dsFaoDVCF4ZdEAR6qqY4tnaosujsPzLxB49";
+        let found = candidates(&format!("wpkh({}/*)", xprv)).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].network, Network::Testnet);
+
+        let xpub = "[db7d25b5/84'/1'/6']tpubDCCh4SuT3pSAQ1qAN86qKEzsLoBeiugoGGQeibmieRUKv8z6fCTTmEXsb9yeueBkUWjGVzJr91bCzeCNShorbBqjZV4WRGjz3CrJsCboXUe";
+        assert_eq!(
+            candidates(&format!("wpkh({}/*)", xpub)).err().unwrap().message,
+            "Descriptor Has No Private Keys"
+        );
+        assert_eq!(
+            candidates("notakey").err().unwrap().message,
+            "Invalid Private Key"
+        );
+    }
+}